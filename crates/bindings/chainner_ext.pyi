@@ -8,13 +8,21 @@ import numpy as np
 # pylint: disable=unused-argument,missing-class-docstring,missing-function-docstring
 
 def fill_alpha_fragment_blur(
-    img: np.ndarray, threshold: float, iterations: int, fragment_count: int
+    img: np.ndarray,
+    threshold: float,
+    iterations: int,
+    fragment_count: int,
+    inplace: bool = False,
 ) -> np.ndarray: ...
 def fill_alpha_extend_color(
-    img: np.ndarray, threshold: float, iterations: int
+    img: np.ndarray, threshold: float, iterations: int, inplace: bool = False
 ) -> np.ndarray: ...
 def fill_alpha_nearest_color(
-    img: np.ndarray, threshold: float, min_radius: int, anti_aliasing: bool
+    img: np.ndarray,
+    threshold: float,
+    min_radius: int,
+    anti_aliasing: bool,
+    inplace: bool = False,
 ) -> np.ndarray: ...
 def binary_threshold(
     img: np.ndarray, threshold: float, anti_aliasing: bool, extra_smoothness: float = 0.0
@@ -22,19 +30,53 @@ def binary_threshold(
 def esdf(
     img: np.ndarray, radius: float, cutoff: float, pre_process: bool, post_process: bool
 ) -> np.ndarray: ...
+# float32, float16, uint8 and uint16 images are supported. The result has the same dtype.
 def pixel_art_upscale(img: np.ndarray, algorithm: str, scale: int) -> np.ndarray: ...
 def fast_gamma(img: np.ndarray, gamma: float) -> np.ndarray: ...
+def srgb_to_linear(img: np.ndarray) -> np.ndarray: ...
+def linear_to_srgb(img: np.ndarray) -> np.ndarray: ...
 
 class UniformQuantization:
     @property
     def colors_per_channel(self) -> int: ...
     def __init__(self, colors_per_channel: int) -> None: ...
 
+class PaletteColorSpace(Enum):
+    Rgb = 0
+    LinearRgb = 1
+    OkLab = 2
+    CieLab76 = 3
+    CieLab2000 = 4
+
+# The palette must have as many channels as the images it is used with.
+# Otherwise, a ValueError is raised.
 class PaletteQuantization:
     @property
     def channels(self) -> int: ...
     def colors(self) -> int: ...
-    def __init__(self, palette: np.ndarray) -> None: ...
+    @property
+    def color_space(self) -> PaletteColorSpace: ...
+    def __init__(
+        self,
+        palette: np.ndarray,
+        color_space: PaletteColorSpace = PaletteColorSpace.Rgb,
+    ) -> None: ...
+
+class PaletteAlgorithm(Enum):
+    MedianCut = 0
+    Octree = 1
+    Wu = 2
+    KMeans = 3
+
+# Returns a 1xNxC palette for PaletteQuantization.
+def generate_palette(
+    img: np.ndarray,
+    max_colors: int,
+    algorithm: PaletteAlgorithm,
+    color_space: PaletteColorSpace = PaletteColorSpace.Rgb,
+    alpha: bool = True,
+    seed: int = 0,
+) -> np.ndarray: ...
 
 class DiffusionAlgorithm(Enum):
     FloydSteinberg = 0
@@ -45,26 +87,59 @@ class DiffusionAlgorithm(Enum):
     Sierra = 5
     TwoRowSierra = 6
     SierraLite = 7
+    Fan = 8
+    ShiauFan = 9
+    ShiauFan2 = 10
+    Ostromoukhov = 11
 
+# The first row of the weights is the row of the current pixel and `origin` is
+# the column of the current pixel. Already processed pixels must have a weight
+# of 0. The kernel can reach at most 2 rows down and 3 columns to either side.
+class DiffusionKernel:
+    def __init__(self, weights: np.ndarray, origin: int) -> None: ...
+
+# float32, float16, uint8 and uint16 images are supported. The result has the same dtype.
 def quantize(
     img: np.ndarray,
     quant: UniformQuantization | PaletteQuantization,
+    inplace: bool = False,
 ) -> np.ndarray: ...
+
+class ThresholdMapType(Enum):
+    Bayer = 0
+    BlueNoise = 1
+
+# Ordered dithering with a palette uses Knoll's pattern dithering.
+# Bayer maps must have a power of 2 as their size. Blue-noise maps can be at most 128.
 def ordered_dither(
     img: np.ndarray,
-    quant: UniformQuantization,
+    quant: UniformQuantization | PaletteQuantization,
     map_size: int,
+    inplace: bool = False,
+    map_type: ThresholdMapType = ThresholdMapType.Bayer,
 ) -> np.ndarray: ...
+# The threshold map is a 2D float32 array of values in [0, 1) that is tiled
+# across the image.
+def ordered_dither_with_map(
+    img: np.ndarray,
+    quant: UniformQuantization | PaletteQuantization,
+    threshold_map: np.ndarray,
+    inplace: bool = False,
+) -> np.ndarray: ...
+# Serpentine scanning alternates the direction of every row.
 def error_diffusion_dither(
     img: np.ndarray,
     quant: UniformQuantization | PaletteQuantization,
-    algorithm: DiffusionAlgorithm,
+    algorithm: DiffusionAlgorithm | DiffusionKernel,
+    inplace: bool = False,
+    serpentine: bool = False,
 ) -> np.ndarray: ...
 def riemersma_dither(
     img: np.ndarray,
     quant: UniformQuantization | PaletteQuantization,
     history_length: int,
     decay_ratio: float,
+    inplace: bool = False,
 ) -> np.ndarray: ...
 
 class ResizeFilter(Enum):
@@ -83,12 +158,185 @@ class ResizeFilter(Enum):
     MKS2013 = 12
     MKS2021 = 13
 
+class GammaCorrection(Enum):
+    Off = 0
+    Gamma22 = 1
+    Srgb = 2
+
+# float16, uint8 and uint16 images are only supported with ResizeFilter.Nearest. The
+# result has the same dtype.
 def resize(
     img: np.ndarray,
     new_size: tuple[int, int],
+    filter: ResizeFilter | tuple[ResizeFilter, ResizeFilter],
+    gamma_correction: bool | GammaCorrection,
+    premultiply_alpha: bool = False,
+    src_rect: tuple[float, float, float, float] | None = None,
+) -> np.ndarray: ...
+
+class BorderMode(Enum):
+    Transparent = 0
+    Clamp = 1
+    Reflect = 2
+    Wrap = 3
+
+def rotate(
+    img: np.ndarray,
+    angle: float,
+    expand: bool,
+    filter: ResizeFilter,
+    border: BorderMode,
+) -> np.ndarray: ...
+def warp_affine(
+    img: np.ndarray,
+    matrix: tuple[tuple[float, float, float], tuple[float, float, float]],
+    new_size: tuple[int, int],
+    filter: ResizeFilter,
+    border: BorderMode,
+) -> np.ndarray: ...
+def warp_perspective(
+    img: np.ndarray,
+    matrix: tuple[
+        tuple[float, float, float],
+        tuple[float, float, float],
+        tuple[float, float, float],
+    ],
+    new_size: tuple[int, int],
     filter: ResizeFilter,
-    gamma_correction: bool,
+    border: BorderMode,
+) -> np.ndarray: ...
+
+def gaussian_blur(
+    img: np.ndarray,
+    sigma: float | tuple[float, float],
+    border: BorderMode,
+    premultiply_alpha: bool = False,
+) -> np.ndarray: ...
+def box_blur(
+    img: np.ndarray,
+    radius: int | tuple[int, int],
+    border: BorderMode,
+    premultiply_alpha: bool = False,
+) -> np.ndarray: ...
+def stack_blur(
+    img: np.ndarray,
+    radius: int | tuple[int, int],
+    border: BorderMode,
+    premultiply_alpha: bool = False,
 ) -> np.ndarray: ...
+def convolve(
+    img: np.ndarray,
+    kernel: np.ndarray,
+    border: BorderMode,
+) -> np.ndarray: ...
+def median_filter(
+    img: np.ndarray,
+    radius: int,
+    border: BorderMode,
+) -> np.ndarray: ...
+def bilateral_filter(
+    img: np.ndarray,
+    sigma_spatial: float,
+    sigma_range: float,
+    border: BorderMode,
+) -> np.ndarray: ...
+def guided_filter(
+    img: np.ndarray,
+    guide: np.ndarray | None,
+    radius: int,
+    eps: float,
+    border: BorderMode,
+) -> np.ndarray: ...
+
+class Connectivity(Enum):
+    Four = 4
+    Eight = 8
+
+class Region:
+    @property
+    def label(self) -> int: ...
+    @property
+    def area(self) -> int: ...
+    @property
+    def bbox(self) -> tuple[int, int, int, int]: ...
+    @property
+    def centroid(self) -> tuple[float, float]: ...
+
+def connected_components(
+    img: np.ndarray,
+    threshold: float,
+    connectivity: Connectivity,
+    min_area: int = 0,
+) -> tuple[np.ndarray, list[Region]]: ...
+
+class MorphOperation(Enum):
+    Dilate = 0
+    Erode = 1
+    Open = 2
+    Close = 3
+    Gradient = 4
+    TopHat = 5
+    BlackHat = 6
+
+class ElementShape(Enum):
+    Square = 0
+    Disc = 1
+
+def morphology(
+    img: np.ndarray,
+    operation: MorphOperation,
+    element: tuple[ElementShape, int] | np.ndarray,
+    binary: bool = False,
+) -> np.ndarray: ...
+
+class SharpenMode(Enum):
+    Luminance = 0
+    PerChannel = 1
+
+def unsharp_mask(
+    img: np.ndarray,
+    radius: float,
+    amount: float,
+    threshold: float,
+    mode: SharpenMode,
+) -> np.ndarray: ...
+def high_pass(
+    img: np.ndarray,
+    radius: float,
+    mode: SharpenMode,
+) -> np.ndarray: ...
+
+# Tiling
+
+class TileGrid:
+    @property
+    def size(self) -> tuple[int, int]: ...
+    @property
+    def tile_count(self) -> int: ...
+    def __init__(
+        self,
+        size: tuple[int, int],
+        tile_size: tuple[int, int],
+        overlap: int,
+        padding: BorderMode | None = None,
+    ) -> None: ...
+    def tiles(self) -> List[tuple[int, int, int, int]]: ...
+    def split(self, img: np.ndarray) -> List[np.ndarray]: ...
+    def merge(self, tiles: List[np.ndarray], scale: int = 1) -> np.ndarray: ...
+
+# Trimming
+
+class TrimMode(Enum):
+    Alpha = 0
+    Color = 1
+
+def trim(
+    img: np.ndarray,
+    mode: TrimMode,
+    threshold: float,
+    padding: int = 0,
+    color: List[float] | None = None,
+) -> tuple[tuple[int, int, int, int], np.ndarray]: ...
 
 # Regex
 
@@ -0,0 +1,128 @@
+use glam::{Vec2, Vec3A, Vec4};
+use image_core::{Flatten, FromFlat, Image, NDimCow};
+use image_ops::{
+    blur::Blur,
+    scale::{FloatPixelFormat, PixelFormat},
+};
+use numpy::{IntoPyArray, PyArray3};
+use pyo3::{exceptions::PyValueError, prelude::*};
+
+use crate::{
+    convert::{IntoNumpy, LoadImage, PyImage},
+    warp::BorderMode,
+};
+
+/// Either one value for both axes or a `(horizontal, vertical)` pair.
+#[derive(FromPyObject)]
+pub enum AxisArg<T> {
+    Single(T),
+    PerAxis(T, T),
+}
+
+impl<T: Copy> AxisArg<T> {
+    fn get(&self) -> (T, T) {
+        match *self {
+            AxisArg::Single(v) => (v, v),
+            AxisArg::PerAxis(x, y) => (x, y),
+        }
+    }
+}
+
+fn run<'py>(
+    py: Python<'py>,
+    img: PyImage,
+    kind: Blur,
+    border: BorderMode,
+    premultiply_alpha: Option<bool>,
+) -> PyResult<&'py PyArray3<f32>> {
+    let border = border.into();
+
+    return match img.channels() {
+        1 => with_pixel_format::<f32>(py, img, kind, border),
+        2 => with_pixel_format::<Vec2>(py, img, kind, border),
+        3 => with_pixel_format::<Vec3A>(py, img, kind, border),
+        4 if premultiply_alpha.unwrap_or(false) => {
+            let img: Image<Vec4> = img.load_image()?;
+            let result = py.allow_threads(|| {
+                image_ops::blur::blur_premultiplied_alpha(img.view(), kind, border).into_numpy()
+            });
+            Ok(result.into_pyarray(py))
+        }
+        4 => with_pixel_format::<Vec4>(py, img, kind, border),
+        _ => {
+            let img: NDimCow = img.load_image()?;
+            let result = py.allow_threads(|| {
+                image_ops::blur::blur_ndim(img.view(), kind, border).into_numpy()
+            });
+            Ok(result.into_pyarray(py))
+        }
+    };
+
+    fn with_pixel_format<'py, P>(
+        py: Python<'py>,
+        img: PyImage,
+        kind: Blur,
+        border: image_ops::border::BorderMode,
+    ) -> PyResult<&'py PyArray3<f32>>
+    where
+        P: Flatten + FromFlat + Copy + Default + Send + Sync + 'static,
+        FloatPixelFormat<P>: PixelFormat<InputPixel = P, OutputPixel = P>,
+    {
+        let img: Image<P> = img.load_image()?;
+        let result =
+            py.allow_threads(|| image_ops::blur::blur(img.view(), kind, border).into_numpy());
+        Ok(result.into_pyarray(py))
+    }
+}
+
+#[pyfunction]
+pub fn gaussian_blur<'py>(
+    py: Python<'py>,
+    img: PyImage,
+    sigma: AxisArg<f32>,
+    border: BorderMode,
+    premultiply_alpha: Option<bool>,
+) -> PyResult<&'py PyArray3<f32>> {
+    let (sigma_x, sigma_y) = sigma.get();
+    if !(sigma_x >= 0.0 && sigma_y >= 0.0 && sigma_x.is_finite() && sigma_y.is_finite()) {
+        return Err(PyValueError::new_err(format!(
+            "Argument '{}' must be finite and non-negative.",
+            stringify!(sigma)
+        )));
+    }
+
+    let kind = Blur::Gaussian { sigma_x, sigma_y };
+    run(py, img, kind, border, premultiply_alpha)
+}
+
+#[pyfunction]
+pub fn box_blur<'py>(
+    py: Python<'py>,
+    img: PyImage,
+    radius: AxisArg<u32>,
+    border: BorderMode,
+    premultiply_alpha: Option<bool>,
+) -> PyResult<&'py PyArray3<f32>> {
+    let (radius_x, radius_y) = radius.get();
+    let kind = Blur::Box {
+        radius_x: radius_x as usize,
+        radius_y: radius_y as usize,
+    };
+    run(py, img, kind, border, premultiply_alpha)
+}
+
+#[pyfunction]
+pub fn stack_blur<'py>(
+    py: Python<'py>,
+    img: PyImage,
+    radius: AxisArg<u32>,
+    border: BorderMode,
+    premultiply_alpha: Option<bool>,
+) -> PyResult<&'py PyArray3<f32>> {
+    let (radius_x, radius_y) = radius.get();
+    let kind = Blur::Stack {
+        radius_x: radius_x as usize,
+        radius_y: radius_y as usize,
+    };
+    run(py, img, kind, border, premultiply_alpha)
+}
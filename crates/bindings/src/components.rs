@@ -0,0 +1,94 @@
+use image_core::{ImageView, NDimCow};
+use numpy::{ndarray::Array2, IntoPyArray, PyArray2};
+use pyo3::{exceptions::PyValueError, prelude::*};
+
+use crate::convert::{LoadImage, PyImage};
+
+#[pyclass]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Connectivity {
+    Four = 4,
+    Eight = 8,
+}
+
+impl From<Connectivity> for image_ops::components::Connectivity {
+    fn from(c: Connectivity) -> Self {
+        match c {
+            Connectivity::Four => image_ops::components::Connectivity::Four,
+            Connectivity::Eight => image_ops::components::Connectivity::Eight,
+        }
+    }
+}
+
+/// A connected region of a mask.
+#[pyclass(frozen)]
+#[derive(Clone)]
+pub struct Region {
+    /// The label of the region's pixels in the label image.
+    #[pyo3(get)]
+    pub label: u32,
+    /// The number of pixels in the region.
+    #[pyo3(get)]
+    pub area: usize,
+    /// The `(x, y, width, height)` of the bounding box.
+    #[pyo3(get)]
+    pub bbox: (usize, usize, usize, usize),
+    /// The `(x, y)` mean position of the region's pixels.
+    #[pyo3(get)]
+    pub centroid: (f32, f32),
+}
+
+/// Labels the connected regions of pixels above the given threshold.
+///
+/// For single-channel images, the mask is made from the values of the image.
+/// For images with 2 or 4 channels, the mask is made from the alpha channel.
+///
+/// Returns the label image (0 is background) and the regions ordered by label.
+#[pyfunction]
+pub fn connected_components<'py>(
+    py: Python<'py>,
+    img: PyImage,
+    threshold: f32,
+    connectivity: Connectivity,
+    min_area: Option<usize>,
+) -> PyResult<(&'py PyArray2<u32>, Vec<Region>)> {
+    let img: NDimCow = img.load_image()?;
+    let channels = img.channels();
+    if !matches!(channels, 1 | 2 | 4) {
+        return Err(PyValueError::new_err(format!(
+            "Expected an image with 1, 2, or 4 channels, but found {channels} channels."
+        )));
+    }
+
+    let (labels, regions) = py.allow_threads(|| {
+        let mask: Vec<bool> = img
+            .data()
+            .iter()
+            .skip(channels - 1)
+            .step_by(channels)
+            .map(|v| *v > threshold)
+            .collect();
+        let components = image_ops::components::label_components(
+            ImageView::new(img.size(), &mask),
+            connectivity.into(),
+            min_area.unwrap_or(0),
+        );
+
+        let size = components.labels.size();
+        let labels = Array2::from_shape_vec((size.height, size.width), components.labels.take())
+            .expect("Expect creation of numpy array to succeed.");
+        let regions: Vec<Region> = components
+            .regions
+            .into_iter()
+            .map(|r| Region {
+                label: r.label,
+                area: r.area,
+                bbox: (r.x, r.y, r.size.width, r.size.height),
+                centroid: (r.centroid.x, r.centroid.y),
+            })
+            .collect();
+        (labels, regions)
+    });
+
+    Ok((labels.into_pyarray(py), regions))
+}
@@ -1,20 +1,99 @@
 use image_core::{
-    util::slice_as_chunks, FromFlat, Image, ImageView, IntoPixels, NDimCow, NDimImage, NDimView,
-    Shape, ShapeMismatch, Size,
+    f16, util::slice_as_chunks, Flatten, FromFlat, Image, ImageError, ImageView, ImageViewMut,
+    IntoPixels, NDimCow, NDimImage, NDimView, NDimViewMut, Sample, Shape, ShapeMismatch, Size,
 };
 use numpy::{
     ndarray::{Array3, Dimension},
-    Ix3, PyReadonlyArray, PyReadonlyArray2, PyReadonlyArray3,
+    Element, IntoPyArray, Ix3, PyArrayDyn, PyReadonlyArray, PyReadonlyArray2, PyReadonlyArray3,
+    PyReadwriteArray, PyReadwriteArray2, PyReadwriteArray3,
 };
-use pyo3::{exceptions::PyValueError, FromPyObject, PyResult};
+use pyo3::{exceptions::PyValueError, prelude::*, PyResult};
 
-#[derive(FromPyObject)]
-pub enum PyImage<'py> {
-    D2(PyReadonlyArray2<'py, f32>),
-    D3(PyReadonlyArray3<'py, f32>),
+/// A sample type of images that can be exchanged with numpy.
+pub trait PySample: Sample + Element {
+    /// The name of the numpy dtype.
+    const DTYPE: &'static str;
+
+    /// Converts the image into pixels. Samples are converted to `f32` first.
+    fn to_pixels<P: FromFlat>(img: NDimView<Self>) -> Result<Image<P>, ShapeMismatch>;
+    /// Converts the pixels back into an image of this sample type.
+    fn from_pixels<P: Flatten>(img: Image<P>) -> NDimImage<Self>;
+}
+
+impl PySample for f32 {
+    const DTYPE: &'static str = "float32";
+
+    fn to_pixels<P: FromFlat>(img: NDimView<Self>) -> Result<Image<P>, ShapeMismatch> {
+        img.into_pixels()
+    }
+    fn from_pixels<P: Flatten>(img: Image<P>) -> NDimImage<Self> {
+        img.into()
+    }
+}
+macro_rules! impl_converted_sample {
+    ($t:ty, $dtype:literal) => {
+        impl PySample for $t {
+            const DTYPE: &'static str = $dtype;
+
+            fn to_pixels<P: FromFlat>(img: NDimView<Self>) -> Result<Image<P>, ShapeMismatch> {
+                img.convert::<f32>().into_pixels()
+            }
+            fn from_pixels<P: Flatten>(img: Image<P>) -> NDimImage<Self> {
+                NDimImage::from(img).convert()
+            }
+        }
+    };
+}
+impl_converted_sample!(f16, "float16");
+impl_converted_sample!(u8, "uint8");
+impl_converted_sample!(u16, "uint16");
+
+/// The supported dtypes of images.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SampleType {
+    F32,
+    F16,
+    U8,
+    U16,
+}
+
+impl SampleType {
+    /// Returns the sample type of the given numpy array.
+    ///
+    /// Anything that isn't an array of a supported dtype is treated as
+    /// `float32`, so that the usual errors are reported when it is extracted.
+    pub fn of(obj: &PyAny) -> Self {
+        fn is<T: Element>(obj: &PyAny) -> bool {
+            obj.extract::<&PyArrayDyn<T>>().is_ok()
+        }
+
+        if is::<f16>(obj) {
+            SampleType::F16
+        } else if is::<u8>(obj) {
+            SampleType::U8
+        } else if is::<u16>(obj) {
+            SampleType::U16
+        } else {
+            SampleType::F32
+        }
+    }
+}
+
+pub enum PyImage<'py, T: Element = f32> {
+    D2(PyReadonlyArray2<'py, T>),
+    D3(PyReadonlyArray3<'py, T>),
+}
+
+impl<'py, T: Element> FromPyObject<'py> for PyImage<'py, T> {
+    fn extract(ob: &'py PyAny) -> PyResult<Self> {
+        if let Ok(img) = ob.extract() {
+            return Ok(PyImage::D2(img));
+        }
+        Ok(PyImage::D3(ob.extract()?))
+    }
 }
 
-impl PyImage<'_> {
+impl<T: PySample> PyImage<'_, T> {
     pub fn shape(&self) -> Shape {
         match self {
             PyImage::D2(img) => {
@@ -36,49 +115,355 @@ impl PyImage<'_> {
 
     /// Tries to create a view of the image.
     ///
-    /// This is only possible if the image is contiguous in memory and C-contiguous.
-    pub fn try_view(&'_ self) -> Option<NDimView<'_>> {
+    /// This is possible if the pixels of each row are contiguous in memory,
+    /// e.g. for C-contiguous arrays and slices of them. Rows may be further
+    /// apart than their length.
+    pub fn try_view(&'_ self) -> PyResult<Option<NDimView<'_, T>>> {
         match self {
-            PyImage::D2(img) => {
-                if img.is_c_contiguous() {
-                    if let Ok(data) = img.as_slice() {
-                        return Some(NDimView::new(self.shape(), data));
-                    }
-                }
-            }
-            PyImage::D3(img) => {
-                if img.is_c_contiguous() {
-                    if let Ok(data) = img.as_slice() {
-                        return Some(NDimView::new(self.shape(), data));
-                    }
-                }
+            PyImage::D2(img) => strided_view(img, self.shape()),
+            PyImage::D3(img) => strided_view(img, self.shape()),
+        }
+    }
+
+    /// Creates a contiguous view of the image of possible. If not possible, it
+    /// will copy the image into a Vec.
+    pub fn as_contiguous(&'_ self) -> PyResult<NDimCow<'_, T>> {
+        if let Some(view) = self.try_view()? {
+            if view.is_contiguous() {
+                return Ok(view.into());
             }
+            return Ok(view.into_owned().into());
+        }
+
+        let shape = self.shape();
+
+        fn to_vec<T: PySample, D: Dimension>(img: &PyReadonlyArray<T, D>) -> Vec<T> {
+            img.as_array().iter().copied().collect()
+        }
+
+        let image = match self {
+            PyImage::D2(img) => NDimImage::try_new(shape, to_vec(img)),
+            PyImage::D3(img) => NDimImage::try_new(shape, to_vec(img)),
+        };
+        Ok(image.map_err(image_error)?.into())
+    }
+}
+
+fn strided_view<'a, T: PySample, D: Dimension>(
+    img: &'a PyReadonlyArray<T, D>,
+    shape: Shape,
+) -> PyResult<Option<NDimView<'a, T>>> {
+    if img.is_c_contiguous() {
+        let Ok(data) = img.as_slice() else {
+            return Ok(None);
+        };
+        return NDimView::try_new(shape, data)
+            .map(Some)
+            .map_err(image_error);
+    }
+    if shape.is_empty() {
+        return Ok(Some(NDimView::new(shape, &[])));
+    }
+
+    let array = img.as_array();
+    let Some(stride) = row_stride(array.strides(), shape) else {
+        return Ok(None);
+    };
+    let len = (stride * (shape.height - 1) + shape.width) * shape.channels;
+    // SAFETY: All strides are positive, so the array's elements all lie within
+    // `len` elements of its first element, and the slice covers exactly the
+    // data range that numpy's borrow checker tracks for `img`. Since the
+    // elements of each row are contiguous (see `row_stride`), the byte strides
+    // of the array have a GCD of `size_of::<T>()`, so any other borrow of an
+    // array that overlaps this range - including the elements between our
+    // rows - conflicts with the borrow held by `img`. This means that nothing
+    // can write to the slice while it is borrowed.
+    let data = unsafe { std::slice::from_raw_parts(array.as_ptr(), len) };
+    NDimView::try_with_stride(shape, stride, data)
+        .map(Some)
+        .map_err(image_error)
+}
+
+fn strided_view_mut<'a, T: PySample, D: Dimension>(
+    img: &'a mut PyReadwriteArray<T, D>,
+    shape: Shape,
+) -> PyResult<Option<NDimViewMut<'a, T>>> {
+    if img.is_c_contiguous() {
+        let Ok(data) = img.as_slice_mut() else {
+            return Ok(None);
         };
+        return NDimViewMut::try_new(shape, data)
+            .map(Some)
+            .map_err(image_error);
+    }
+    if shape.is_empty() {
+        return Ok(Some(NDimViewMut::new(shape, &mut [])));
+    }
+
+    let mut array = img.as_array_mut();
+    let Some(stride) = row_stride(array.strides(), shape) else {
+        return Ok(None);
+    };
+    let len = (stride * (shape.height - 1) + shape.width) * shape.channels;
+    // SAFETY: As explained in `strided_view`, numpy's borrow checker treats any
+    // other borrow of an array overlapping this range as a conflict with the
+    // mutable borrow held by `img`. So the slice, including the elements
+    // between our rows, isn't aliased while it is borrowed.
+    let data = unsafe { std::slice::from_raw_parts_mut(array.as_mut_ptr(), len) };
+    NDimViewMut::try_with_stride(shape, stride, data)
+        .map(Some)
+        .map_err(image_error)
+}
+
+/// Returns the distance between rows in pixels if the pixels of each row are
+/// contiguous in memory. `strides` are the element strides of
+/// `(row, pixel[, channel])`.
+///
+/// Rows of a single value are rejected, because numpy's borrow checker may
+/// consider arrays interleaved with them as disjoint.
+fn row_stride(strides: &[isize], shape: Shape) -> Option<usize> {
+    let Shape {
+        width,
+        height,
+        channels,
+    } = shape;
+
+    let channel_stride = strides.get(2).copied().unwrap_or(1);
+    if channels > 1 && channel_stride != 1 {
+        return None;
+    }
+    if width > 1 && strides[1] != channels as isize {
+        return None;
+    }
+    if height <= 1 {
+        return Some(width);
+    }
+    if width * channels == 1 {
+        return None;
+    }
 
-        None
+    let row_stride = strides[0];
+    if row_stride < (width * channels) as isize || row_stride % channels as isize != 0 {
+        return None;
     }
+    Some(row_stride as usize / channels)
+}
 
-    /// Creates a view of the image of possible. If not possible, it will copy
-    /// the image into a Vec.
-    pub fn as_contiguous(&'_ self) -> NDimCow<'_> {
-        if let Some(view) = self.try_view() {
-            return view.into();
+/// A writable image.
+pub enum PyImageMut<'py, T: Element = f32> {
+    D2(PyReadwriteArray2<'py, T>),
+    D3(PyReadwriteArray3<'py, T>),
+}
+
+impl<'py, T: Element> FromPyObject<'py> for PyImageMut<'py, T> {
+    fn extract(ob: &'py PyAny) -> PyResult<Self> {
+        if let Ok(img) = ob.extract() {
+            return Ok(PyImageMut::D2(img));
         }
+        Ok(PyImageMut::D3(ob.extract()?))
+    }
+}
 
+impl<T: PySample> PyImageMut<'_, T> {
+    pub fn shape(&self) -> Shape {
+        match self {
+            PyImageMut::D2(img) => {
+                let shape = img.shape();
+                Shape::new(shape[1], shape[0], 1)
+            }
+            PyImageMut::D3(img) => {
+                let shape = img.shape();
+                Shape::new(shape[1], shape[0], shape[2])
+            }
+        }
+    }
+    pub fn channels(&self) -> usize {
+        self.shape().channels
+    }
+
+    /// Tries to create a mutable view of the image.
+    ///
+    /// See [`PyImage::try_view`] for when this is possible.
+    pub fn try_view_mut(&'_ mut self) -> PyResult<Option<NDimViewMut<'_, T>>> {
+        let shape = self.shape();
+        match self {
+            PyImageMut::D2(img) => strided_view_mut(img, shape),
+            PyImageMut::D3(img) => strided_view_mut(img, shape),
+        }
+    }
+
+    /// Calls the given function with a mutable view of the image. If the
+    /// image can't be viewed, a copy of it is modified and written back.
+    pub fn modify<R: Send>(
+        &mut self,
+        py: Python,
+        f: impl FnOnce(NDimViewMut<T>) -> R + Send,
+    ) -> PyResult<R> {
         let shape = self.shape();
+        if let Some(view) = self.try_view_mut()? {
+            return Ok(py.allow_threads(|| f(view)));
+        }
 
-        fn to_vec<D: Dimension>(img: &PyReadonlyArray<f32, D>) -> Vec<f32> {
-            img.as_array().iter().copied().collect()
+        let mut copy = NDimImage::try_new(shape, self.to_vec()).map_err(image_error)?;
+        let result = py.allow_threads(|| f(copy.view_mut()));
+
+        fn write_back<T: PySample, D: Dimension>(img: &mut PyReadwriteArray<T, D>, data: &[T]) {
+            for (dst, src) in img.as_array_mut().iter_mut().zip(data) {
+                *dst = *src;
+            }
         }
+        match self {
+            PyImageMut::D2(img) => write_back(img, copy.data()),
+            PyImageMut::D3(img) => write_back(img, copy.data()),
+        }
+        Ok(result)
+    }
 
+    fn to_vec(&self) -> Vec<T> {
         match self {
-            PyImage::D2(img) => NDimImage::new(shape, to_vec(img)).into(),
-            PyImage::D3(img) => NDimImage::new(shape, to_vec(img)).into(),
+            PyImageMut::D2(img) => img.as_array().iter().copied().collect(),
+            PyImageMut::D3(img) => img.as_array().iter().copied().collect(),
         }
     }
 }
 
-fn new_numpy_array(size: Size, channels: usize, data: Vec<f32>) -> Array3<f32> {
+/// The image argument of an operation that can optionally modify the image in
+/// place.
+pub enum ImageArg<'py, T: Element = f32> {
+    /// The operation is applied to a copy of the image.
+    Copy(PyImage<'py, T>),
+    /// The operation modifies the given array.
+    InPlace(&'py PyAny, PyImageMut<'py, T>),
+}
+
+impl<'py, T: PySample> ImageArg<'py, T> {
+    pub fn new(img: &'py PyAny, inplace: Option<bool>) -> PyResult<Self> {
+        if inplace.unwrap_or(false) {
+            Ok(Self::InPlace(img, img.extract()?))
+        } else {
+            Ok(Self::Copy(img.extract()?))
+        }
+    }
+
+    pub fn channels(&self) -> usize {
+        match self {
+            Self::Copy(img) => img.channels(),
+            Self::InPlace(_, img) => img.channels(),
+        }
+    }
+
+    /// Applies the given operation and returns the modified image. In place,
+    /// this is the given array itself.
+    pub fn apply_ndim(
+        self,
+        py: Python<'py>,
+        f: impl FnOnce(NDimViewMut<T>) + Send,
+    ) -> PyResult<PyObject> {
+        self.try_apply_ndim(py, |img| {
+            f(img);
+            Ok(())
+        })
+    }
+    /// Same as [`Self::apply_ndim`] for operations that can fail.
+    ///
+    /// Operations must not modify the image if they fail.
+    pub fn try_apply_ndim(
+        self,
+        py: Python<'py>,
+        f: impl FnOnce(NDimViewMut<T>) -> PyResult<()> + Send,
+    ) -> PyResult<PyObject> {
+        match self {
+            Self::Copy(img) => {
+                let mut img: NDimImage<T> = img.load_image()?;
+                let result = py.allow_threads(|| {
+                    f(img.view_mut())?;
+                    Ok::<_, PyErr>(ndim_into_numpy(img))
+                })?;
+                Ok(result.into_pyarray(py).into_py(py))
+            }
+            Self::InPlace(obj, mut img) => {
+                img.modify(py, f)??;
+                Ok(obj.into_py(py))
+            }
+        }
+    }
+
+    /// Same as [`Self::apply_ndim`] for operations on pixels.
+    ///
+    /// In place, the pixels are copied and written back after the operation.
+    /// Samples that aren't `f32` are converted for the operation.
+    pub fn apply<P>(
+        self,
+        py: Python<'py>,
+        f: impl FnOnce(ImageViewMut<P>) + Send,
+    ) -> PyResult<PyObject>
+    where
+        P: FromFlat + Flatten + Send,
+    {
+        self.try_apply(py, |img| {
+            f(img);
+            Ok(())
+        })
+    }
+    /// Same as [`Self::apply`] for operations that can fail.
+    ///
+    /// Operations must not modify the image if they fail.
+    pub fn try_apply<P>(
+        self,
+        py: Python<'py>,
+        f: impl FnOnce(ImageViewMut<P>) -> PyResult<()> + Send,
+    ) -> PyResult<PyObject>
+    where
+        P: FromFlat + Flatten + Send,
+    {
+        match self {
+            Self::Copy(img) => {
+                let img = img.as_contiguous()?;
+                let mut img: Image<P> = T::to_pixels(img.view()).map_err(shape_mismatch_error)?;
+                let result = py.allow_threads(|| {
+                    f(img.view_mut())?;
+                    Ok::<_, PyErr>(ndim_into_numpy(T::from_pixels(img)))
+                })?;
+                Ok(result.into_pyarray(py).into_py(py))
+            }
+            Self::InPlace(obj, mut img) => {
+                img.modify(py, |mut view| {
+                    let mut pixels: Image<P> =
+                        T::to_pixels(view.view()).map_err(shape_mismatch_error)?;
+                    f(pixels.view_mut())?;
+                    view.copy_from(T::from_pixels(pixels).view());
+                    Ok::<_, PyErr>(())
+                })??;
+                Ok(obj.into_py(py))
+            }
+        }
+    }
+}
+
+fn image_error(error: ImageError) -> PyErr {
+    match error {
+        ImageError::DataLength { expected, actual } => PyValueError::new_err(format!(
+            "Image data does not have the right length. Expected {expected} elements but found {actual}."
+        )),
+        ImageError::StrideTooSmall { width, stride } => PyValueError::new_err(format!(
+            "Image rows are too close together. Expected a stride of at least {width} pixels but found {stride}."
+        )),
+    }
+}
+
+fn shape_mismatch_error(ShapeMismatch { actual, expected }: ShapeMismatch) -> PyErr {
+    PyValueError::new_err(format!(
+        "Image does not have the right shape. Expected {} channel(s) but found {}.",
+        expected
+            .iter()
+            .map(|s| s.to_string())
+            .collect::<Vec<_>>()
+            .join(", "),
+        actual
+    ))
+}
+
+fn new_numpy_array<T>(size: Size, channels: usize, data: Vec<T>) -> Array3<T> {
     let shape = Ix3(size.height, size.width, channels);
     Array3::from_shape_vec(shape, data).expect("Expect creation of numpy array to succeed.")
 }
@@ -89,22 +474,26 @@ pub trait IntoNumpy {
 
 impl<T: Into<NDimImage>> IntoNumpy for T {
     fn into_numpy(self) -> Array3<f32> {
-        let image: NDimImage = self.into();
-        new_numpy_array(image.size(), image.channels(), image.take())
+        ndim_into_numpy(self.into())
     }
 }
 
+/// Same as [`IntoNumpy`] for images of any sample type.
+pub fn ndim_into_numpy<T>(image: NDimImage<T>) -> Array3<T> {
+    new_numpy_array(image.size(), image.channels(), image.take())
+}
+
 pub trait LoadImage<T> {
     fn load_image(self) -> PyResult<T>;
 }
-impl<'py> LoadImage<NDimCow<'py>> for &'py PyImage<'py> {
-    fn load_image(self) -> PyResult<NDimCow<'py>> {
-        Ok(self.as_contiguous())
+impl<'py, T: PySample> LoadImage<NDimCow<'py, T>> for &'py PyImage<'py, T> {
+    fn load_image(self) -> PyResult<NDimCow<'py, T>> {
+        self.as_contiguous()
     }
 }
-impl<'py> LoadImage<NDimImage> for &'py PyImage<'py> {
-    fn load_image(self) -> PyResult<NDimImage> {
-        Ok(self.as_contiguous().into_owned())
+impl<'py, T: PySample> LoadImage<NDimImage<T>> for &'py PyImage<'py, T> {
+    fn load_image(self) -> PyResult<NDimImage<T>> {
+        Ok(self.as_contiguous()?.into_owned())
     }
 }
 impl<'py, T> LoadImage<Image<T>> for &'py PyImage<'py>
@@ -112,55 +501,51 @@ where
     T: FromFlat,
 {
     fn load_image(self) -> PyResult<Image<T>> {
-        let cow = self.as_contiguous();
+        let cow = self.as_contiguous()?;
         let result = match cow {
             NDimCow::View(view) => view.into_pixels(),
             NDimCow::Image(image) => image.into_pixels(),
         };
-        match result {
-            Ok(image) => Ok(image),
-            Err(ShapeMismatch { actual, expected }) => Err(PyValueError::new_err(format!(
-                "Image does not have the right shape. Expected {} channel(s) but found {}.",
-                expected
-                    .iter()
-                    .map(|s| s.to_string())
-                    .collect::<Vec<_>>()
-                    .join(", "),
-                actual
-            ))),
-        }
+        result.map_err(shape_mismatch_error)
     }
 }
 
 pub trait ViewImage<T> {
-    fn view_image(self) -> Option<T>;
+    fn view_image(self) -> PyResult<Option<T>>;
 }
 impl<'py> ViewImage<NDimView<'py>> for &'py PyImage<'py> {
-    fn view_image(self) -> Option<NDimView<'py>> {
+    fn view_image(self) -> PyResult<Option<NDimView<'py>>> {
         self.try_view()
     }
 }
 impl<'py> ViewImage<ImageView<'py, f32>> for &'py PyImage<'py> {
-    fn view_image(self) -> Option<ImageView<'py, f32>> {
-        if let Some(view) = self.try_view() {
+    fn view_image(self) -> PyResult<Option<ImageView<'py, f32>>> {
+        if let Some(view) = self.try_view()? {
             if view.channels() == 1 {
-                let size = view.size();
-                return Some(ImageView::new(size, view.data()));
+                let data = view.strided_data();
+                return Ok(Some(ImageView::with_stride(
+                    view.size(),
+                    view.stride(),
+                    data,
+                )));
             }
         }
-        None
+        Ok(None)
     }
 }
 impl<'py, const N: usize> ViewImage<ImageView<'py, [f32; N]>> for &'py PyImage<'py> {
-    fn view_image(self) -> Option<ImageView<'py, [f32; N]>> {
-        if let Some(view) = self.try_view() {
+    fn view_image(self) -> PyResult<Option<ImageView<'py, [f32; N]>>> {
+        if let Some(view) = self.try_view()? {
             if view.channels() == N {
-                let (chunks, rest) = slice_as_chunks(view.data());
+                let (chunks, rest) = slice_as_chunks(view.strided_data());
                 assert!(rest.is_empty());
-                let size = view.size();
-                return Some(ImageView::new(size, chunks));
+                return Ok(Some(ImageView::with_stride(
+                    view.size(),
+                    view.stride(),
+                    chunks,
+                )));
             }
         }
-        None
+        Ok(None)
     }
 }
@@ -0,0 +1,96 @@
+use glam::{Vec2, Vec3A, Vec4};
+use image_core::{Flatten, FromFlat, Image, NDimCow};
+use image_ops::{
+    convolve::{ChannelKernels, ConvolveError, Kernel},
+    scale::{FloatPixelFormat, PixelFormat},
+};
+use numpy::{IntoPyArray, PyArray3};
+use pyo3::{exceptions::PyValueError, prelude::*};
+
+use crate::{
+    convert::{IntoNumpy, LoadImage, PyImage},
+    warp::BorderMode,
+};
+
+/// Convolves the image with the given kernel.
+///
+/// The kernel is either a 2D array that is applied to all channels, or a 3D
+/// array with one kernel per channel.
+#[pyfunction]
+pub fn convolve<'py>(
+    py: Python<'py>,
+    img: PyImage,
+    kernel: PyImage,
+    border: BorderMode,
+) -> PyResult<&'py PyArray3<f32>> {
+    let border = border.into();
+
+    let kernel: NDimCow = kernel.load_image()?;
+    let kernels = (0..kernel.channels())
+        .map(|c| {
+            let data = kernel
+                .data()
+                .iter()
+                .skip(c)
+                .step_by(kernel.channels())
+                .copied()
+                .collect();
+            Kernel::new(kernel.size(), data)
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| {
+            PyValueError::new_err(format!(
+                "Argument '{}' must be a non-empty array of finite values.",
+                stringify!(kernel)
+            ))
+        })?;
+
+    let channels = img.channels();
+    if kernels.len() == 1 {
+        let kernel = &kernels[0];
+        match channels {
+            1 => return with_pixel_format::<f32>(py, img, kernel, border),
+            2 => return with_pixel_format::<Vec2>(py, img, kernel, border),
+            3 => return with_pixel_format::<Vec3A>(py, img, kernel, border),
+            4 => return with_pixel_format::<Vec4>(py, img, kernel, border),
+            _ => {}
+        }
+    }
+
+    let img: NDimCow = img.load_image()?;
+    let result = py.allow_threads(|| {
+        let kernels = if kernels.len() == 1 {
+            ChannelKernels::All(&kernels[0])
+        } else {
+            ChannelKernels::PerChannel(&kernels)
+        };
+        image_ops::convolve::convolve_ndim(img.view(), kernels, border).map(|r| r.into_numpy())
+    });
+
+    return match result {
+        Ok(r) => Ok(r.into_pyarray(py)),
+        Err(ConvolveError::ChannelMismatch { expected, actual }) => {
+            Err(PyValueError::new_err(format!(
+                "Expected a kernel with 1 or {expected} channels, but found {actual} channels."
+            )))
+        }
+        Err(ConvolveError::InvalidKernel) => unreachable!("kernels are validated above"),
+    };
+
+    fn with_pixel_format<'py, P>(
+        py: Python<'py>,
+        img: PyImage,
+        kernel: &Kernel,
+        border: image_ops::border::BorderMode,
+    ) -> PyResult<&'py PyArray3<f32>>
+    where
+        P: Flatten + FromFlat + Copy + Default + Send + Sync + 'static,
+        FloatPixelFormat<P>: PixelFormat<InputPixel = P, OutputPixel = P>,
+    {
+        let img: Image<P> = img.load_image()?;
+        let result = py.allow_threads(|| {
+            image_ops::convolve::convolve(img.view(), kernel, border).into_numpy()
+        });
+        Ok(result.into_pyarray(py))
+    }
+}
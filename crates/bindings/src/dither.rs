@@ -1,15 +1,14 @@
 use std::sync::Arc;
 
 use glam::{Vec3A, Vec4};
-use image_core::{FromFlat, Image, IntoPixels, NDimImage};
+use image_core::{f16, Flatten, FromFlat, Image, IntoPixels, NDimImage};
 use image_ops::{
     dither::*,
     palette::{extract_unique_ndim, ExtractionError},
 };
-use numpy::{IntoPyArray, PyArray3};
 use pyo3::{exceptions::PyValueError, prelude::*};
 
-use crate::convert::{IntoNumpy, LoadImage, PyImage};
+use crate::convert::{ImageArg, IntoNumpy, LoadImage, PyImage, PySample, SampleType};
 
 #[pyclass(frozen)]
 #[derive(Clone, PartialEq, Debug)]
@@ -21,15 +20,9 @@ pub struct UniformQuantization {
 impl UniformQuantization {
     #[new]
     pub fn new(colors_per_channel: u32) -> PyResult<Self> {
-        if colors_per_channel < 2 {
-            return Err(PyValueError::new_err(format!(
-                "Argument '{}' must be at least 2.",
-                stringify!(per_channel)
-            )));
-        }
-
         Ok(Self {
-            inner: ChannelQuantization::new(colors_per_channel as usize),
+            inner: ChannelQuantization::try_new(colors_per_channel as usize)
+                .map_err(to_py_error)?,
         })
     }
 
@@ -39,16 +32,72 @@ impl UniformQuantization {
     }
 }
 
+#[pyclass]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum PaletteColorSpace {
+    Rgb = 0,
+    LinearRgb = 1,
+    OkLab = 2,
+    CieLab76 = 3,
+    CieLab2000 = 4,
+}
+
+impl<P> ColorSpace<P> for PaletteColorSpace
+where
+    RGB: ColorSpace<P>,
+    LinearRGB: ColorSpace<P, Coord = <RGB as ColorSpace<P>>::Coord>,
+    OkLab: ColorSpace<P, Coord = <RGB as ColorSpace<P>>::Coord>,
+    CieLab: ColorSpace<P, Coord = <RGB as ColorSpace<P>>::Coord>,
+{
+    type Coord = <RGB as ColorSpace<P>>::Coord;
+
+    fn get_coordinate(&self, color: P) -> Self::Coord {
+        match self {
+            PaletteColorSpace::Rgb => RGB.get_coordinate(color),
+            PaletteColorSpace::LinearRgb => LinearRGB.get_coordinate(color),
+            PaletteColorSpace::OkLab => OkLab.get_coordinate(color),
+            PaletteColorSpace::CieLab76 | PaletteColorSpace::CieLab2000 => {
+                self.cie_lab().get_coordinate(color)
+            }
+        }
+    }
+    fn distance_2(&self, a: &Self::Coord, b: &Self::Coord) -> f32 {
+        match self {
+            PaletteColorSpace::Rgb => RGB.distance_2(a, b),
+            PaletteColorSpace::LinearRgb => LinearRGB.distance_2(a, b),
+            PaletteColorSpace::OkLab => OkLab.distance_2(a, b),
+            PaletteColorSpace::CieLab76 | PaletteColorSpace::CieLab2000 => {
+                self.cie_lab().distance_2(a, b)
+            }
+        }
+    }
+    fn is_euclidean(&self) -> bool {
+        *self != PaletteColorSpace::CieLab2000
+    }
+}
+
+impl PaletteColorSpace {
+    fn cie_lab(self) -> CieLab {
+        CieLab {
+            delta_e: match self {
+                PaletteColorSpace::CieLab2000 => DeltaE::Cie2000,
+                _ => DeltaE::Cie76,
+            },
+        }
+    }
+}
+
 #[pyclass(frozen)]
 #[derive(Clone)]
 pub struct PaletteQuantization {
     palette: Arc<NDimImage>,
+    color_space: PaletteColorSpace,
 }
 
 #[pymethods]
 impl PaletteQuantization {
     #[new]
-    pub fn new(palette: PyImage) -> PyResult<Self> {
+    pub fn new(palette: PyImage, color_space: Option<PaletteColorSpace>) -> PyResult<Self> {
         let palette: NDimImage = palette.load_image()?;
         if palette.height() != 1 {
             return Err(PyValueError::new_err(format!(
@@ -83,6 +132,7 @@ impl PaletteQuantization {
 
         Ok(Self {
             palette: Arc::new(palette),
+            color_space: color_space.unwrap_or(PaletteColorSpace::Rgb),
         })
     }
 
@@ -95,24 +145,74 @@ impl PaletteQuantization {
     pub fn colors(&self) -> u32 {
         self.palette.width() as u32
     }
+
+    #[getter]
+    pub fn color_space(&self) -> PaletteColorSpace {
+        self.color_space
+    }
 }
 
 impl PaletteQuantization {
-    fn into_quantizer<P>(self) -> impl Quantizer<P, P>
+    fn into_quantizer<P>(self) -> PyResult<impl Quantizer<P, P>>
     where
         P: Pixel + std::ops::Sub<Output = P> + FromFlat,
-        RGB: ColorSpace<P>,
+        PaletteColorSpace: ColorSpace<P>,
         BoundError: ErrorCombinator<P>,
     {
         let ndim = NDimImage::new(self.palette.shape(), self.palette.data().to_vec());
-        let img: Image<P> = ndim
-            .into_pixels()
-            .expect("Expected shape of palette to match.");
-
-        ColorPalette::new(RGB, img.take(), BoundError)
+        let img: Image<P> = ndim.into_pixels().map_err(|e| {
+            PyValueError::new_err(format!(
+                "Expected a palette with {} channels, but found {}.",
+                e.expected
+                    .iter()
+                    .map(|s| s.to_string())
+                    .collect::<Vec<_>>()
+                    .join(" or "),
+                e.actual
+            ))
+        })?;
+
+        ColorPalette::try_new(self.color_space, img.take(), BoundError).map_err(to_py_error)
     }
 }
 
+fn to_py_error(e: DitherError) -> PyErr {
+    PyValueError::new_err(match e {
+        DitherError::TooFewColorsPerChannel { per_channel } => {
+            format!("Expected at least 2 colors per channel, but found {per_channel}.")
+        }
+        DitherError::EmptyPalette => "The palette must contain at least one color.".to_string(),
+        DitherError::InvalidMapSize { map_size } => {
+            format!("Expected the map size to be a power of 2, but found {map_size}.")
+        }
+        DitherError::EmptyThresholdMap => {
+            "The threshold map must contain at least one threshold.".to_string()
+        }
+        DitherError::InvalidThreshold { threshold } => {
+            format!("Expected thresholds between 0 (inclusive) and 1 (exclusive), but found {threshold}.")
+        }
+        DitherError::HistoryTooShort { history_length } => {
+            format!("Expected a history length of at least 2, but found {history_length}.")
+        }
+        DitherError::InvalidDecayRatio { decay_ratio } => {
+            format!("Expected a decay ratio between 0 and 1 (exclusive), but found {decay_ratio}.")
+        }
+        DitherError::InvalidKernelOrigin { origin, width } => {
+            format!("Expected the origin to be less than the kernel width {width}, but found {origin}.")
+        }
+        DitherError::KernelTooLarge {
+            width,
+            height,
+            origin,
+        } => format!(
+            "Expected a kernel that reaches at most 2 rows down and 3 columns to the left and right, but found a {width}x{height} kernel with origin {origin}."
+        ),
+        DitherError::InvalidKernelWeight { x, y, weight } => format!(
+            "Expected finite weights and a weight of 0 for already processed pixels, but found {weight} at x={x} y={y}."
+        ),
+    })
+}
+
 #[derive(FromPyObject)]
 pub enum Quant {
     Uniform(UniformQuantization),
@@ -130,107 +230,240 @@ pub enum DiffusionAlgorithm {
     Sierra = 5,
     TwoRowSierra = 6,
     SierraLite = 7,
+    Fan = 8,
+    ShiauFan = 9,
+    ShiauFan2 = 10,
+    Ostromoukhov = 11,
+}
+
+/// A custom error diffusion kernel.
+///
+/// The first row of the weights is the row of the current pixel and `origin`
+/// is the column of the current pixel.
+#[pyclass(frozen)]
+#[derive(Clone, PartialEq, Debug)]
+pub struct DiffusionKernel {
+    inner: image_ops::dither::DiffusionKernel,
+}
+
+#[pymethods]
+impl DiffusionKernel {
+    #[new]
+    pub fn new(weights: PyImage, origin: u32) -> PyResult<Self> {
+        let weights: Image<f32> = weights.load_image()?;
+        Ok(Self {
+            inner: image_ops::dither::DiffusionKernel::new(weights.view(), origin as usize)
+                .map_err(to_py_error)?,
+        })
+    }
+}
+
+#[derive(FromPyObject)]
+pub enum Diffusion {
+    Algorithm(DiffusionAlgorithm),
+    Kernel(DiffusionKernel),
 }
 
 #[pyfunction]
 pub fn quantize<'py>(
     py: Python<'py>,
-    img: PyImage<'py>,
+    img: &'py PyAny,
     quant: Quant,
-) -> PyResult<&'py PyArray3<f32>> {
-    match quant {
-        Quant::Uniform(quant) => {
-            let mut img: NDimImage = img.load_image()?;
-            let result = py.allow_threads(|| {
-                image_ops::dither::quantize_ndim(&mut img, quant.inner);
-                img.into_numpy()
-            });
-            Ok(result.into_pyarray(py))
+    inplace: Option<bool>,
+) -> PyResult<PyObject> {
+    return match SampleType::of(img) {
+        SampleType::F32 => with_sample::<f32>(py, img, quant, inplace),
+        SampleType::F16 => with_sample::<f16>(py, img, quant, inplace),
+        SampleType::U8 => with_sample::<u8>(py, img, quant, inplace),
+        SampleType::U16 => with_sample::<u16>(py, img, quant, inplace),
+    };
+
+    fn with_sample<'py, T: PySample>(
+        py: Python<'py>,
+        img: &'py PyAny,
+        quant: Quant,
+        inplace: Option<bool>,
+    ) -> PyResult<PyObject> {
+        let img = ImageArg::<T>::new(img, inplace)?;
+        match quant {
+            Quant::Uniform(quant) => img.apply_ndim(py, |img| {
+                image_ops::dither::quantize_ndim(img, quant.inner);
+            }),
+            Quant::Palette(quant) => {
+                fn with_pixel_format<'py, T, P>(
+                    py: Python<'py>,
+                    img: ImageArg<'py, T>,
+                    quant: impl Quantizer<P, P> + Sync,
+                ) -> PyResult<PyObject>
+                where
+                    T: PySample,
+                    P: Pixel + Send + FromFlat + Flatten,
+                {
+                    img.apply(py, |img| image_ops::dither::quantize(img, &quant))
+                }
+
+                let c = img.channels();
+                match c {
+                    1 => with_pixel_format::<T, f32>(py, img, quant.into_quantizer()?),
+                    3 => with_pixel_format::<T, Vec3A>(py, img, quant.into_quantizer()?),
+                    4 => with_pixel_format::<T, Vec4>(py, img, quant.into_quantizer()?),
+                    _ => Err(PyValueError::new_err(format!(
+                            "Argument '{}' does not have the right shape. Expected 1, 3, or 4 channels but found {}.",
+                            stringify!(img),
+                            c
+                        ))),
+                }
+            }
         }
+    }
+}
+
+#[pyclass]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ThresholdMapType {
+    Bayer = 0,
+    BlueNoise = 1,
+}
+
+enum ThresholdMap {
+    Bayer(usize),
+    Custom(Arc<Image<f32>>),
+}
+
+/// The largest supported size of blue-noise maps. Generating maps takes time
+/// proportional to `size^4`, so larger maps would take minutes.
+const MAX_BLUE_NOISE_MAP_SIZE: usize = 128;
+
+#[pyfunction]
+pub fn ordered_dither<'py>(
+    py: Python<'py>,
+    img: &'py PyAny,
+    quant: Quant,
+    map_size: u32,
+    inplace: Option<bool>,
+    map_type: Option<ThresholdMapType>,
+) -> PyResult<PyObject> {
+    let map_size = map_size as usize;
+    let map = match map_type.unwrap_or(ThresholdMapType::Bayer) {
+        ThresholdMapType::Bayer => ThresholdMap::Bayer(map_size),
+        ThresholdMapType::BlueNoise => {
+            if !(1..=MAX_BLUE_NOISE_MAP_SIZE).contains(&map_size) {
+                return Err(PyValueError::new_err(format!(
+                    "Expected the map size to be between 1 and {MAX_BLUE_NOISE_MAP_SIZE}, but found {map_size}."
+                )));
+            }
+            ThresholdMap::Custom(py.allow_threads(|| blue_noise_map(map_size)))
+        }
+    };
+    with_threshold_map(py, ImageArg::new(img, inplace)?, quant, map)
+}
+
+/// Ordered dithering with a custom threshold map. The map is a 2D array of
+/// thresholds in the range 0 (inclusive) to 1 (exclusive) that is repeated
+/// across the image.
+#[pyfunction]
+pub fn ordered_dither_with_map<'py>(
+    py: Python<'py>,
+    img: &'py PyAny,
+    quant: Quant,
+    threshold_map: PyImage,
+    inplace: Option<bool>,
+) -> PyResult<PyObject> {
+    let threshold_map: Image<f32> = threshold_map.load_image()?;
+    with_threshold_map(
+        py,
+        ImageArg::new(img, inplace)?,
+        quant,
+        ThresholdMap::Custom(Arc::new(threshold_map)),
+    )
+}
+
+fn with_threshold_map<'py>(
+    py: Python<'py>,
+    img: ImageArg<'py>,
+    quant: Quant,
+    map: ThresholdMap,
+) -> PyResult<PyObject> {
+    match quant {
+        Quant::Uniform(quant) => img.try_apply_ndim(py, |img| {
+            match &map {
+                ThresholdMap::Bayer(n) => {
+                    image_ops::dither::try_ordered_dither(img, *n, quant.inner)
+                }
+                ThresholdMap::Custom(map) => {
+                    image_ops::dither::try_ordered_dither_with_map(img, map.view(), quant.inner)
+                }
+            }
+            .map_err(to_py_error)
+        }),
         Quant::Palette(quant) => {
             fn with_pixel_format<'py, P>(
                 py: Python<'py>,
-                img: PyImage<'py>,
+                img: ImageArg<'py>,
+                map: ThresholdMap,
                 quant: impl Quantizer<P, P> + Sync,
-            ) -> PyResult<&'py PyArray3<f32>>
+            ) -> PyResult<PyObject>
             where
-                P: Pixel + Send + FromFlat,
-                Image<P>: IntoNumpy,
+                P: Pixel + Luminance + Send + Sync + FromFlat + Flatten,
             {
-                let mut img: Image<P> = img.load_image()?;
-                let result = py.allow_threads(|| {
-                    image_ops::dither::quantize(&mut img, &quant);
-                    img.into_numpy()
-                });
-                Ok(result.into_pyarray(py))
+                img.try_apply(py, |img| {
+                    match &map {
+                        ThresholdMap::Bayer(n) => {
+                            image_ops::dither::try_palette_ordered_dither(img, *n, &quant)
+                        }
+                        ThresholdMap::Custom(map) => {
+                            image_ops::dither::try_palette_ordered_dither_with_map(
+                                img,
+                                map.view(),
+                                &quant,
+                            )
+                        }
+                    }
+                    .map_err(to_py_error)
+                })
             }
 
             let c = img.channels();
             match c {
-                1 => with_pixel_format::<f32>(py, img, quant.into_quantizer()),
-                3 => with_pixel_format::<Vec3A>(py, img, quant.into_quantizer()),
-                4 => with_pixel_format::<Vec4>(py, img, quant.into_quantizer()),
+                1 => with_pixel_format::<f32>(py, img, map, quant.into_quantizer()?),
+                3 => with_pixel_format::<Vec3A>(py, img, map, quant.into_quantizer()?),
+                4 => with_pixel_format::<Vec4>(py, img, map, quant.into_quantizer()?),
                 _ => Err(PyValueError::new_err(format!(
-                        "Argument '{}' does not have the right shape. Expected 1, 3, or 4 channels but found {}.",
-                        stringify!(img),
-                        c
-                    ))),
+                    "Argument '{}' does not have the right shape. Expected 1, 3, or 4 channels but found {}.",
+                    stringify!(img),
+                    c
+                ))),
             }
         }
     }
 }
 
-#[pyfunction]
-pub fn ordered_dither<'py>(
-    py: Python<'py>,
-    img: PyImage,
-    quant: UniformQuantization,
-    map_size: u32,
-) -> PyResult<&'py PyArray3<f32>> {
-    if !map_size.is_power_of_two() {
-        return Err(PyValueError::new_err(format!(
-            "Argument '{}' must be a power of 2.",
-            stringify!(map_size)
-        )));
-    }
-
-    let mut img = img.load_image()?;
-    let result = py.allow_threads(|| {
-        image_ops::dither::ordered_dither(&mut img, map_size as usize, quant.inner);
-        img.into_numpy()
-    });
-    Ok(result.into_pyarray(py))
-}
-
 mod diffusion {
-    use image_core::FromFlat;
+    use image_core::{Flatten, FromFlat};
 
     use super::*;
 
-    pub struct Config<'py>(pub Python<'py>, pub PyImage<'py>);
+    pub struct Config<'py>(pub Python<'py>, pub ImageArg<'py>, pub ScanOrder);
 
     fn with_pixel_format<P>(
-        Config(py, img): Config<'_>,
+        Config(py, img, scan): Config<'_>,
         quant: impl Quantizer<P, P> + Sync,
         algorithm: impl image_ops::dither::DiffusionAlgorithm + Send,
-    ) -> PyResult<&PyArray3<f32>>
+    ) -> PyResult<PyObject>
     where
-        P: Pixel + Send + FromFlat,
+        P: Pixel + Luminance + Send + FromFlat + Flatten,
         Image<P>: IntoNumpy,
     {
-        let mut img: Image<P> = img.load_image()?;
-        let result = py.allow_threads(|| {
-            image_ops::dither::error_diffusion_dither(&mut img, algorithm, &quant);
-            img.into_numpy()
-        });
-        Ok(result.into_pyarray(py))
+        img.apply(py, |img| {
+            image_ops::dither::error_diffusion_dither(img, algorithm, &quant, scan);
+        })
     }
 
     pub fn with_algorithm(
         config: Config,
         quant: Quant,
         algorithm: impl image_ops::dither::DiffusionAlgorithm + Send,
-    ) -> PyResult<&PyArray3<f32>> {
+    ) -> PyResult<PyObject> {
         let c = config.1.channels();
         let err = Err(PyValueError::new_err(format!(
             "Argument '{}' does not have the right shape. Expected 1, 3, or 4 channels but found {}.",
@@ -246,9 +479,9 @@ mod diffusion {
                 _ => err,
             },
             Quant::Palette(quant) => match c {
-                1 => with_pixel_format::<f32>(config, quant.into_quantizer(), algorithm),
-                3 => with_pixel_format::<Vec3A>(config, quant.into_quantizer(), algorithm),
-                4 => with_pixel_format::<Vec4>(config, quant.into_quantizer(), algorithm),
+                1 => with_pixel_format::<f32>(config, quant.into_quantizer()?, algorithm),
+                3 => with_pixel_format::<Vec3A>(config, quant.into_quantizer()?, algorithm),
+                4 => with_pixel_format::<Vec4>(config, quant.into_quantizer()?, algorithm),
                 _ => err,
             },
         }
@@ -258,13 +491,24 @@ mod diffusion {
 #[pyfunction]
 pub fn error_diffusion_dither<'py>(
     py: Python<'py>,
-    img: PyImage<'py>,
+    img: &'py PyAny,
     quant: Quant,
-    algorithm: DiffusionAlgorithm,
-) -> PyResult<&'py PyArray3<f32>> {
+    algorithm: Diffusion,
+    inplace: Option<bool>,
+    serpentine: Option<bool>,
+) -> PyResult<PyObject> {
     use diffusion::*;
 
-    let config: Config<'py> = Config(py, img);
+    let scan = if serpentine.unwrap_or(false) {
+        ScanOrder::Serpentine
+    } else {
+        ScanOrder::Raster
+    };
+    let config: Config<'py> = Config(py, ImageArg::new(img, inplace)?, scan);
+    let algorithm = match algorithm {
+        Diffusion::Algorithm(algorithm) => algorithm,
+        Diffusion::Kernel(kernel) => return with_algorithm(config, quant, kernel.inner),
+    };
     match algorithm {
         DiffusionAlgorithm::FloydSteinberg => with_algorithm(config, quant, FloydSteinberg),
         DiffusionAlgorithm::JarvisJudiceNinke => with_algorithm(config, quant, JarvisJudiceNinke),
@@ -274,52 +518,47 @@ pub fn error_diffusion_dither<'py>(
         DiffusionAlgorithm::Sierra => with_algorithm(config, quant, Sierra),
         DiffusionAlgorithm::TwoRowSierra => with_algorithm(config, quant, TwoRowSierra),
         DiffusionAlgorithm::SierraLite => with_algorithm(config, quant, SierraLite),
+        DiffusionAlgorithm::Fan => with_algorithm(config, quant, Fan),
+        DiffusionAlgorithm::ShiauFan => with_algorithm(config, quant, ShiauFan),
+        DiffusionAlgorithm::ShiauFan2 => with_algorithm(config, quant, ShiauFan2),
+        DiffusionAlgorithm::Ostromoukhov => with_algorithm(config, quant, Ostromoukhov),
     }
 }
 
 mod riemersma {
-    use image_core::FromFlat;
-
-    use crate::convert::LoadImage;
+    use image_core::{Flatten, FromFlat};
 
     use super::*;
 
-    pub struct Config<'py>(pub Python<'py>, pub PyImage<'py>, pub usize, pub f32);
+    pub struct Config<'py>(pub Python<'py>, pub ImageArg<'py>, pub usize, pub f32);
 
     pub fn with_pixel_format<P>(
         Config(py, img, history_length, decay_ratio): Config<'_>,
         quant: impl Quantizer<P, P> + Sync,
-    ) -> PyResult<&PyArray3<f32>>
+    ) -> PyResult<PyObject>
     where
-        P: Pixel + Send + FromFlat,
+        P: Pixel + Send + FromFlat + Flatten,
         Image<P>: IntoNumpy,
     {
-        let mut img: Image<P> = img.load_image()?;
-        let result = py.allow_threads(|| {
-            image_ops::dither::riemersma_dither(&mut img, history_length, decay_ratio, &quant);
-            img.into_numpy()
-        });
-        Ok(result.into_pyarray(py))
+        img.try_apply(py, |img| {
+            image_ops::dither::try_riemersma_dither(img, history_length, decay_ratio, &quant)
+                .map_err(to_py_error)
+        })
     }
 }
 
 #[pyfunction]
 pub fn riemersma_dither<'py>(
     py: Python<'py>,
-    img: PyImage<'py>,
+    img: &'py PyAny,
     quant: Quant,
     history_length: u32,
     decay_ratio: f32,
-) -> PyResult<&'py PyArray3<f32>> {
-    if history_length < 2 {
-        return Err(PyValueError::new_err(format!(
-            "Argument '{}' must be at least 2.",
-            stringify!(history_length)
-        )));
-    }
-
+    inplace: Option<bool>,
+) -> PyResult<PyObject> {
     use riemersma::*;
 
+    let img = ImageArg::new(img, inplace)?;
     let c = img.channels();
     let config: Config<'py> = Config(py, img, history_length as usize, decay_ratio);
     let err = PyValueError::new_err(format!(
@@ -336,9 +575,9 @@ pub fn riemersma_dither<'py>(
             _ => Err(err),
         },
         Quant::Palette(quant) => match c {
-            1 => with_pixel_format::<f32>(config, quant.into_quantizer()),
-            3 => with_pixel_format::<Vec3A>(config, quant.into_quantizer()),
-            4 => with_pixel_format::<Vec4>(config, quant.into_quantizer()),
+            1 => with_pixel_format::<f32>(config, quant.into_quantizer()?),
+            3 => with_pixel_format::<Vec3A>(config, quant.into_quantizer()?),
+            4 => with_pixel_format::<Vec4>(config, quant.into_quantizer()?),
             _ => Err(err),
         },
     }
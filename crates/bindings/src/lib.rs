@@ -1,16 +1,30 @@
+// The `#[pymethods]` expansion of our pyo3 version trips this lint on newer
+// compilers.
+#![allow(non_local_definitions)]
+
+mod blur;
 mod clipboard;
+mod components;
 mod convert;
+mod convolve;
 mod dither;
+mod morphology;
+mod palette;
 mod pixel_art;
 mod regex;
 mod resize;
+mod sharpen;
+mod smooth;
+mod tile;
+mod trim;
+mod warp;
 
 use image_core::{Image, NDimImage};
-use image_ops::fill_alpha::{fill_alpha, FillMode};
+use image_ops::fill_alpha::{fill_alpha, try_fill_alpha, FillAlphaError, FillMode};
 use numpy::{IntoPyArray, PyArray3};
-use pyo3::prelude::*;
+use pyo3::{exceptions::PyValueError, prelude::*};
 
-use crate::convert::{IntoNumpy, LoadImage, PyImage};
+use crate::convert::{ImageArg, IntoNumpy, LoadImage, PyImage};
 
 /// A Python module implemented in Rust.
 #[pymodule]
@@ -22,77 +36,115 @@ fn chainner_ext(_py: Python, m: &PyModule) -> PyResult<()> {
     m.add_class::<clipboard::Clipboard>()?;
 
     m.add_class::<dither::DiffusionAlgorithm>()?;
+    m.add_class::<dither::DiffusionKernel>()?;
     m.add_class::<dither::UniformQuantization>()?;
     m.add_class::<dither::PaletteQuantization>()?;
+    m.add_class::<dither::PaletteColorSpace>()?;
+    m.add_class::<dither::ThresholdMapType>()?;
     m.add_wrapped(wrap_pyfunction!(dither::quantize))?;
     m.add_wrapped(wrap_pyfunction!(dither::error_diffusion_dither))?;
     m.add_wrapped(wrap_pyfunction!(dither::ordered_dither))?;
+    m.add_wrapped(wrap_pyfunction!(dither::ordered_dither_with_map))?;
     m.add_wrapped(wrap_pyfunction!(dither::riemersma_dither))?;
 
+    m.add_class::<palette::PaletteAlgorithm>()?;
+    m.add_wrapped(wrap_pyfunction!(palette::generate_palette))?;
+
     m.add_wrapped(wrap_pyfunction!(pixel_art::pixel_art_upscale))?;
 
     m.add_class::<resize::ResizeFilter>()?;
+    m.add_class::<resize::GammaCorrection>()?;
     m.add_wrapped(wrap_pyfunction!(resize::resize))?;
 
+    m.add_class::<warp::BorderMode>()?;
+    m.add_wrapped(wrap_pyfunction!(warp::rotate))?;
+    m.add_wrapped(wrap_pyfunction!(warp::warp_affine))?;
+    m.add_wrapped(wrap_pyfunction!(warp::warp_perspective))?;
+
+    m.add_class::<tile::TileGrid>()?;
+
+    m.add_class::<trim::TrimMode>()?;
+    m.add_wrapped(wrap_pyfunction!(trim::trim))?;
+
+    m.add_wrapped(wrap_pyfunction!(blur::gaussian_blur))?;
+    m.add_wrapped(wrap_pyfunction!(blur::box_blur))?;
+    m.add_wrapped(wrap_pyfunction!(blur::stack_blur))?;
+
+    m.add_wrapped(wrap_pyfunction!(convolve::convolve))?;
+
+    m.add_wrapped(wrap_pyfunction!(smooth::median_filter))?;
+    m.add_wrapped(wrap_pyfunction!(smooth::bilateral_filter))?;
+    m.add_wrapped(wrap_pyfunction!(smooth::guided_filter))?;
+
+    m.add_class::<components::Connectivity>()?;
+    m.add_class::<components::Region>()?;
+    m.add_wrapped(wrap_pyfunction!(components::connected_components))?;
+
+    m.add_class::<morphology::MorphOperation>()?;
+    m.add_class::<morphology::ElementShape>()?;
+    m.add_wrapped(wrap_pyfunction!(morphology::morphology))?;
+
+    m.add_class::<sharpen::SharpenMode>()?;
+    m.add_wrapped(wrap_pyfunction!(sharpen::unsharp_mask))?;
+    m.add_wrapped(wrap_pyfunction!(sharpen::high_pass))?;
+
     /// Fill the transparent pixels in the given image with nearby colors.
     #[pyfn(m)]
     fn fill_alpha_fragment_blur<'py>(
         py: Python<'py>,
-        img: PyImage,
+        img: &'py PyAny,
         threshold: f32,
         iterations: u32,
         fragment_count: u32,
-    ) -> PyResult<&'py PyArray3<f32>> {
-        let mut img = img.load_image()?;
-        let result = py.allow_threads(|| {
-            fill_alpha(
-                &mut img,
+        inplace: Option<bool>,
+    ) -> PyResult<PyObject> {
+        ImageArg::<f32>::new(img, inplace)?.try_apply(py, |img| {
+            try_fill_alpha(
+                img,
                 threshold,
                 FillMode::Fragment {
                     iterations,
                     fragment_count,
                 },
                 None,
-            );
-            img.into_numpy()
-        });
-        Ok(result.into_pyarray(py))
+            )
+            .map_err(|e| match e {
+                FillAlphaError::InvalidFragmentCount { fragment_count } => {
+                    PyValueError::new_err(format!(
+                        "Expected a fragment count between 1 and 255, but found {fragment_count}."
+                    ))
+                }
+            })
+        })
     }
 
     /// Fill the transparent pixels in the given image with nearby colors.
     #[pyfn(m)]
     fn fill_alpha_extend_color<'py>(
         py: Python<'py>,
-        img: PyImage,
+        img: &'py PyAny,
         threshold: f32,
         iterations: u32,
-    ) -> PyResult<&'py PyArray3<f32>> {
-        let mut img = img.load_image()?;
-        let result = py.allow_threads(|| {
-            fill_alpha(
-                &mut img,
-                threshold,
-                FillMode::ExtendColor { iterations },
-                None,
-            );
-            img.into_numpy()
-        });
-        Ok(result.into_pyarray(py))
+        inplace: Option<bool>,
+    ) -> PyResult<PyObject> {
+        ImageArg::<f32>::new(img, inplace)?.apply(py, |img| {
+            fill_alpha(img, threshold, FillMode::ExtendColor { iterations }, None);
+        })
     }
 
     /// Fill the transparent pixels in the given image with nearby colors.
     #[pyfn(m)]
     fn fill_alpha_nearest_color<'py>(
         py: Python<'py>,
-        img: PyImage,
+        img: &'py PyAny,
         threshold: f32,
         min_radius: u32,
         anti_aliasing: bool,
-    ) -> PyResult<&'py PyArray3<f32>> {
-        let mut img = img.load_image()?;
-        let result = py.allow_threads(|| {
+        inplace: Option<bool>,
+    ) -> PyResult<PyObject> {
+        ImageArg::<f32>::new(img, inplace)?.apply(py, |img| {
             fill_alpha(
-                &mut img,
+                img,
                 threshold,
                 FillMode::Nearest {
                     min_radius,
@@ -100,9 +152,7 @@ fn chainner_ext(_py: Python, m: &PyModule) -> PyResult<()> {
                 },
                 None,
             );
-            img.into_numpy()
-        });
-        Ok(result.into_pyarray(py))
+        })
     }
 
     /// Fill the transparent pixels in the given image with nearby colors.
@@ -157,5 +207,27 @@ fn chainner_ext(_py: Python, m: &PyModule) -> PyResult<()> {
         Ok(result.into_pyarray(py))
     }
 
+    /// Converts the given sRGB image to linear RGB.
+    #[pyfn(m)]
+    fn srgb_to_linear<'py>(py: Python<'py>, img: PyImage) -> PyResult<&'py PyArray3<f32>> {
+        let mut img = img.load_image()?;
+        let result = py.allow_threads(|| {
+            image_ops::gamma::srgb_to_linear_ndim(&mut img);
+            img.into_numpy()
+        });
+        Ok(result.into_pyarray(py))
+    }
+
+    /// Converts the given linear RGB image to sRGB.
+    #[pyfn(m)]
+    fn linear_to_srgb<'py>(py: Python<'py>, img: PyImage) -> PyResult<&'py PyArray3<f32>> {
+        let mut img = img.load_image()?;
+        let result = py.allow_threads(|| {
+            image_ops::gamma::linear_to_srgb_ndim(&mut img);
+            img.into_numpy()
+        });
+        Ok(result.into_pyarray(py))
+    }
+
     Ok(())
 }
@@ -0,0 +1,114 @@
+use image_core::{NDimCow, Size};
+use image_ops::morphology::{Operation, StructuringElement};
+use numpy::{IntoPyArray, PyArray3};
+use pyo3::{exceptions::PyValueError, prelude::*};
+
+use crate::convert::{IntoNumpy, LoadImage, PyImage};
+
+#[pyclass]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum MorphOperation {
+    Dilate = 0,
+    Erode = 1,
+    Open = 2,
+    Close = 3,
+    Gradient = 4,
+    TopHat = 5,
+    BlackHat = 6,
+}
+
+impl From<MorphOperation> for Operation {
+    fn from(o: MorphOperation) -> Self {
+        match o {
+            MorphOperation::Dilate => Operation::Dilate,
+            MorphOperation::Erode => Operation::Erode,
+            MorphOperation::Open => Operation::Open,
+            MorphOperation::Close => Operation::Close,
+            MorphOperation::Gradient => Operation::Gradient,
+            MorphOperation::TopHat => Operation::TopHat,
+            MorphOperation::BlackHat => Operation::BlackHat,
+        }
+    }
+}
+
+#[pyclass]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ElementShape {
+    Square = 0,
+    Disc = 1,
+}
+
+/// Either a `(shape, radius)` pair or a 2D array whose non-zero values are
+/// part of the structuring element.
+#[derive(FromPyObject)]
+pub enum ElementArg<'py> {
+    Shape(ElementShape, u32),
+    Custom(PyImage<'py>),
+}
+
+impl ElementArg<'_> {
+    /// Loads the element for an image of the given size.
+    ///
+    /// Offsets outside the image are ignored, so the radius of shapes is
+    /// limited to what can reach across the image. This doesn't change the
+    /// result, but keeps huge radii from allocating huge elements.
+    fn load(&self, size: Size) -> PyResult<StructuringElement> {
+        match self {
+            ElementArg::Shape(ElementShape::Square, radius) => {
+                let max_radius = size.width.max(size.height);
+                Ok(StructuringElement::square(
+                    (*radius as usize).min(max_radius),
+                ))
+            }
+            ElementArg::Shape(ElementShape::Disc, radius) => {
+                let diagonal = (size.width as f64).hypot(size.height as f64).ceil() as usize;
+                Ok(StructuringElement::disc((*radius as usize).min(diagonal)))
+            }
+            ElementArg::Custom(element) => {
+                let element: NDimCow = element.load_image()?;
+                let invalid = || {
+                    PyValueError::new_err(format!(
+                        "Argument '{}' must be a 2D array with at least one non-zero value.",
+                        stringify!(element)
+                    ))
+                };
+                if element.channels() != 1 {
+                    return Err(invalid());
+                }
+                let data = element.data().iter().map(|v| *v != 0.0).collect();
+                StructuringElement::new(element.size(), data).map_err(|_| invalid())
+            }
+        }
+    }
+}
+
+/// Applies a morphological operation to each channel of the image.
+///
+/// In binary mode, values >= 0.5 are foreground, and the result only contains
+/// 0 and 1.
+#[pyfunction]
+pub fn morphology<'py>(
+    py: Python<'py>,
+    img: PyImage,
+    operation: MorphOperation,
+    element: ElementArg,
+    binary: Option<bool>,
+) -> PyResult<&'py PyArray3<f32>> {
+    let operation = operation.into();
+    let element = element.load(img.size())?;
+
+    if binary.unwrap_or(false) {
+        let img: NDimCow = img.load_image()?;
+        let result = py.allow_threads(|| {
+            image_ops::morphology::morphology_binary_ndim(img.view(), operation, &element)
+                .into_numpy()
+        });
+        return Ok(result.into_pyarray(py));
+    }
+
+    let img: NDimCow = img.load_image()?;
+    let result = py.allow_threads(|| {
+        image_ops::morphology::morphology_ndim(img.view(), operation, &element).into_numpy()
+    });
+    Ok(result.into_pyarray(py))
+}
@@ -0,0 +1,78 @@
+use image_core::NDimCow;
+use image_ops::palette::{PaletteError, PaletteOptions, PaletteSpace};
+use numpy::{IntoPyArray, PyArray3};
+use pyo3::{exceptions::PyValueError, prelude::*};
+
+use crate::{
+    convert::{IntoNumpy, LoadImage, PyImage},
+    dither::PaletteColorSpace,
+};
+
+#[pyclass]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum PaletteAlgorithm {
+    MedianCut = 0,
+    Octree = 1,
+    Wu = 2,
+    KMeans = 3,
+}
+
+/// Generates a palette of at most `max_colors` colors for the given image.
+///
+/// The palette is returned as an image with a height of 1, so it can be used
+/// with `PaletteQuantization`. `seed` is only used by k-means.
+#[pyfunction]
+pub fn generate_palette<'py>(
+    py: Python<'py>,
+    img: PyImage,
+    max_colors: u32,
+    algorithm: PaletteAlgorithm,
+    color_space: Option<PaletteColorSpace>,
+    alpha: Option<bool>,
+    seed: Option<u64>,
+) -> PyResult<&'py PyArray3<f32>> {
+    let color_space = match color_space.unwrap_or(PaletteColorSpace::Rgb) {
+        PaletteColorSpace::Rgb => PaletteSpace::Rgb,
+        PaletteColorSpace::LinearRgb => PaletteSpace::LinearRgb,
+        PaletteColorSpace::OkLab => PaletteSpace::OkLab,
+        PaletteColorSpace::CieLab76 => PaletteSpace::CieLab,
+        PaletteColorSpace::CieLab2000 => {
+            return Err(PyValueError::new_err(
+                "Palettes can't be generated with CIEDE2000. Use CieLab76 instead.",
+            ));
+        }
+    };
+    let options = PaletteOptions {
+        color_space,
+        alpha: alpha.unwrap_or(PaletteOptions::default().alpha),
+    };
+    let algorithm = match algorithm {
+        PaletteAlgorithm::MedianCut => image_ops::palette::PaletteAlgorithm::MedianCut,
+        PaletteAlgorithm::Octree => image_ops::palette::PaletteAlgorithm::Octree,
+        PaletteAlgorithm::Wu => image_ops::palette::PaletteAlgorithm::Wu,
+        PaletteAlgorithm::KMeans => image_ops::palette::PaletteAlgorithm::KMeans {
+            seed: seed.unwrap_or(0),
+        },
+    };
+
+    let img: NDimCow = img.load_image()?;
+    let result = py.allow_threads(|| {
+        image_ops::palette::generate_palette_ndim(
+            img.view(),
+            max_colors as usize,
+            algorithm,
+            options,
+        )
+        .map(|palette| palette.into_numpy())
+    });
+
+    match result {
+        Ok(palette) => Ok(palette.into_pyarray(py)),
+        Err(PaletteError::ZeroColors) => Err(PyValueError::new_err(
+            "Expected a palette with at least 1 color, but found 0.",
+        )),
+        Err(PaletteError::UnsupportedChannels { channels }) => Err(PyValueError::new_err(format!(
+            "Expected an image with 1, 2, 3, or 4 channels, but found {channels}."
+        ))),
+    }
+}
@@ -1,38 +1,66 @@
 use std::ops::{Add, Mul};
 
 use glam::{Vec3A, Vec4};
-use image_core::{FromFlat, Image};
+use image_core::{f16, Flatten, FromFlat, Image};
 use image_ops::pixel_art::IntoYuv;
-use numpy::{IntoPyArray, PyArray3};
+use numpy::IntoPyArray;
 use pyo3::{exceptions::PyValueError, prelude::*};
 
-use crate::convert::{IntoNumpy, LoadImage, PyImage};
+use crate::convert::{ndim_into_numpy, PyImage, PySample, SampleType};
 
 #[pyfunction]
 pub fn pixel_art_upscale<'py>(
     py: Python<'py>,
-    img: PyImage<'py>,
+    img: &'py PyAny,
     algorithm: &str,
     scale: u32,
-) -> PyResult<&'py PyArray3<f32>> {
-    fn with_pixel_format<'py, P>(
+) -> PyResult<PyObject> {
+    return match SampleType::of(img) {
+        SampleType::F32 => with_sample::<f32>(py, img.extract()?, algorithm, scale),
+        SampleType::F16 => with_sample::<f16>(py, img.extract()?, algorithm, scale),
+        SampleType::U8 => with_sample::<u8>(py, img.extract()?, algorithm, scale),
+        SampleType::U16 => with_sample::<u16>(py, img.extract()?, algorithm, scale),
+    };
+
+    fn with_sample<'py, T: PySample>(
+        py: Python<'py>,
+        img: PyImage<'py, T>,
+        algorithm: &str,
+        scale: u32,
+    ) -> PyResult<PyObject> {
+        let c = img.channels();
+        match c {
+            1 => with_pixel_format::<T, f32>(py, img, algorithm, scale),
+            3 => with_pixel_format::<T, Vec3A>(py, img, algorithm, scale),
+            4 => with_pixel_format::<T, Vec4>(py, img, algorithm, scale),
+            _ => Err(PyValueError::new_err(format!(
+                "Argument '{}' does not have the right shape. Expected 1, 3, or 4 channels but found {}.",
+                stringify!(img),
+                c
+            ))),
+        }
+    }
+
+    fn with_pixel_format<'py, T, P>(
         py: Python<'py>,
-        img: PyImage<'py>,
+        img: PyImage<'py, T>,
         algorithm: &str,
         scale: u32,
-    ) -> PyResult<&'py PyArray3<f32>>
+    ) -> PyResult<PyObject>
     where
+        T: PySample,
         P: FromFlat
+            + Flatten
             + Default
             + Copy
             + PartialEq
             + IntoYuv
             + Add<P, Output = P>
             + Mul<f32, Output = P>
+            + Send
             + Sync,
-        Image<P>: IntoNumpy,
     {
-        let img: Image<P> = img.load_image()?;
+        let img: Image<P> = T::to_pixels(img.as_contiguous()?.view()).expect("");
         let result = py.allow_threads(|| {
             let result: Image<P> = match algorithm {
                 "adv_mame" => match scale {
@@ -101,20 +129,8 @@ pub fn pixel_art_upscale<'py>(
                     )))
                 }
             };
-            Ok(result.into_numpy())
+            Ok(ndim_into_numpy(T::from_pixels(result)))
         })?;
-        Ok(result.into_pyarray(py))
-    }
-
-    let c = img.channels();
-    match c {
-        1 => with_pixel_format::<f32>(py, img, algorithm,scale),
-        3 => with_pixel_format::<Vec3A>(py, img, algorithm,scale),
-        4 => with_pixel_format::<Vec4>(py, img, algorithm,scale),
-        _ => Err(PyValueError::new_err(format!(
-            "Argument '{}' does not have the right shape. Expected 1, 3, or 4 channels but found {}.",
-            stringify!(img),
-            c
-        ))),
+        Ok(result.into_pyarray(py).into_py(py))
     }
 }
@@ -1,11 +1,14 @@
 use glam::{Vec2, Vec3A, Vec4};
-use image_core::{ClipFloat, Flatten, FromFlat, Image, ImageView, IntoPixels, NDimImage, Size};
-use image_ops::scale::{Filter, FloatPixelFormat, PixelFormat};
+use image_core::{
+    f16, ClipFloat, Flatten, FromFlat, Image, ImageView, IntoPixels, NDimCow, NDimImage, NDimView,
+    Size,
+};
+use image_ops::scale::{Filter, FloatPixelFormat, PixelFormat, SourceRect};
 use numpy::{IntoPyArray, PyArray3};
 use pyo3::{exceptions::PyValueError, prelude::*};
 
 use crate::{
-    convert::{LoadImage, PyImage, ViewImage},
+    convert::{ndim_into_numpy, LoadImage, PyImage, PySample, SampleType, ViewImage},
     IntoNumpy,
 };
 
@@ -24,6 +27,57 @@ pub enum ResizeFilter {
     Lanczos = 4,
     Lagrange = 7,
     Gauss = 5,
+    MKS2013 = 12,
+    MKS2021 = 13,
+}
+
+#[pyclass]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum GammaCorrection {
+    Off = 0,
+    Gamma22 = 1,
+    Srgb = 2,
+}
+
+impl GammaCorrection {
+    fn linearize(self, img: &mut NDimImage) {
+        match self {
+            GammaCorrection::Off => {}
+            GammaCorrection::Gamma22 => image_ops::gamma::gamma_ndim(img, 2.2),
+            GammaCorrection::Srgb => image_ops::gamma::srgb_to_linear_ndim(img),
+        }
+    }
+    fn delinearize(self, img: &mut NDimImage) {
+        match self {
+            GammaCorrection::Off => {}
+            GammaCorrection::Gamma22 => image_ops::gamma::gamma_ndim(img, 1.0 / 2.2),
+            GammaCorrection::Srgb => image_ops::gamma::linear_to_srgb_ndim(img),
+        }
+    }
+}
+
+/// For backwards compatibility, `True` means power-2.2 gamma correction.
+#[derive(FromPyObject)]
+pub enum GammaCorrectionArg {
+    Bool(bool),
+    Mode(GammaCorrection),
+}
+
+impl From<GammaCorrectionArg> for GammaCorrection {
+    fn from(arg: GammaCorrectionArg) -> Self {
+        match arg {
+            GammaCorrectionArg::Bool(true) => GammaCorrection::Gamma22,
+            GammaCorrectionArg::Bool(false) => GammaCorrection::Off,
+            GammaCorrectionArg::Mode(mode) => mode,
+        }
+    }
+}
+
+/// Either one filter for both axes or a `(horizontal, vertical)` pair.
+#[derive(FromPyObject)]
+pub enum ResizeFilterArg {
+    Single(ResizeFilter),
+    PerAxis(ResizeFilter, ResizeFilter),
 }
 
 impl From<ResizeFilter> for Filter {
@@ -41,53 +95,185 @@ impl From<ResizeFilter> for Filter {
             ResizeFilter::Lanczos => Filter::Lanczos3,
             ResizeFilter::Lagrange => Filter::Lagrange,
             ResizeFilter::Gauss => Filter::Gauss,
+            ResizeFilter::MKS2013 => Filter::MagicKernelSharp2013,
+            ResizeFilter::MKS2021 => Filter::MagicKernelSharp2021,
         }
     }
 }
 
+/// All parameters that describe how the image is scaled.
+#[derive(Debug, Clone, Copy)]
+struct Scaling {
+    rect: SourceRect,
+    size: Size,
+    filter_x: Filter,
+    filter_y: Filter,
+}
+
+impl Scaling {
+    fn interpolates(&self) -> bool {
+        self.filter_x != Filter::Nearest || self.filter_y != Filter::Nearest
+    }
+    fn may_overshoot(&self) -> bool {
+        let overshoots = |f: Filter| f != Filter::Nearest && f != Filter::Linear;
+        overshoots(self.filter_x) || overshoots(self.filter_y)
+    }
+
+    fn scale<P>(&self, img: ImageView<P>) -> PyResult<Image<P>>
+    where
+        P: Clone + Default,
+        FloatPixelFormat<P>: PixelFormat<InputPixel = P, OutputPixel = P>,
+    {
+        image_ops::scale::scale_rect(img, self.rect, self.size, self.filter_x, self.filter_y)
+            .map_err(|_| new_alloc_error(self.size))
+    }
+    fn scale_premultiplied_alpha(&self, img: ImageView<Vec4>) -> PyResult<Image<Vec4>> {
+        image_ops::scale::scale_rect_premultiplied_alpha(
+            img,
+            self.rect,
+            self.size,
+            self.filter_x,
+            self.filter_y,
+        )
+        .map_err(|_| new_alloc_error(self.size))
+    }
+    fn scale_ndim(&self, img: NDimView) -> PyResult<NDimImage> {
+        image_ops::scale::scale_ndim_rect(img, self.rect, self.size, self.filter_x, self.filter_y)
+            .map_err(|_| new_alloc_error(self.size))
+    }
+}
+
 #[pyfunction]
 pub fn resize<'py>(
     py: Python<'py>,
-    img: PyImage<'py>,
+    img: &'py PyAny,
     new_size: (u32, u32),
-    filter: ResizeFilter,
-    mut gamma_correction: bool,
-) -> PyResult<&'py PyArray3<f32>> {
-    let new_size: Size = new_size.into();
-    let filter: Filter = filter.into();
+    filter: ResizeFilterArg,
+    gamma_correction: GammaCorrectionArg,
+    premultiply_alpha: Option<bool>,
+    src_rect: Option<(f64, f64, f64, f64)>,
+) -> PyResult<PyObject> {
+    let (filter_x, filter_y) = match filter {
+        ResizeFilterArg::Single(f) => (f.into(), f.into()),
+        ResizeFilterArg::PerAxis(x, y) => (x.into(), y.into()),
+    };
+    let scaling = |img_size: Size| -> PyResult<Scaling> {
+        let rect = match src_rect {
+            Some((left, top, right, bottom)) => SourceRect::new(left, top, right, bottom),
+            None => SourceRect::from_size(img_size),
+        };
+        if !rect.is_valid() {
+            return Err(PyValueError::new_err(format!(
+                "Argument '{}' must be a non-empty rectangle with finite coordinates.",
+                stringify!(src_rect)
+            )));
+        }
 
-    if filter == Filter::Nearest {
-        // no point in paying for gamma correction if we're not interpolating
-        gamma_correction = false;
+        Ok(Scaling {
+            rect,
+            size: new_size.into(),
+            filter_x,
+            filter_y,
+        })
+    };
+
+    match SampleType::of(img) {
+        SampleType::F32 => {
+            let img: PyImage = img.extract()?;
+            let scaling = scaling(img.size())?;
+            let result = resize_float(py, img, scaling, gamma_correction, premultiply_alpha)?;
+            Ok(result.into_py(py))
+        }
+        SampleType::F16 => {
+            let img: PyImage<f16> = img.extract()?;
+            resize_nearest(py, scaling(img.size())?, img)
+        }
+        SampleType::U8 => {
+            let img: PyImage<u8> = img.extract()?;
+            resize_nearest(py, scaling(img.size())?, img)
+        }
+        SampleType::U16 => {
+            let img: PyImage<u16> = img.extract()?;
+            resize_nearest(py, scaling(img.size())?, img)
+        }
     }
+}
+
+/// Resizes images with samples other than `f32`, which is only supported for
+/// nearest neighbor.
+fn resize_nearest<T: PySample>(
+    py: Python,
+    scaling: Scaling,
+    img: PyImage<T>,
+) -> PyResult<PyObject> {
+    if scaling.interpolates() {
+        return Err(PyValueError::new_err(format!(
+            "Argument '{}' must be nearest neighbor for images of type {}. Only float32 images support other filters.",
+            stringify!(filter),
+            T::DTYPE
+        )));
+    }
+
+    let img: NDimCow<T> = img.load_image()?;
+    let result: PyResult<_> = py.allow_threads(|| {
+        if scaling.rect == SourceRect::from_size(img.size()) {
+            return Ok(image_ops::scale::scale_ndim_nearest(
+                img.view(),
+                scaling.size,
+            ));
+        }
+
+        // nearest neighbor only copies pixels, so converting is lossless
+        let img: NDimImage = img.view().convert();
+        Ok(scaling.scale_ndim(img.view())?.convert())
+    });
+
+    Ok(ndim_into_numpy(result?).into_pyarray(py).into_py(py))
+}
+
+fn resize_float<'py>(
+    py: Python<'py>,
+    img: PyImage<'py>,
+    scaling: Scaling,
+    gamma_correction: GammaCorrectionArg,
+    premultiply_alpha: Option<bool>,
+) -> PyResult<&'py PyArray3<f32>> {
+    let new_size = scaling.size;
+    let mut gamma_correction: GammaCorrection = gamma_correction.into();
 
     let c = img.channels();
 
-    let new_error = || {
-        PyValueError::new_err(format!(
-            "Argument '{}' does not have the right shape. Expected 1, 2, 3, or 4 channels but found {}.",
-            stringify!(img),
-            c
-        ))
-    };
+    let mut premultiply_alpha = premultiply_alpha.unwrap_or(false);
+    if c != 4 {
+        // only RGBA images have an alpha channel
+        premultiply_alpha = false;
+    }
+
+    if !scaling.interpolates() {
+        // no point in paying for gamma correction or premultiplied alpha if
+        // we're not interpolating
+        gamma_correction = GammaCorrection::Off;
+        premultiply_alpha = false;
+    }
 
-    if gamma_correction {
+    if gamma_correction != GammaCorrection::Off {
         let mut img: NDimImage = img.load_image()?;
         let result: PyResult<_> = py.allow_threads(|| {
             // convert to linear
-            image_ops::gamma::gamma_ndim(&mut img, 2.2);
+            gamma_correction.linearize(&mut img);
 
             // the actual resizing
             let mut result = match c {
-                1 => with_pixel_format::<f32>(img, new_size, filter)?,
-                2 => with_pixel_format::<Vec2>(img, new_size, filter)?,
-                3 => with_pixel_format::<Vec3A>(img, new_size, filter)?,
-                4 => with_pixel_format::<Vec4>(img, new_size, filter)?,
-                _ => return Err(new_error()),
+                1 => with_pixel_format::<f32>(img, scaling)?,
+                2 => with_pixel_format::<Vec2>(img, scaling)?,
+                3 => with_pixel_format::<Vec3A>(img, scaling)?,
+                4 if premultiply_alpha => with_premultiplied_alpha(img, scaling)?,
+                4 => with_pixel_format::<Vec4>(img, scaling)?,
+                _ => scaling.scale_ndim(img.view())?,
             };
 
             // fix up overshooting
-            if filter != Filter::Nearest && filter != Filter::Linear {
+            if scaling.may_overshoot() {
                 // the filters may overshoot, so we have to clip the result
                 result
                     .data_mut()
@@ -96,76 +282,83 @@ pub fn resize<'py>(
             }
 
             // convert back to sRGB
-            image_ops::gamma::gamma_ndim(&mut result, 1.0 / 2.2);
+            gamma_correction.delinearize(&mut result);
 
             return Ok(result.into_numpy());
 
-            fn with_pixel_format<P>(
-                img: NDimImage,
-                new_size: Size,
-                filter: Filter,
-            ) -> PyResult<NDimImage>
+            fn with_pixel_format<P>(img: NDimImage, scaling: Scaling) -> PyResult<NDimImage>
             where
                 P: Flatten + FromFlat + Default + Clone + 'static,
                 FloatPixelFormat<P>: PixelFormat<InputPixel = P, OutputPixel = P>,
             {
                 let img: Image<P> = img.into_pixels().expect("");
-                let r = image_ops::scale::scale(img.view(), new_size, filter);
+                let r = scaling.scale(img.view());
 
                 // drop image now to free up memory asap
                 std::mem::drop(img);
 
-                match r {
-                    Ok(r) => Ok(r.into()),
-                    Err(_) => Err(PyValueError::new_err(format!(
-                        "Not enough memory to allocate a {}x{} image.",
-                        new_size.width, new_size.height,
-                    ))),
-                }
+                r.map(|r| r.into())
+            }
+
+            fn with_premultiplied_alpha(img: NDimImage, scaling: Scaling) -> PyResult<NDimImage> {
+                let img: Image<Vec4> = img.into_pixels().expect("");
+                let r = scaling.scale_premultiplied_alpha(img.view());
+
+                // drop image now to free up memory asap
+                std::mem::drop(img);
+
+                r.map(|r| r.into())
             }
         });
 
         return Ok(result?.into_pyarray(py));
     }
 
+    if premultiply_alpha {
+        let img: Image<Vec4> = img.load_image()?;
+        let result: PyResult<_> = py.allow_threads(|| {
+            let r = scaling.scale_premultiplied_alpha(img.view());
+            std::mem::drop(img);
+
+            let mut r = r?;
+            if scaling.may_overshoot() {
+                // the filters may overshoot, so we have to clip the result
+                r.data_mut().iter_mut().for_each(|x| *x = x.clip(0.0, 1.0));
+            }
+            Ok(r.into_numpy())
+        });
+
+        return Ok(result?.into_pyarray(py));
+    }
+
     {
         // read the image directly if we can to avoid copying
 
-        if let Some(view) = img.view_image() {
-            return with_pixel_format::<f32>(py, view, new_size, filter);
+        if let Some(view) = img.view_image()? {
+            return with_pixel_format::<f32>(py, view, scaling);
         }
-        if let Some(view) = img.view_image() {
-            return with_pixel_format::<[f32; 3]>(py, view, new_size, filter);
+        if let Some(view) = img.view_image()? {
+            return with_pixel_format::<[f32; 3]>(py, view, scaling);
         }
-        if let Some(view) = img.view_image() {
-            return with_pixel_format::<[f32; 4]>(py, view, new_size, filter);
+        if let Some(view) = img.view_image()? {
+            return with_pixel_format::<[f32; 4]>(py, view, scaling);
         }
 
         fn with_pixel_format<'py, P>(
             py: Python<'py>,
             img: ImageView<'_, P>,
-            new_size: Size,
-            filter: Filter,
+            scaling: Scaling,
         ) -> PyResult<&'py PyArray3<f32>>
         where
             P: Flatten + ClipFloat + Default + Copy + Sync + Send + 'static,
             FloatPixelFormat<P>: PixelFormat<InputPixel = P, OutputPixel = P>,
         {
-            let r = image_ops::scale::scale(img, new_size, filter);
-            match r {
-                Ok(mut r) => {
-                    if filter != Filter::Nearest && filter != Filter::Linear {
-                        // the filters may overshoot, so we have to clip the result
-                        r.data_mut().iter_mut().for_each(|x| *x = x.clip(0.0, 1.0));
-                    }
-                    Ok(r.into_numpy().into_pyarray(py))
-                }
-
-                Err(_) => Err(PyValueError::new_err(format!(
-                    "Not enough memory to allocate a {}x{} image.",
-                    new_size.width, new_size.height,
-                ))),
+            let mut r = scaling.scale(img)?;
+            if scaling.may_overshoot() {
+                // the filters may overshoot, so we have to clip the result
+                r.data_mut().iter_mut().for_each(|x| *x = x.clip(0.0, 1.0));
             }
+            Ok(r.into_numpy().into_pyarray(py))
         }
     }
 
@@ -189,7 +382,7 @@ pub fn resize<'py>(
         // memory usage.
         vec_worth = false;
     }
-    if filter == Filter::Nearest {
+    if !scaling.interpolates() {
         // NN doesn't interpolate pixels
         vec_worth = false;
     }
@@ -197,57 +390,68 @@ pub fn resize<'py>(
     return match c {
         1 => {
             let img: Image<f32> = img.load_image()?;
-            with_pixel_format(py, img, new_size, filter)
+            with_pixel_format(py, img, scaling)
         }
         2 => {
             let img: Image<[f32; 2]> = img.load_image()?;
-            with_pixel_format(py, img, new_size, filter)
+            with_pixel_format(py, img, scaling)
         }
         3 => {
             let img: Image<[f32; 3]> = img.load_image()?;
-            with_pixel_format(py, img, new_size, filter)
+            with_pixel_format(py, img, scaling)
         }
         4 => {
             if vec_worth {
                 let img: Image<Vec4> = img.load_image()?;
-                with_pixel_format(py, img, new_size, filter)
+                with_pixel_format(py, img, scaling)
             } else {
                 let img: Image<[f32; 4]> = img.load_image()?;
-                with_pixel_format(py, img, new_size, filter)
+                with_pixel_format(py, img, scaling)
             }
         }
-        _ => Err(new_error()),
+        _ => {
+            let img: NDimCow = img.load_image()?;
+            let result: PyResult<_> = py.allow_threads(|| {
+                let mut r = scaling.scale_ndim(img.view())?;
+                if scaling.may_overshoot() {
+                    // the filters may overshoot, so we have to clip the result
+                    r.data_mut().iter_mut().for_each(|x| *x = x.clip(0.0, 1.0));
+                }
+                Ok(r.into_numpy())
+            });
+
+            Ok(result?.into_pyarray(py))
+        }
     };
 
     fn with_pixel_format<P>(
-        py: Python,
+        py: Python<'_>,
         img: Image<P>,
-        new_size: Size,
-        filter: Filter,
+        scaling: Scaling,
     ) -> PyResult<&PyArray3<f32>>
     where
         P: Flatten + ClipFloat + Default + Copy + Send + 'static,
         FloatPixelFormat<P>: PixelFormat<InputPixel = P, OutputPixel = P>,
     {
-        let result = py.allow_threads(|| {
-            let r = image_ops::scale::scale(img.view(), new_size, filter);
+        let result: PyResult<_> = py.allow_threads(|| {
+            let r = scaling.scale(img.view());
             std::mem::drop(img);
-            match r {
-                Ok(mut r) => {
-                    if filter != Filter::Nearest && filter != Filter::Linear {
-                        // the filters may overshoot, so we have to clip the result
-                        r.data_mut().iter_mut().for_each(|x| *x = x.clip(0.0, 1.0));
-                    }
-                    Ok(r.into_numpy())
-                }
 
-                Err(_) => Err(PyValueError::new_err(format!(
-                    "Not enough memory to allocate a {}x{} image.",
-                    new_size.width, new_size.height,
-                ))),
+            let mut r = r?;
+            if scaling.may_overshoot() {
+                // the filters may overshoot, so we have to clip the result
+                r.data_mut().iter_mut().for_each(|x| *x = x.clip(0.0, 1.0));
             }
-        })?;
+            Ok(r.into_numpy())
+        });
 
-        Ok(result.into_pyarray(py))
+        Ok(result?.into_pyarray(py))
     }
 }
+
+fn new_alloc_error(new_size: Size) -> PyErr {
+    PyValueError::new_err(format!(
+        "Not enough memory to allocate a {}x{} image.",
+        new_size.width, new_size.height,
+    ))
+}
@@ -0,0 +1,73 @@
+use image_core::{ClipFloat, NDimCow};
+use numpy::{IntoPyArray, PyArray3};
+use pyo3::{exceptions::PyValueError, prelude::*};
+
+use crate::convert::{IntoNumpy, LoadImage, PyImage};
+
+#[pyclass]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SharpenMode {
+    Luminance = 0,
+    PerChannel = 1,
+}
+
+impl From<SharpenMode> for image_ops::sharpen::SharpenMode {
+    fn from(m: SharpenMode) -> Self {
+        match m {
+            SharpenMode::Luminance => image_ops::sharpen::SharpenMode::Luminance,
+            SharpenMode::PerChannel => image_ops::sharpen::SharpenMode::PerChannel,
+        }
+    }
+}
+
+fn check_radius(radius: f32) -> PyResult<()> {
+    if radius.is_nan() || radius < 0.0 || radius.is_infinite() {
+        return Err(PyValueError::new_err(format!(
+            "Argument '{}' must be finite and non-negative.",
+            stringify!(radius)
+        )));
+    }
+    Ok(())
+}
+
+/// Sharpens the given image with an unsharp mask.
+#[pyfunction]
+pub fn unsharp_mask<'py>(
+    py: Python<'py>,
+    img: PyImage,
+    radius: f32,
+    amount: f32,
+    threshold: f32,
+    mode: SharpenMode,
+) -> PyResult<&'py PyArray3<f32>> {
+    check_radius(radius)?;
+
+    let img: NDimCow = img.load_image()?;
+    let result = py.allow_threads(|| {
+        let mut r =
+            image_ops::sharpen::unsharp_mask(img.view(), radius, amount, threshold, mode.into());
+        // sharpening overshoots, so we have to clip the result
+        r.data_mut().iter_mut().for_each(|x| *x = x.clip(0.0, 1.0));
+        r.into_numpy()
+    });
+    Ok(result.into_pyarray(py))
+}
+
+/// Applies a high-pass filter to the given image.
+#[pyfunction]
+pub fn high_pass<'py>(
+    py: Python<'py>,
+    img: PyImage,
+    radius: f32,
+    mode: SharpenMode,
+) -> PyResult<&'py PyArray3<f32>> {
+    check_radius(radius)?;
+
+    let img: NDimCow = img.load_image()?;
+    let result = py.allow_threads(|| {
+        let mut r = image_ops::sharpen::high_pass(img.view(), radius, mode.into());
+        r.data_mut().iter_mut().for_each(|x| *x = x.clip(0.0, 1.0));
+        r.into_numpy()
+    });
+    Ok(result.into_pyarray(py))
+}
@@ -0,0 +1,92 @@
+use image_core::NDimCow;
+use image_ops::smooth::GuideError;
+use numpy::{IntoPyArray, PyArray3};
+use pyo3::{exceptions::PyValueError, prelude::*};
+
+use crate::{
+    convert::{IntoNumpy, LoadImage, PyImage},
+    warp::BorderMode,
+};
+
+#[pyfunction]
+pub fn median_filter<'py>(
+    py: Python<'py>,
+    img: PyImage,
+    radius: u32,
+    border: BorderMode,
+) -> PyResult<&'py PyArray3<f32>> {
+    let img: NDimCow = img.load_image()?;
+    let result = py.allow_threads(|| {
+        image_ops::smooth::median_filter(img.view(), radius as usize, border.into()).into_numpy()
+    });
+    Ok(result.into_pyarray(py))
+}
+
+#[pyfunction]
+pub fn bilateral_filter<'py>(
+    py: Python<'py>,
+    img: PyImage,
+    sigma_spatial: f32,
+    sigma_range: f32,
+    border: BorderMode,
+) -> PyResult<&'py PyArray3<f32>> {
+    if !(sigma_spatial >= 0.0 && sigma_spatial.is_finite()) {
+        return Err(PyValueError::new_err(format!(
+            "Argument '{}' must be finite and non-negative.",
+            stringify!(sigma_spatial)
+        )));
+    }
+    if sigma_range.is_nan() || sigma_range < 0.0 {
+        return Err(PyValueError::new_err(format!(
+            "Argument '{}' must be non-negative.",
+            stringify!(sigma_range)
+        )));
+    }
+
+    let img: NDimCow = img.load_image()?;
+    let result = py.allow_threads(|| {
+        image_ops::smooth::bilateral_filter(img.view(), sigma_spatial, sigma_range, border.into())
+            .into_numpy()
+    });
+    Ok(result.into_pyarray(py))
+}
+
+#[pyfunction]
+#[pyo3(signature = (img, guide, radius, eps, border))]
+pub fn guided_filter<'py>(
+    py: Python<'py>,
+    img: PyImage,
+    guide: Option<PyImage>,
+    radius: u32,
+    eps: f32,
+    border: BorderMode,
+) -> PyResult<&'py PyArray3<f32>> {
+    let img: NDimCow = img.load_image()?;
+    let guide: Option<NDimCow> = guide.as_ref().map(|g| g.load_image()).transpose()?;
+
+    let result = py.allow_threads(|| {
+        image_ops::smooth::guided_filter(
+            img.view(),
+            guide.as_ref().map(|g| g.view()),
+            radius as usize,
+            eps,
+            border.into(),
+        )
+        .map(|r| r.into_numpy())
+    });
+
+    match result {
+        Ok(r) => Ok(r.into_pyarray(py)),
+        Err(GuideError::SizeMismatch { expected, actual }) => Err(PyValueError::new_err(format!(
+            "Expected a guide of size {}x{}, but found {}x{}.",
+            expected.width, expected.height, actual.width, actual.height
+        ))),
+        Err(GuideError::ChannelMismatch { expected, actual }) => Err(PyValueError::new_err(
+            format!("Expected a guide with 1 or {expected} channels, but found {actual} channels."),
+        )),
+        Err(GuideError::InvalidEps) => Err(PyValueError::new_err(format!(
+            "Argument '{}' must be finite and positive.",
+            stringify!(eps)
+        ))),
+    }
+}
@@ -0,0 +1,122 @@
+use image_core::{NDimCow, NDimView};
+use image_ops::tile::{TileError, TilePadding};
+use numpy::{IntoPyArray, PyArray3};
+use pyo3::{exceptions::PyValueError, prelude::*};
+
+use crate::{
+    convert::{IntoNumpy, LoadImage, PyImage},
+    warp::BorderMode,
+};
+
+/// Splits images into overlapping tiles and merges processed tiles back.
+#[pyclass(frozen)]
+#[derive(Clone)]
+pub struct TileGrid {
+    inner: image_ops::tile::TileGrid,
+}
+
+#[pymethods]
+impl TileGrid {
+    /// If no padding is given, edge tiles are shifted into the image instead.
+    #[new]
+    pub fn new(
+        size: (u32, u32),
+        tile_size: (u32, u32),
+        overlap: u32,
+        padding: Option<BorderMode>,
+    ) -> PyResult<Self> {
+        let padding = match padding {
+            Some(border) => TilePadding::Pad(border.into()),
+            None => TilePadding::Shift,
+        };
+
+        let inner = image_ops::tile::TileGrid::new(
+            size.into(),
+            tile_size.into(),
+            overlap as usize,
+            padding,
+        )
+        .map_err(to_py_error)?;
+        Ok(Self { inner })
+    }
+
+    #[getter]
+    pub fn size(&self) -> (usize, usize) {
+        let size = self.inner.size();
+        (size.width, size.height)
+    }
+
+    #[getter]
+    pub fn tile_count(&self) -> usize {
+        self.inner.tile_count()
+    }
+
+    /// Returns the `(x, y, width, height)` of every tile.
+    pub fn tiles(&self) -> Vec<(usize, usize, usize, usize)> {
+        self.inner
+            .tiles()
+            .map(|t| (t.x, t.y, t.size.width, t.size.height))
+            .collect()
+    }
+
+    pub fn split<'py>(&self, py: Python<'py>, img: PyImage) -> PyResult<Vec<&'py PyArray3<f32>>> {
+        let img: NDimCow = img.load_image()?;
+        let tiles = py
+            .allow_threads(|| self.inner.split(img.view()))
+            .map_err(to_py_error)?;
+
+        Ok(tiles
+            .into_iter()
+            .map(|t| t.into_numpy().into_pyarray(py))
+            .collect())
+    }
+
+    pub fn merge<'py>(
+        &self,
+        py: Python<'py>,
+        tiles: Vec<PyImage>,
+        scale: Option<u32>,
+    ) -> PyResult<&'py PyArray3<f32>> {
+        let tiles: Vec<NDimCow> = tiles
+            .iter()
+            .map(|t| t.load_image())
+            .collect::<PyResult<_>>()?;
+        let scale = scale.unwrap_or(1) as usize;
+
+        let result = py.allow_threads(|| {
+            let views: Vec<NDimView> = tiles.iter().map(|t| t.view()).collect();
+            self.inner.merge(&views, scale).map(|r| r.into_numpy())
+        });
+        Ok(result.map_err(to_py_error)?.into_pyarray(py))
+    }
+}
+
+fn to_py_error(e: TileError) -> PyErr {
+    PyValueError::new_err(match e {
+        TileError::EmptySize => "Image, tile size, and scale must not be empty.".to_string(),
+        TileError::OverlapTooLarge { overlap, tile_size } => format!(
+            "An overlap of {overlap} is too large for tiles of size {}x{}.",
+            tile_size.width, tile_size.height
+        ),
+        TileError::ImageSizeMismatch { expected, actual } => format!(
+            "Expected an image of size {}x{}, but found {}x{}.",
+            expected.width, expected.height, actual.width, actual.height
+        ),
+        TileError::TileCountMismatch { expected, actual } => {
+            format!("Expected {expected} tiles, but found {actual} tiles.")
+        }
+        TileError::TileSizeMismatch {
+            index,
+            expected,
+            actual,
+        } => format!(
+            "Expected tile {index} to have size {}x{}, but found {}x{}.",
+            expected.width, expected.height, actual.width, actual.height
+        ),
+        TileError::ChannelMismatch {
+            index,
+            expected,
+            actual,
+        } => format!("Expected tile {index} to have {expected} channels, but found {actual}."),
+    })
+}
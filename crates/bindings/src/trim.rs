@@ -0,0 +1,66 @@
+use image_core::NDimCow;
+use image_ops::trim::{Background, TrimError};
+use numpy::{IntoPyArray, PyArray3};
+use pyo3::{exceptions::PyValueError, prelude::*};
+
+use crate::convert::{IntoNumpy, LoadImage, PyImage};
+
+/// `(x, y, width, height)`
+type PyRect = (usize, usize, usize, usize);
+
+#[pyclass]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TrimMode {
+    /// Pixels with an alpha below the threshold are trimmed.
+    Alpha = 0,
+    /// Pixels whose channels all differ by at most the threshold from the
+    /// background color are trimmed.
+    Color = 1,
+}
+
+/// Crops away the background around the content of the image.
+///
+/// In color mode, the background color defaults to the top-left pixel of the
+/// image.
+///
+/// Returns the `(x, y, width, height)` of the crop and the cropped image.
+#[pyfunction]
+pub fn trim<'py>(
+    py: Python<'py>,
+    img: PyImage,
+    mode: TrimMode,
+    threshold: f32,
+    padding: Option<u32>,
+    color: Option<Vec<f32>>,
+) -> PyResult<(PyRect, &'py PyArray3<f32>)> {
+    let img: NDimCow = img.load_image()?;
+    let color = color.unwrap_or_else(|| match img.data().get(..img.channels()) {
+        Some(top_left) => top_left.to_vec(),
+        None => vec![0.0; img.channels()],
+    });
+    let background = match mode {
+        TrimMode::Alpha => Background::Alpha { threshold },
+        TrimMode::Color => Background::Color {
+            color: &color,
+            tolerance: threshold,
+        },
+    };
+
+    let result = py.allow_threads(|| {
+        image_ops::trim::trim(img.view(), background, padding.unwrap_or(0) as usize)
+            .map(|(rect, cropped)| (rect, cropped.into_numpy()))
+    });
+
+    match result {
+        Ok((rect, cropped)) => Ok((
+            (rect.x, rect.y, rect.width, rect.height),
+            cropped.into_pyarray(py),
+        )),
+        Err(TrimError::NoAlpha { channels }) => Err(PyValueError::new_err(format!(
+            "Expected an image with 2 or 4 channels, but found {channels} channels."
+        ))),
+        Err(TrimError::ChannelMismatch { expected, actual }) => Err(PyValueError::new_err(
+            format!("Expected a color with {expected} channels, but found {actual} channels."),
+        )),
+    }
+}
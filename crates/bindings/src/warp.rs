@@ -0,0 +1,185 @@
+use glam::{Affine2, Mat3, Vec2, Vec3A, Vec4};
+use image_core::{ClipFloat, Flatten, FromFlat, Image};
+use image_ops::{
+    scale::{Filter, FloatPixelFormat, PixelFormat},
+    warp::{RotationSize, WarpError},
+};
+use numpy::{IntoPyArray, PyArray3};
+use pyo3::{exceptions::PyValueError, prelude::*};
+
+use crate::{
+    convert::{IntoNumpy, LoadImage, PyImage},
+    resize::ResizeFilter,
+};
+
+#[pyclass]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum BorderMode {
+    Transparent = 0,
+    Clamp = 1,
+    Reflect = 2,
+    Wrap = 3,
+}
+
+impl From<BorderMode> for image_ops::border::BorderMode {
+    fn from(b: BorderMode) -> Self {
+        match b {
+            BorderMode::Transparent => image_ops::border::BorderMode::Transparent,
+            BorderMode::Clamp => image_ops::border::BorderMode::Clamp,
+            BorderMode::Reflect => image_ops::border::BorderMode::Reflect,
+            BorderMode::Wrap => image_ops::border::BorderMode::Wrap,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Transform {
+    Rotate { angle: f32, size: RotationSize },
+    Perspective { matrix: Mat3, size: (u32, u32) },
+}
+
+/// All parameters that describe how the image is warped.
+#[derive(Debug, Clone, Copy)]
+struct Warp {
+    transform: Transform,
+    filter: Filter,
+    border: image_ops::border::BorderMode,
+}
+
+impl Warp {
+    fn apply<P>(&self, img: Image<P>) -> Result<Image<P>, WarpError>
+    where
+        P: ClipFloat + Copy + Default + Send + Sync,
+        FloatPixelFormat<P>: PixelFormat<InputPixel = P, OutputPixel = P>,
+    {
+        let mut r = match self.transform {
+            Transform::Rotate { angle, size } => Ok(image_ops::warp::rotate(
+                img.view(),
+                angle,
+                size,
+                self.filter,
+                self.border,
+            )),
+            Transform::Perspective { matrix, size } => image_ops::warp::warp_perspective(
+                img.view(),
+                matrix,
+                size.into(),
+                self.filter,
+                self.border,
+            ),
+        }?;
+
+        // drop image now to free up memory asap
+        std::mem::drop(img);
+
+        if self.filter != Filter::Nearest && self.filter != Filter::Linear {
+            // the filters may overshoot, so we have to clip the result
+            r.data_mut().iter_mut().for_each(|x| *x = x.clip(0.0, 1.0));
+        }
+        Ok(r)
+    }
+
+    fn run<'py>(&self, py: Python<'py>, img: PyImage) -> PyResult<&'py PyArray3<f32>> {
+        return match img.channels() {
+            1 => with_pixel_format::<f32>(py, img, *self),
+            2 => with_pixel_format::<Vec2>(py, img, *self),
+            3 => with_pixel_format::<Vec3A>(py, img, *self),
+            4 => with_pixel_format::<Vec4>(py, img, *self),
+            c => Err(PyValueError::new_err(format!(
+                "Expected 1, 2, 3, or 4 channels, but found {c} channels."
+            ))),
+        };
+
+        fn with_pixel_format<'py, P>(
+            py: Python<'py>,
+            img: PyImage,
+            warp: Warp,
+        ) -> PyResult<&'py PyArray3<f32>>
+        where
+            P: Flatten + FromFlat + ClipFloat + Copy + Default + Send + Sync + 'static,
+            FloatPixelFormat<P>: PixelFormat<InputPixel = P, OutputPixel = P>,
+        {
+            let img: Image<P> = img.load_image()?;
+            let result = py.allow_threads(|| warp.apply(img).map(|r| r.into_numpy()));
+            match result {
+                Ok(r) => Ok(r.into_pyarray(py)),
+                Err(WarpError::NotInvertible) => Err(PyValueError::new_err(format!(
+                    "Argument '{}' must be an invertible matrix.",
+                    stringify!(matrix)
+                ))),
+            }
+        }
+    }
+}
+
+/// Rotates the image counter-clockwise by the given angle in degrees.
+#[pyfunction]
+pub fn rotate<'py>(
+    py: Python<'py>,
+    img: PyImage,
+    angle: f32,
+    expand: bool,
+    filter: ResizeFilter,
+    border: BorderMode,
+) -> PyResult<&'py PyArray3<f32>> {
+    let size = if expand {
+        RotationSize::Expand
+    } else {
+        RotationSize::Crop
+    };
+
+    Warp {
+        transform: Transform::Rotate {
+            angle: angle.to_radians(),
+            size,
+        },
+        filter: filter.into(),
+        border: border.into(),
+    }
+    .run(py, img)
+}
+
+/// Applies the given 2x3 affine transformation matrix (row-major).
+#[pyfunction]
+pub fn warp_affine<'py>(
+    py: Python<'py>,
+    img: PyImage,
+    matrix: [[f32; 3]; 2],
+    new_size: (u32, u32),
+    filter: ResizeFilter,
+    border: BorderMode,
+) -> PyResult<&'py PyArray3<f32>> {
+    let [[a, b, c], [d, e, f]] = matrix;
+    let matrix = Affine2::from_cols(Vec2::new(a, d), Vec2::new(b, e), Vec2::new(c, f));
+
+    Warp {
+        transform: Transform::Perspective {
+            matrix: Mat3::from(matrix),
+            size: new_size,
+        },
+        filter: filter.into(),
+        border: border.into(),
+    }
+    .run(py, img)
+}
+
+/// Applies the given 3x3 perspective transformation matrix (row-major).
+#[pyfunction]
+pub fn warp_perspective<'py>(
+    py: Python<'py>,
+    img: PyImage,
+    matrix: [[f32; 3]; 3],
+    new_size: (u32, u32),
+    filter: ResizeFilter,
+    border: BorderMode,
+) -> PyResult<&'py PyArray3<f32>> {
+    Warp {
+        transform: Transform::Perspective {
+            matrix: Mat3::from_cols_array_2d(&matrix).transpose(),
+            size: new_size,
+        },
+        filter: filter.into(),
+        border: border.into(),
+    }
+    .run(py, img)
+}
@@ -0,0 +1,42 @@
+use crate::{image::strided_len, Size};
+
+/// An error of a fallible image constructor.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ImageError {
+    /// The data doesn't have the number of elements the image requires.
+    ///
+    /// For views with a stride, the data must have at least `expected`
+    /// elements. Otherwise, it must have exactly `expected` elements.
+    DataLength { expected: usize, actual: usize },
+    /// The stride of a view is smaller than its width.
+    StrideTooSmall { width: usize, stride: usize },
+}
+
+/// Checks that the data of a contiguous image has exactly `expected` elements.
+pub(crate) fn check_len(expected: usize, actual: usize) -> Result<(), ImageError> {
+    if expected != actual {
+        return Err(ImageError::DataLength { expected, actual });
+    }
+    Ok(())
+}
+
+/// Checks the stride and data of a view with `channels` elements per pixel
+/// and returns the number of elements the view uses.
+pub(crate) fn check_strided(
+    size: Size,
+    stride: usize,
+    channels: usize,
+    actual: usize,
+) -> Result<usize, ImageError> {
+    if stride < size.width {
+        return Err(ImageError::StrideTooSmall {
+            width: size.width,
+            stride,
+        });
+    }
+    let expected = strided_len(size, stride) * channels;
+    if actual < expected {
+        return Err(ImageError::DataLength { expected, actual });
+    }
+    Ok(expected)
+}
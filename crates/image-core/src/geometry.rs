@@ -0,0 +1,664 @@
+use crate::{Image, ImageView, NDimImage, NDimView, Rect, Shape, Size};
+
+/// Determines the pixels added by padding.
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
+pub enum PadMode<C> {
+    /// All new pixels have the given color.
+    Constant(C),
+    /// New pixels repeat the nearest edge pixel.
+    Edge,
+    /// New pixels mirror the image. The edge pixel is repeated, so `abc`
+    /// becomes `cba|abc|cba`.
+    Reflect,
+    /// New pixels tile the image, so `abc` becomes `abc|abc|abc`.
+    Wrap,
+}
+
+/// The number of pixels added on each side by padding.
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug, Default)]
+pub struct Padding {
+    pub left: usize,
+    pub top: usize,
+    pub right: usize,
+    pub bottom: usize,
+}
+
+impl Padding {
+    pub fn new(left: usize, top: usize, right: usize, bottom: usize) -> Self {
+        Self {
+            left,
+            top,
+            right,
+            bottom,
+        }
+    }
+    /// The same padding on all sides.
+    pub fn uniform(amount: usize) -> Self {
+        Self::new(amount, amount, amount, amount)
+    }
+
+    fn apply(&self, size: Size) -> Size {
+        Size::new(
+            self.left + size.width + self.right,
+            self.top + size.height + self.bottom,
+        )
+    }
+}
+
+/// A rotation by a multiple of 90°.
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
+pub enum Rotation {
+    Clockwise90,
+    Rotate180,
+    CounterClockwise90,
+}
+
+impl<C> PadMode<C> {
+    /// Maps the given pixel index into `0..len` according to the padding
+    /// mode. Returns `None` if the pixel has the constant color.
+    ///
+    /// `len` must not be 0 unless the mode is constant.
+    #[inline]
+    pub fn map_index(&self, i: isize, len: usize) -> Option<usize> {
+        let len = len as isize;
+        if (0..len).contains(&i) {
+            return Some(i as usize);
+        }
+        match self {
+            PadMode::Constant(_) => None,
+            PadMode::Edge => Some(i.clamp(0, len - 1) as usize),
+            PadMode::Reflect => {
+                let i = i.rem_euclid(2 * len);
+                Some(if i < len { i } else { 2 * len - 1 - i } as usize)
+            }
+            PadMode::Wrap => Some(i.rem_euclid(len) as usize),
+        }
+    }
+}
+
+/// The rows of an image where each pixel consists of `c` consecutive
+/// elements. `ImageView<P>` uses `c = 1`.
+///
+/// Rows are borrowed individually, so strided views are supported.
+struct Rows<'a, T> {
+    rows: Vec<&'a [T]>,
+    size: Size,
+    c: usize,
+}
+
+impl<'a, T: Clone> Rows<'a, T> {
+    /// The rows of a contiguous image.
+    fn from_data(data: &'a [T], size: Size, c: usize) -> Self {
+        let row_len = size.width * c;
+        let rows = if row_len == 0 {
+            vec![&data[..0]; size.height]
+        } else {
+            data.chunks_exact(row_len).collect()
+        };
+        Self { rows, size, c }
+    }
+
+    fn pad(&self, padding: Padding, mode: PadMode<&[T]>) -> Vec<T> {
+        let Self { size, c, .. } = *self;
+        if let PadMode::Constant(color) = mode {
+            assert_eq!(
+                color.len(),
+                c,
+                "The padding color must have one value per channel."
+            );
+        } else {
+            assert!(
+                !size.is_empty(),
+                "Only constant padding can be applied to an empty image."
+            );
+        }
+
+        let new_size = padding.apply(size);
+        let row_len = new_size.width * c;
+        let mut result = Vec::with_capacity(new_size.len() * c);
+
+        // pads the given source row horizontally
+        let push_row = |result: &mut Vec<T>, row: &[T]| {
+            let push_column = |result: &mut Vec<T>, x: isize| match mode.map_index(x, size.width) {
+                Some(x) => result.extend_from_slice(&row[x * c..(x + 1) * c]),
+                None => result.extend_from_slice(mode.color()),
+            };
+            for x in -(padding.left as isize)..0 {
+                push_column(result, x);
+            }
+            result.extend_from_slice(row);
+            let right = size.width as isize;
+            for x in right..right + padding.right as isize {
+                push_column(result, x);
+            }
+        };
+
+        // each row of the result is either padded once and copied afterwards
+        // or filled with the constant color
+        let mut row_starts: Vec<Option<usize>> = vec![None; size.height];
+        let mut constant_start: Option<usize> = None;
+        for y in 0..new_size.height {
+            let start = result.len();
+            match mode.map_index(y as isize - padding.top as isize, size.height) {
+                Some(sy) => match row_starts[sy] {
+                    Some(s) => result.extend_from_within(s..s + row_len),
+                    None => {
+                        push_row(&mut result, self.rows[sy]);
+                        row_starts[sy] = Some(start);
+                    }
+                },
+                None => match constant_start {
+                    Some(s) => result.extend_from_within(s..s + row_len),
+                    None => {
+                        for _ in 0..new_size.width {
+                            result.extend_from_slice(mode.color());
+                        }
+                        constant_start = Some(start);
+                    }
+                },
+            }
+        }
+        result
+    }
+
+    fn flip_horizontal(&self) -> Vec<T> {
+        let mut result = Vec::with_capacity(self.size.len() * self.c);
+        if self.c == 0 {
+            return result;
+        }
+        for row in &self.rows {
+            let start = result.len();
+            result.extend_from_slice(row);
+            // reversing the row reverses the channels of each pixel too, so
+            // we have to reverse them again
+            let row = &mut result[start..];
+            row.reverse();
+            if self.c > 1 {
+                row.chunks_exact_mut(self.c).for_each(|p| p.reverse());
+            }
+        }
+        result
+    }
+
+    fn flip_vertical(&self) -> Vec<T> {
+        let mut result = Vec::with_capacity(self.size.len() * self.c);
+        for row in self.rows.iter().rev() {
+            result.extend_from_slice(row);
+        }
+        result
+    }
+
+    fn transpose(&self) -> Vec<T> {
+        // the image is transposed in square blocks, so that both reading and
+        // writing stay within a few cache lines
+        const BLOCK: usize = 32;
+
+        let Self { size, c, .. } = *self;
+        let first = match self.rows.first().and_then(|row| row.first()) {
+            Some(first) => first,
+            None => return Vec::new(),
+        };
+
+        let mut result = vec![first.clone(); size.len() * c];
+        for y0 in (0..size.height).step_by(BLOCK) {
+            for x0 in (0..size.width).step_by(BLOCK) {
+                for y in y0..(y0 + BLOCK).min(size.height) {
+                    let row = self.rows[y];
+                    for x in x0..(x0 + BLOCK).min(size.width) {
+                        let dst = (x * size.height + y) * c;
+                        result[dst..dst + c].clone_from_slice(&row[x * c..(x + 1) * c]);
+                    }
+                }
+            }
+        }
+        result
+    }
+
+    fn rotate(&self, rotation: Rotation) -> (Size, Vec<T>) {
+        let transposed = Size::new(self.size.height, self.size.width);
+        let transposed_rows = |data| Rows::from_data(data, transposed, self.c);
+
+        match rotation {
+            Rotation::Clockwise90 => {
+                let data = self.transpose();
+                (transposed, transposed_rows(&data).flip_horizontal())
+            }
+            Rotation::Rotate180 => {
+                let mut data = Vec::with_capacity(self.size.len() * self.c);
+                for row in &self.rows {
+                    data.extend_from_slice(row);
+                }
+                // reversing all data reverses rows and pixels, so we only
+                // have to restore the order of the channels
+                data.reverse();
+                if self.c > 1 {
+                    data.chunks_exact_mut(self.c).for_each(|p| p.reverse());
+                }
+                (self.size, data)
+            }
+            Rotation::CounterClockwise90 => {
+                let data = self.transpose();
+                (transposed, transposed_rows(&data).flip_vertical())
+            }
+        }
+    }
+}
+
+impl<'a, T> PadMode<&'a [T]> {
+    fn color(&self) -> &'a [T] {
+        match self {
+            PadMode::Constant(color) => color,
+            _ => unreachable!("only constant padding maps to no pixel"),
+        }
+    }
+}
+
+impl<'a, P: Clone> ImageView<'a, P> {
+    fn pixel_rows(&self) -> Rows<'a, P> {
+        Rows {
+            rows: self.rows().collect(),
+            size: self.size(),
+            c: 1,
+        }
+    }
+
+    /// Copies the given rectangle out of the image.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the rectangle isn't inside the image.
+    pub fn crop(&self, rect: Rect) -> Image<P> {
+        self.sub_view(rect).into_owned()
+    }
+
+    /// Adds the given number of pixels on each side of the image.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the image is empty and the mode isn't constant.
+    pub fn pad(&self, padding: Padding, mode: PadMode<P>) -> Image<P> {
+        let rows = self.pixel_rows();
+        let data = match mode {
+            PadMode::Constant(color) => {
+                rows.pad(padding, PadMode::Constant(std::slice::from_ref(&color)))
+            }
+            PadMode::Edge => rows.pad(padding, PadMode::Edge),
+            PadMode::Reflect => rows.pad(padding, PadMode::Reflect),
+            PadMode::Wrap => rows.pad(padding, PadMode::Wrap),
+        };
+        Image::new(padding.apply(self.size()), data)
+    }
+
+    /// Mirrors the image along the vertical axis, so left becomes right.
+    pub fn flip_horizontal(&self) -> Image<P> {
+        Image::new(self.size(), self.pixel_rows().flip_horizontal())
+    }
+    /// Mirrors the image along the horizontal axis, so top becomes bottom.
+    pub fn flip_vertical(&self) -> Image<P> {
+        Image::new(self.size(), self.pixel_rows().flip_vertical())
+    }
+
+    /// Swaps rows and columns.
+    pub fn transpose(&self) -> Image<P> {
+        let size = self.size();
+        let data = self.pixel_rows().transpose();
+        Image::new(Size::new(size.height, size.width), data)
+    }
+
+    pub fn rotate(&self, rotation: Rotation) -> Image<P> {
+        let (size, data) = self.pixel_rows().rotate(rotation);
+        Image::new(size, data)
+    }
+}
+
+impl<P: Clone> Image<P> {
+    /// See [`ImageView::crop`].
+    pub fn crop(&self, rect: Rect) -> Image<P> {
+        self.view().crop(rect)
+    }
+    /// See [`ImageView::pad`].
+    pub fn pad(&self, padding: Padding, mode: PadMode<P>) -> Image<P> {
+        self.view().pad(padding, mode)
+    }
+    /// See [`ImageView::flip_horizontal`].
+    pub fn flip_horizontal(&self) -> Image<P> {
+        self.view().flip_horizontal()
+    }
+    /// See [`ImageView::flip_vertical`].
+    pub fn flip_vertical(&self) -> Image<P> {
+        self.view().flip_vertical()
+    }
+    /// See [`ImageView::transpose`].
+    pub fn transpose(&self) -> Image<P> {
+        self.view().transpose()
+    }
+    /// See [`ImageView::rotate`].
+    pub fn rotate(&self, rotation: Rotation) -> Image<P> {
+        self.view().rotate(rotation)
+    }
+}
+
+impl<'a, T: Clone> NDimView<'a, T> {
+    fn pixel_rows(&self) -> Rows<'a, T> {
+        Rows {
+            rows: self.rows().collect(),
+            size: self.size(),
+            c: self.channels(),
+        }
+    }
+
+    /// Copies the given rectangle out of the image.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the rectangle isn't inside the image.
+    pub fn crop(&self, rect: Rect) -> NDimImage<T> {
+        self.sub_view(rect).into_owned()
+    }
+
+    /// Adds the given number of pixels on each side of the image. A constant
+    /// color must have one value per channel.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the image is empty and the mode isn't constant, or if the
+    /// constant color has the wrong number of channels.
+    pub fn pad(&self, padding: Padding, mode: PadMode<&[T]>) -> NDimImage<T> {
+        let c = self.channels();
+        let data = self.pixel_rows().pad(padding, mode);
+        NDimImage::new(Shape::from_size(padding.apply(self.size()), c), data)
+    }
+
+    /// Mirrors the image along the vertical axis, so left becomes right.
+    pub fn flip_horizontal(&self) -> NDimImage<T> {
+        NDimImage::new(self.shape(), self.pixel_rows().flip_horizontal())
+    }
+    /// Mirrors the image along the horizontal axis, so top becomes bottom.
+    pub fn flip_vertical(&self) -> NDimImage<T> {
+        NDimImage::new(self.shape(), self.pixel_rows().flip_vertical())
+    }
+
+    /// Swaps rows and columns.
+    pub fn transpose(&self) -> NDimImage<T> {
+        let Shape {
+            width,
+            height,
+            channels,
+        } = self.shape();
+        let data = self.pixel_rows().transpose();
+        NDimImage::new(Shape::new(height, width, channels), data)
+    }
+
+    pub fn rotate(&self, rotation: Rotation) -> NDimImage<T> {
+        let c = self.channels();
+        let (size, data) = self.pixel_rows().rotate(rotation);
+        NDimImage::new(Shape::from_size(size, c), data)
+    }
+}
+
+impl<T: Clone> NDimImage<T> {
+    /// See [`NDimView::crop`].
+    pub fn crop(&self, rect: Rect) -> NDimImage<T> {
+        self.view().crop(rect)
+    }
+    /// See [`NDimView::pad`].
+    pub fn pad(&self, padding: Padding, mode: PadMode<&[T]>) -> NDimImage<T> {
+        self.view().pad(padding, mode)
+    }
+    /// See [`NDimView::flip_horizontal`].
+    pub fn flip_horizontal(&self) -> NDimImage<T> {
+        self.view().flip_horizontal()
+    }
+    /// See [`NDimView::flip_vertical`].
+    pub fn flip_vertical(&self) -> NDimImage<T> {
+        self.view().flip_vertical()
+    }
+    /// See [`NDimView::transpose`].
+    pub fn transpose(&self) -> NDimImage<T> {
+        self.view().transpose()
+    }
+    /// See [`NDimView::rotate`].
+    pub fn rotate(&self, rotation: Rotation) -> NDimImage<T> {
+        self.view().rotate(rotation)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Image, NDimImage, Rect, Shape, Size};
+
+    use super::{PadMode, Padding, Rotation};
+
+    /// A 3x2 image with the pixels 0 to 5.
+    fn small() -> Image<u8> {
+        Image::new(Size::new(3, 2), vec![0, 1, 2, 3, 4, 5])
+    }
+    /// A 3x2 image with 2 channels, where the pixels are `[i, 10 + i]`.
+    fn small_ndim() -> NDimImage<u8> {
+        let data = (0..6).flat_map(|i| [i, 10 + i]).collect();
+        NDimImage::new(Shape::new(3, 2, 2), data)
+    }
+
+    #[test]
+    fn crop() {
+        let img = small();
+        assert_eq!(img.crop(Rect::new(1, 0, 2, 2)).data(), &[1, 2, 4, 5]);
+        assert_eq!(img.crop(Rect::new(0, 1, 3, 1)).data(), &[3, 4, 5]);
+        assert!(img.crop(Rect::new(2, 2, 0, 0)).size().is_empty());
+
+        let img = small_ndim();
+        let cropped = img.crop(Rect::new(2, 0, 1, 2));
+        assert_eq!(cropped.shape(), Shape::new(1, 2, 2));
+        assert_eq!(cropped.data(), &[2, 12, 5, 15]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn crop_outside() {
+        small().crop(Rect::new(2, 0, 2, 1));
+    }
+
+    #[test]
+    fn pad() {
+        let img = small();
+        let padding = Padding::new(2, 1, 1, 2);
+        let pad = |mode| {
+            let padded = img.pad(padding, mode);
+            assert_eq!(padded.size(), Size::new(6, 5));
+            padded.data().to_vec()
+        };
+
+        #[rustfmt::skip]
+        assert_eq!(pad(PadMode::Constant(9)), [
+            9, 9, 9, 9, 9, 9,
+            9, 9, 0, 1, 2, 9,
+            9, 9, 3, 4, 5, 9,
+            9, 9, 9, 9, 9, 9,
+            9, 9, 9, 9, 9, 9,
+        ]);
+        #[rustfmt::skip]
+        assert_eq!(pad(PadMode::Edge), [
+            0, 0, 0, 1, 2, 2,
+            0, 0, 0, 1, 2, 2,
+            3, 3, 3, 4, 5, 5,
+            3, 3, 3, 4, 5, 5,
+            3, 3, 3, 4, 5, 5,
+        ]);
+        #[rustfmt::skip]
+        assert_eq!(pad(PadMode::Reflect), [
+            1, 0, 0, 1, 2, 2,
+            1, 0, 0, 1, 2, 2,
+            4, 3, 3, 4, 5, 5,
+            4, 3, 3, 4, 5, 5,
+            1, 0, 0, 1, 2, 2,
+        ]);
+        #[rustfmt::skip]
+        assert_eq!(pad(PadMode::Wrap), [
+            4, 5, 3, 4, 5, 3,
+            1, 2, 0, 1, 2, 0,
+            4, 5, 3, 4, 5, 3,
+            1, 2, 0, 1, 2, 0,
+            4, 5, 3, 4, 5, 3,
+        ]);
+    }
+
+    #[test]
+    fn pad_more_than_size() {
+        let img = Image::new(Size::new(2, 1), vec![0, 1]);
+        let padding = Padding::new(5, 0, 3, 0);
+        assert_eq!(
+            img.pad(padding, PadMode::Reflect).data(),
+            &[0, 0, 1, 1, 0, 0, 1, 1, 0, 0]
+        );
+        assert_eq!(
+            img.pad(padding, PadMode::Wrap).data(),
+            &[1, 0, 1, 0, 1, 0, 1, 0, 1, 0]
+        );
+    }
+
+    #[test]
+    fn pad_ndim() {
+        let img = small_ndim();
+        let padding = Padding::new(1, 0, 0, 1);
+
+        let padded = img.pad(padding, PadMode::Constant(&[7, 8]));
+        assert_eq!(padded.shape(), Shape::new(4, 3, 2));
+        #[rustfmt::skip]
+        assert_eq!(padded.data(), &[
+            7, 8, 0, 10, 1, 11, 2, 12,
+            7, 8, 3, 13, 4, 14, 5, 15,
+            7, 8, 7, 8, 7, 8, 7, 8,
+        ]);
+
+        let padded = img.pad(padding, PadMode::Wrap);
+        #[rustfmt::skip]
+        assert_eq!(padded.data(), &[
+            2, 12, 0, 10, 1, 11, 2, 12,
+            5, 15, 3, 13, 4, 14, 5, 15,
+            2, 12, 0, 10, 1, 11, 2, 12,
+        ]);
+    }
+
+    #[test]
+    fn pad_empty() {
+        let img: Image<u8> = Image::new(Size::new(0, 0), vec![]);
+        let padded = img.pad(Padding::uniform(1), PadMode::Constant(3));
+        assert_eq!(padded.size(), Size::new(2, 2));
+        assert_eq!(padded.data(), &[3; 4]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn pad_empty_edge() {
+        let img: Image<u8> = Image::new(Size::new(0, 2), vec![]);
+        img.pad(Padding::uniform(1), PadMode::Edge);
+    }
+
+    #[test]
+    #[should_panic]
+    fn pad_wrong_color() {
+        small_ndim().pad(Padding::uniform(1), PadMode::Constant(&[1]));
+    }
+
+    #[test]
+    fn flip() {
+        let img = small();
+        assert_eq!(img.flip_horizontal().data(), &[2, 1, 0, 5, 4, 3]);
+        assert_eq!(img.flip_vertical().data(), &[3, 4, 5, 0, 1, 2]);
+
+        let img = small_ndim();
+        assert_eq!(
+            img.flip_horizontal().data(),
+            &[2, 12, 1, 11, 0, 10, 5, 15, 4, 14, 3, 13]
+        );
+        assert_eq!(
+            img.flip_vertical().data(),
+            &[3, 13, 4, 14, 5, 15, 0, 10, 1, 11, 2, 12]
+        );
+
+        // strided views
+        let view = img.view();
+        let sub = view.sub_view(Rect::new(1, 0, 2, 2));
+        assert_eq!(sub.flip_horizontal().data(), &[2, 12, 1, 11, 5, 15, 4, 14]);
+        assert_eq!(sub.flip_vertical().data(), &[4, 14, 5, 15, 1, 11, 2, 12]);
+    }
+
+    #[test]
+    fn zero_channels() {
+        let img: NDimImage<u8> = NDimImage::new(Shape::new(3, 2, 0), vec![]);
+        assert_eq!(img.flip_horizontal().shape(), img.shape());
+        assert_eq!(img.flip_vertical().shape(), img.shape());
+        assert_eq!(img.transpose().shape(), Shape::new(2, 3, 0));
+        for rotation in [
+            Rotation::Clockwise90,
+            Rotation::Rotate180,
+            Rotation::CounterClockwise90,
+        ] {
+            assert!(img.rotate(rotation).data().is_empty());
+        }
+        let padded = img.pad(Padding::uniform(1), PadMode::Edge);
+        assert_eq!(padded.shape(), Shape::new(5, 4, 0));
+    }
+
+    #[test]
+    fn transpose() {
+        let img = small();
+        let transposed = img.transpose();
+        assert_eq!(transposed.size(), Size::new(2, 3));
+        assert_eq!(transposed.data(), &[0, 3, 1, 4, 2, 5]);
+
+        let transposed = small_ndim().transpose();
+        assert_eq!(transposed.shape(), Shape::new(2, 3, 2));
+        assert_eq!(
+            transposed.data(),
+            &[0, 10, 3, 13, 1, 11, 4, 14, 2, 12, 5, 15]
+        );
+
+        // larger than one block with odd sizes
+        let size = Size::new(37, 71);
+        let img = Image::from_fn(size, |x, y| (x, y));
+        let transposed = img.transpose();
+        assert_eq!(transposed.size(), Size::new(71, 37));
+        for (i, p) in transposed.data().iter().enumerate() {
+            assert_eq!(*p, (i / 71, i % 71));
+        }
+        assert_eq!(transposed.transpose().data(), img.data());
+    }
+
+    #[test]
+    fn rotate() {
+        let img = small();
+
+        let rotated = img.rotate(Rotation::Clockwise90);
+        assert_eq!(rotated.size(), Size::new(2, 3));
+        assert_eq!(rotated.data(), &[3, 0, 4, 1, 5, 2]);
+
+        let rotated = img.rotate(Rotation::Rotate180);
+        assert_eq!(rotated.size(), Size::new(3, 2));
+        assert_eq!(rotated.data(), &[5, 4, 3, 2, 1, 0]);
+
+        let rotated = img.rotate(Rotation::CounterClockwise90);
+        assert_eq!(rotated.size(), Size::new(2, 3));
+        assert_eq!(rotated.data(), &[2, 5, 1, 4, 0, 3]);
+    }
+
+    #[test]
+    fn rotate_ndim() {
+        let img = small_ndim();
+
+        let rotated = img.rotate(Rotation::Clockwise90);
+        assert_eq!(rotated.shape(), Shape::new(2, 3, 2));
+        assert_eq!(rotated.data(), &[3, 13, 0, 10, 4, 14, 1, 11, 5, 15, 2, 12]);
+
+        let rotated = img.rotate(Rotation::Rotate180);
+        assert_eq!(rotated.data(), &[5, 15, 4, 14, 3, 13, 2, 12, 1, 11, 0, 10]);
+
+        let rotated = img.rotate(Rotation::CounterClockwise90);
+        assert_eq!(rotated.data(), &[2, 12, 5, 15, 1, 11, 4, 14, 0, 10, 3, 13]);
+
+        let round_trip = img
+            .rotate(Rotation::Clockwise90)
+            .rotate(Rotation::CounterClockwise90);
+        assert_eq!(round_trip.data(), img.data());
+    }
+}
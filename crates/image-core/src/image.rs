@@ -1,4 +1,9 @@
-use std::slice::ChunksExact;
+use std::{borrow::Cow, slice::ChunksExact};
+
+use crate::{
+    error::{check_len, check_strided},
+    ImageError, Sample,
+};
 
 /// A non-empty size consisting of width and height in that order.
 #[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
@@ -50,6 +55,45 @@ impl From<(u32, u32)> for Size {
     }
 }
 
+/// An axis-aligned rectangle of pixels.
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
+pub struct Rect {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Rect {
+    pub fn new(x: usize, y: usize, width: usize, height: usize) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+    /// Returns the rectangle at the origin with the given size.
+    pub fn from_size(size: Size) -> Self {
+        Self::new(0, 0, size.width, size.height)
+    }
+
+    pub fn size(&self) -> Size {
+        Size::new(self.width, self.height)
+    }
+    pub fn is_empty(&self) -> bool {
+        self.size().is_empty()
+    }
+    /// The exclusive right edge.
+    pub fn right(&self) -> usize {
+        self.x + self.width
+    }
+    /// The exclusive bottom edge.
+    pub fn bottom(&self) -> usize {
+        self.y + self.height
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct Image<P> {
     data: Vec<P>,
@@ -63,9 +107,18 @@ impl<P> Image<P> {
             size: Size::empty(),
         }
     }
+    /// Creates a new image from its row-major pixel data.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the number of pixels doesn't match the size. See
+    /// [`Self::try_new`].
     pub fn new(size: Size, data: Vec<P>) -> Self {
-        assert_eq!(size.len(), data.len());
-        Self { data, size }
+        Self::try_new(size, data).expect("The data must match the size of the image.")
+    }
+    pub fn try_new(size: Size, data: Vec<P>) -> Result<Self, ImageError> {
+        check_len(size.len(), data.len())?;
+        Ok(Self { data, size })
     }
     pub fn from_fn(size: Size, f: impl Fn(usize, usize) -> P) -> Self {
         let f = &f;
@@ -108,6 +161,17 @@ impl<P> Image<P> {
     pub fn view(&self) -> ImageView<'_, P> {
         ImageView::new(self.size(), &self.data)
     }
+    pub fn view_mut(&mut self) -> ImageViewMut<'_, P> {
+        ImageViewMut::new(self.size, &mut self.data)
+    }
+    /// See [`ImageViewMut::sub_view_mut`].
+    pub fn sub_view_mut(&mut self, rect: Rect) -> ImageViewMut<'_, P> {
+        self.view_mut().into_sub_view(rect)
+    }
+    /// See [`ImageView::sub_view`].
+    pub fn sub_view(&self, rect: Rect) -> ImageView<'_, P> {
+        self.view().sub_view(rect)
+    }
 
     /// The pixel data of the image.
     ///
@@ -165,22 +229,76 @@ impl<P> Image<P> {
     }
 }
 
-#[derive(Clone, Copy, Debug)]
+/// Returns the number of elements a strided buffer must have.
+pub(crate) fn strided_len(size: Size, stride: usize) -> usize {
+    if size.is_empty() {
+        0
+    } else {
+        stride * (size.height - 1) + size.width
+    }
+}
+
+/// A borrowed (part of an) image.
+///
+/// Rows of the view are `stride` pixels apart in the underlying buffer. A
+/// view is contiguous if there are no gaps between its rows.
+#[derive(Debug)]
 pub struct ImageView<'a, P> {
     data: &'a [P],
     size: Size,
+    stride: usize,
+}
+
+// manual impls, because views are copyable regardless of the pixel type
+impl<P> Clone for ImageView<'_, P> {
+    fn clone(&self) -> Self {
+        *self
+    }
 }
+impl<P> Copy for ImageView<'_, P> {}
 
 impl<'a, P> ImageView<'a, P> {
     pub fn empty() -> Self {
         Self {
             data: &[],
             size: Size::empty(),
+            stride: 0,
         }
     }
+    /// # Panics
+    ///
+    /// Panics if the number of pixels doesn't match the size. See
+    /// [`Self::try_new`].
     pub fn new(size: Size, data: &'a [P]) -> Self {
-        assert_eq!(size.len(), data.len());
-        Self { data, size }
+        Self::try_new(size, data).expect("The data must match the size of the image.")
+    }
+    pub fn try_new(size: Size, data: &'a [P]) -> Result<Self, ImageError> {
+        check_len(size.len(), data.len())?;
+        Ok(Self {
+            data,
+            size,
+            stride: size.width,
+        })
+    }
+    /// Creates a view of an image whose rows are `stride` pixels apart.
+    ///
+    /// Excess data after the last row is ignored.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the stride is smaller than the width or if there isn't
+    /// enough data. See [`Self::try_with_stride`].
+    pub fn with_stride(size: Size, stride: usize, data: &'a [P]) -> Self {
+        Self::try_with_stride(size, stride, data)
+            .expect("The data must match the size and stride of the view.")
+    }
+    pub fn try_with_stride(size: Size, stride: usize, data: &'a [P]) -> Result<Self, ImageError> {
+        let len = check_strided(size, stride, 1, data.len())?;
+        Ok(Self {
+            data: &data[..len],
+            size,
+            stride,
+        })
     }
 
     pub fn size(&self) -> Size {
@@ -200,32 +318,103 @@ impl<'a, P> ImageView<'a, P> {
         self.size().is_empty()
     }
 
+    /// The number of pixels between the starts of 2 consecutive rows.
+    pub fn stride(&self) -> usize {
+        self.stride
+    }
+    /// Whether the rows of the view directly follow each other in memory.
+    pub fn is_contiguous(&self) -> bool {
+        self.data.len() == self.len()
+    }
+
     pub fn into_owned(&self) -> Image<P>
     where
         P: Clone,
     {
-        Image::new(self.size(), self.data().to_vec())
+        if self.is_contiguous() {
+            return Image::new(self.size(), self.data.to_vec());
+        }
+
+        let mut data = Vec::with_capacity(self.len());
+        for row in self.rows() {
+            data.extend_from_slice(row);
+        }
+        Image::new(self.size(), data)
     }
 
     /// The pixel data of the image.
     ///
     /// Pixel data is layed out in row-major order.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the view isn't contiguous. Use [`Self::rows`] to access the
+    /// pixels of any view.
     pub fn data(&self) -> &'a [P] {
+        assert!(
+            self.is_contiguous(),
+            "The pixel data of a non-contiguous view can only be accessed by row."
+        );
+        self.data
+    }
+
+    /// The pixel data of the image. The data is only copied if the view isn't
+    /// contiguous.
+    pub fn contiguous_data(&self) -> Cow<'a, [P]>
+    where
+        P: Clone,
+    {
+        if self.is_contiguous() {
+            return Cow::Borrowed(self.data);
+        }
+        Cow::Owned(self.into_owned().take())
+    }
+
+    /// The data of all rows including the gaps between them. Row `y` starts
+    /// at `y * stride()` pixels.
+    pub fn strided_data(&self) -> &'a [P] {
         self.data
     }
 
     /// The pixel data of a single row of the image.
     pub fn row(&self, y: usize) -> &'a [P] {
-        &self.data()[y * self.width()..(y + 1) * self.width()]
+        assert!(y < self.height());
+        if self.width() == 0 {
+            return &[];
+        }
+        let start = y * self.stride;
+        &self.data[start..start + self.width()]
     }
 
-    pub fn rows(&self) -> ChunksExact<'_, P> {
-        self.data().chunks_exact(self.width())
+    pub fn rows(
+        &self,
+    ) -> impl DoubleEndedIterator<Item = &'a [P]> + ExactSizeIterator + Clone + 'a {
+        let view = *self;
+        (0..self.height()).map(move |y| view.row(y))
+    }
+
+    /// Returns a view of the given rectangle of the image without copying.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the rectangle isn't inside the image.
+    pub fn sub_view(&self, rect: Rect) -> ImageView<'a, P> {
+        assert!(
+            rect.right() <= self.width() && rect.bottom() <= self.height(),
+            "The rectangle {rect:?} is not inside an image of size {:?}.",
+            self.size()
+        );
+
+        if rect.is_empty() {
+            return ImageView::with_stride(rect.size(), rect.width, &[]);
+        }
+        let start = rect.y * self.stride + rect.x;
+        ImageView::with_stride(rect.size(), self.stride, &self.data[start..])
     }
 
     pub fn map<T>(&self, f: impl Fn(&P) -> T) -> Image<T> {
         Image {
-            data: self.data().iter().map(f).collect(),
+            data: self.rows().flat_map(|row| row.iter().map(&f)).collect(),
             size: self.size(),
         }
     }
@@ -243,3 +432,380 @@ impl<'a, P> ImageView<'a, P> {
         }
     }
 }
+
+impl<T: Sample> Image<T> {
+    /// Converts all samples of the image to another sample type.
+    ///
+    /// See [`Sample`] for how values are mapped.
+    pub fn convert<U: Sample>(&self) -> Image<U> {
+        self.map(|&v| U::from_f32(v.to_f32()))
+    }
+}
+impl<T: Sample> ImageView<'_, T> {
+    /// Converts all samples of the image to another sample type.
+    ///
+    /// See [`Sample`] for how values are mapped.
+    pub fn convert<U: Sample>(&self) -> Image<U> {
+        self.map(|&v| U::from_f32(v.to_f32()))
+    }
+}
+
+/// A mutably borrowed (part of an) image.
+///
+/// This is the mutable counterpart of [`ImageView`] and uses the same layout.
+#[derive(Debug)]
+pub struct ImageViewMut<'a, P> {
+    data: &'a mut [P],
+    size: Size,
+    stride: usize,
+}
+
+impl<'a, P> ImageViewMut<'a, P> {
+    /// # Panics
+    ///
+    /// Panics if the number of pixels doesn't match the size. See
+    /// [`Self::try_new`].
+    pub fn new(size: Size, data: &'a mut [P]) -> Self {
+        Self::try_new(size, data).expect("The data must match the size of the image.")
+    }
+    pub fn try_new(size: Size, data: &'a mut [P]) -> Result<Self, ImageError> {
+        check_len(size.len(), data.len())?;
+        Ok(Self {
+            data,
+            size,
+            stride: size.width,
+        })
+    }
+    /// Creates a view of an image whose rows are `stride` pixels apart.
+    ///
+    /// Excess data after the last row is ignored.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the stride is smaller than the width or if there isn't
+    /// enough data. See [`Self::try_with_stride`].
+    pub fn with_stride(size: Size, stride: usize, data: &'a mut [P]) -> Self {
+        Self::try_with_stride(size, stride, data)
+            .expect("The data must match the size and stride of the view.")
+    }
+    pub fn try_with_stride(
+        size: Size,
+        stride: usize,
+        data: &'a mut [P],
+    ) -> Result<Self, ImageError> {
+        let len = check_strided(size, stride, 1, data.len())?;
+        Ok(Self {
+            data: &mut data[..len],
+            size,
+            stride,
+        })
+    }
+
+    pub fn size(&self) -> Size {
+        self.size
+    }
+
+    pub fn width(&self) -> usize {
+        self.size().width
+    }
+    pub fn height(&self) -> usize {
+        self.size().height
+    }
+    pub fn len(&self) -> usize {
+        self.size().len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.size().is_empty()
+    }
+
+    /// The number of pixels between the starts of 2 consecutive rows.
+    pub fn stride(&self) -> usize {
+        self.stride
+    }
+    /// Whether the rows of the view directly follow each other in memory.
+    pub fn is_contiguous(&self) -> bool {
+        self.data.len() == self.len()
+    }
+
+    pub fn view(&self) -> ImageView<'_, P> {
+        ImageView::with_stride(self.size, self.stride, self.data)
+    }
+    /// Returns a shorter-lived mutable view of the same pixels.
+    pub fn reborrow(&mut self) -> ImageViewMut<'_, P> {
+        ImageViewMut {
+            data: self.data,
+            size: self.size,
+            stride: self.stride,
+        }
+    }
+
+    /// The pixel data of the image.
+    ///
+    /// Pixel data is layed out in row-major order.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the view isn't contiguous.
+    pub fn data(&self) -> &[P] {
+        self.view().data()
+    }
+    /// The pixel data of the image.
+    ///
+    /// Pixel data is layed out in row-major order.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the view isn't contiguous. Use [`Self::rows_mut`] to access
+    /// the pixels of any view.
+    pub fn data_mut(&mut self) -> &mut [P] {
+        assert!(
+            self.is_contiguous(),
+            "The pixel data of a non-contiguous view can only be accessed by row."
+        );
+        self.data
+    }
+
+    /// The pixel data of a single row of the image.
+    pub fn row(&self, y: usize) -> &[P] {
+        self.view().row(y)
+    }
+    /// The pixel data of a single row of the image.
+    pub fn row_mut(&mut self, y: usize) -> &mut [P] {
+        assert!(y < self.height());
+        if self.width() == 0 {
+            return &mut [];
+        }
+        let start = y * self.stride;
+        &mut self.data[start..start + self.size.width]
+    }
+
+    pub fn rows_mut(
+        &mut self,
+    ) -> impl DoubleEndedIterator<Item = &mut [P]> + ExactSizeIterator + '_ {
+        let width = self.width();
+        // the last row is exactly `width` pixels long
+        self.data
+            .chunks_mut(self.stride.max(1))
+            .map(move |row| &mut row[..width])
+    }
+
+    /// Returns a mutable view of the given rectangle of the image.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the rectangle isn't inside the image.
+    pub fn sub_view_mut(&mut self, rect: Rect) -> ImageViewMut<'_, P> {
+        self.reborrow().into_sub_view(rect)
+    }
+    /// Same as [`Self::sub_view_mut`], but keeps the lifetime of the view.
+    pub fn into_sub_view(self, rect: Rect) -> ImageViewMut<'a, P> {
+        assert!(
+            rect.right() <= self.width() && rect.bottom() <= self.height(),
+            "The rectangle {rect:?} is not inside an image of size {:?}.",
+            self.size()
+        );
+
+        if rect.is_empty() {
+            return ImageViewMut::with_stride(rect.size(), rect.width, &mut []);
+        }
+        let start = rect.y * self.stride + rect.x;
+        ImageViewMut::with_stride(rect.size(), self.stride, &mut self.data[start..])
+    }
+
+    pub fn fill(&mut self, c: P)
+    where
+        P: Clone,
+    {
+        for row in self.rows_mut() {
+            row.fill(c.clone());
+        }
+    }
+
+    /// Copies all pixels of the given image into this view.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the sizes of the images differ.
+    pub fn copy_from(&mut self, src: ImageView<P>)
+    where
+        P: Clone,
+    {
+        assert_eq!(self.size(), src.size());
+        for (dst, src) in self.rows_mut().zip(src.rows()) {
+            dst.clone_from_slice(src);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ImageError;
+
+    use super::{Image, ImageView, ImageViewMut, Rect, Size};
+
+    /// A 4x3 image with the pixels 0 to 11.
+    fn small() -> Image<u8> {
+        Image::new(Size::new(4, 3), (0..12).collect())
+    }
+
+    #[test]
+    fn try_new() {
+        let size = Size::new(2, 2);
+        assert!(Image::try_new(size, vec![0; 4]).is_ok());
+        assert_eq!(
+            Image::try_new(size, vec![0; 5]).err(),
+            Some(ImageError::DataLength {
+                expected: 4,
+                actual: 5
+            })
+        );
+
+        let data = [0; 5];
+        assert!(ImageView::try_new(size, &data[..4]).is_ok());
+        assert_eq!(
+            ImageView::try_new(size, &data[..3]).err(),
+            Some(ImageError::DataLength {
+                expected: 4,
+                actual: 3
+            })
+        );
+        assert!(ImageView::try_with_stride(size, 3, &data).is_ok());
+        assert_eq!(
+            ImageView::try_with_stride(size, 3, &data[..4]).err(),
+            Some(ImageError::DataLength {
+                expected: 5,
+                actual: 4
+            })
+        );
+        assert_eq!(
+            ImageView::try_with_stride(size, 1, &data).err(),
+            Some(ImageError::StrideTooSmall {
+                width: 2,
+                stride: 1
+            })
+        );
+
+        let mut data = [0; 5];
+        assert_eq!(
+            ImageViewMut::try_with_stride(size, 1, &mut data).err(),
+            Some(ImageError::StrideTooSmall {
+                width: 2,
+                stride: 1
+            })
+        );
+    }
+
+    #[test]
+    fn strided_view() {
+        // a 2x2 view of the rows of a 3x2 image with 1 pixel of padding
+        let data = [0, 1, 9, 2, 3];
+        let view = ImageView::with_stride(Size::new(2, 2), 3, &data);
+        assert_eq!(view.stride(), 3);
+        assert!(!view.is_contiguous());
+        assert_eq!(view.strided_data(), &data);
+        assert_eq!(view.row(1), &[2, 3]);
+        assert_eq!(view.rows().collect::<Vec<_>>(), [&[0, 1], &[2, 3]]);
+        assert_eq!(view.contiguous_data().as_ref(), &[0, 1, 2, 3]);
+        assert_eq!(view.into_owned().data(), &[0, 1, 2, 3]);
+
+        // excess data after the last row is ignored
+        let view = ImageView::with_stride(Size::new(2, 2), 2, &[0, 1, 2, 3, 4]);
+        assert!(view.is_contiguous());
+        assert_eq!(view.data(), &[0, 1, 2, 3]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn strided_view_data() {
+        ImageView::with_stride(Size::new(2, 2), 3, &[0, 1, 9, 2, 3]).data();
+    }
+
+    #[test]
+    fn sub_view() {
+        let img = small();
+        let view = img.sub_view(Rect::new(1, 1, 2, 2));
+        assert_eq!(view.size(), Size::new(2, 2));
+        assert_eq!(view.stride(), 4);
+        assert!(!view.is_contiguous());
+        assert_eq!(view.strided_data(), &[5, 6, 7, 8, 9, 10]);
+        assert_eq!(view.rows().collect::<Vec<_>>(), [&[5, 6], &[9, 10]]);
+        assert_eq!(view.rows().next_back(), Some(&[9, 10][..]));
+        assert_eq!(view.contiguous_data().as_ref(), &[5, 6, 9, 10]);
+
+        // sub views of sub views
+        let inner = view.sub_view(Rect::new(1, 0, 1, 2));
+        assert_eq!(inner.stride(), 4);
+        assert_eq!(inner.rows().collect::<Vec<_>>(), [&[6], &[10]]);
+
+        // full rows and single rows are contiguous
+        let rows = img.sub_view(Rect::new(0, 1, 4, 2));
+        assert!(rows.is_contiguous());
+        assert_eq!(rows.data(), &[4, 5, 6, 7, 8, 9, 10, 11]);
+        let row = img.sub_view(Rect::new(1, 2, 2, 1));
+        assert!(row.is_contiguous());
+        assert_eq!(row.data(), &[9, 10]);
+
+        assert!(img.sub_view(Rect::new(4, 3, 0, 0)).is_empty());
+    }
+
+    #[test]
+    fn empty_sub_view() {
+        let mut img = small();
+        let view = img.sub_view(Rect::new(1, 0, 0, 3));
+        assert_eq!(view.size(), Size::new(0, 3));
+        assert_eq!(view.row(2), &[] as &[u8]);
+        assert_eq!(view.rows().count(), 3);
+        assert!(view.into_owned().is_empty());
+        assert!(view.map(|p| p + 1).is_empty());
+
+        let mut view = img.sub_view_mut(Rect::new(4, 1, 0, 2));
+        assert_eq!(view.row_mut(1), &mut [] as &mut [u8]);
+        view.fill(99);
+        assert_eq!(img.data(), small().data());
+
+        // views built with any stride don't have pixels in their rows
+        let view = ImageView::<u8>::with_stride(Size::new(0, 3), 5, &[]);
+        assert_eq!(view.rows().count(), 3);
+    }
+
+    #[test]
+    #[should_panic]
+    fn sub_view_outside() {
+        small().sub_view(Rect::new(2, 2, 3, 1));
+    }
+
+    #[test]
+    fn sub_view_mut() {
+        let mut img = small();
+        let mut view = img.sub_view_mut(Rect::new(1, 1, 2, 2));
+        view.fill(99);
+        view.row_mut(1)[1] = 42;
+        #[rustfmt::skip]
+        assert_eq!(img.data(), &[
+            0, 1, 2, 3,
+            4, 99, 99, 7,
+            8, 99, 42, 11,
+        ]);
+
+        let mut img = small();
+        let src = Image::new(Size::new(1, 3), vec![20, 21, 22]);
+        img.sub_view_mut(Rect::new(0, 0, 4, 3))
+            .into_sub_view(Rect::new(2, 0, 1, 3))
+            .copy_from(src.view());
+        #[rustfmt::skip]
+        assert_eq!(img.data(), &[
+            0, 1, 20, 3,
+            4, 5, 21, 7,
+            8, 9, 22, 11,
+        ]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn copy_from_mismatched_size() {
+        let mut img = small();
+        let src = Image::new(Size::new(3, 2), vec![0; 6]);
+        img.sub_view_mut(Rect::new(0, 0, 2, 3))
+            .copy_from(src.view());
+    }
+}
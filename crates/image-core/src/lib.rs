@@ -1,8 +1,14 @@
+mod error;
+mod geometry;
 mod image;
 mod ndim;
 mod pixel;
+mod sample;
 pub mod util;
 
+pub use error::ImageError;
+pub use geometry::*;
 pub use image::*;
 pub use ndim::*;
 pub use pixel::*;
+pub use sample::*;
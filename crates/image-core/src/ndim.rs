@@ -1,4 +1,10 @@
-use crate::{pixel::Flatten, FromFlat, Image, Size};
+use std::borrow::Cow;
+
+use crate::{
+    error::{check_len, check_strided},
+    pixel::Flatten,
+    FromFlat, Image, ImageError, Rect, Sample, Size,
+};
 
 #[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
 pub struct Shape {
@@ -37,16 +43,12 @@ impl Shape {
 
 /// A 3D image that is similar to how numpy arrays.
 #[derive(Debug, Clone)]
-pub struct NDimImage {
-    data: Vec<f32>,
+pub struct NDimImage<T = f32> {
+    data: Vec<T>,
     shape: Shape,
 }
 
 impl NDimImage {
-    pub fn new(shape: Shape, data: Vec<f32>) -> Self {
-        assert!(shape.len() == data.len());
-        Self { data, shape }
-    }
     pub fn zeros(shape: Shape) -> Self {
         Self::new(shape, vec![0f32; shape.len()])
     }
@@ -61,13 +63,40 @@ impl NDimImage {
                 .collect(),
         )
     }
+}
+
+impl<T> NDimImage<T> {
+    /// Creates a new image from its row-major, interleaved data.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the number of elements doesn't match the shape. See
+    /// [`Self::try_new`].
+    pub fn new(shape: Shape, data: Vec<T>) -> Self {
+        Self::try_new(shape, data).expect("The data must match the shape of the image.")
+    }
+    pub fn try_new(shape: Shape, data: Vec<T>) -> Result<Self, ImageError> {
+        check_len(shape.len(), data.len())?;
+        Ok(Self { data, shape })
+    }
 
-    pub fn take(self) -> Vec<f32> {
+    pub fn take(self) -> Vec<T> {
         self.data
     }
-    pub fn view(&self) -> NDimView {
+    pub fn view(&self) -> NDimView<'_, T> {
         NDimView::new(self.shape, &self.data)
     }
+    /// See [`NDimView::sub_view`].
+    pub fn sub_view(&self, rect: Rect) -> NDimView<'_, T> {
+        self.view().sub_view(rect)
+    }
+    pub fn view_mut(&mut self) -> NDimViewMut<'_, T> {
+        NDimViewMut::new(self.shape, &mut self.data)
+    }
+    /// See [`NDimViewMut::sub_view_mut`].
+    pub fn sub_view_mut(&mut self, rect: Rect) -> NDimViewMut<'_, T> {
+        self.view_mut().into_sub_view(rect)
+    }
 
     pub fn shape(&self) -> Shape {
         self.shape
@@ -85,24 +114,229 @@ impl NDimImage {
         self.shape().channels
     }
 
-    pub fn data(&self) -> &[f32] {
+    pub fn data(&self) -> &[T] {
         &self.data[..]
     }
-    pub fn data_mut(&mut self) -> &mut [f32] {
+    pub fn data_mut(&mut self) -> &mut [T] {
         &mut self.data[..]
     }
 }
 
-#[derive(Debug, Clone, Copy)]
-pub struct NDimView<'a> {
-    data: &'a [f32],
+/// A borrowed (part of a) 3D image.
+///
+/// Rows of the view are `stride` pixels apart in the underlying buffer. A
+/// view is contiguous if there are no gaps between its rows.
+#[derive(Debug)]
+pub struct NDimView<'a, T = f32> {
+    data: &'a [T],
+    shape: Shape,
+    stride: usize,
+}
+
+impl<T> Clone for NDimView<'_, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+impl<T> Copy for NDimView<'_, T> {}
+
+impl<'a, T> NDimView<'a, T> {
+    /// # Panics
+    ///
+    /// Panics if the number of elements doesn't match the shape. See
+    /// [`Self::try_new`].
+    pub fn new(shape: Shape, data: &'a [T]) -> Self {
+        Self::try_new(shape, data).expect("The data must match the shape of the image.")
+    }
+    pub fn try_new(shape: Shape, data: &'a [T]) -> Result<Self, ImageError> {
+        check_len(shape.len(), data.len())?;
+        Ok(Self {
+            data,
+            shape,
+            stride: shape.width,
+        })
+    }
+    /// Creates a view of an image whose rows are `stride` pixels apart.
+    ///
+    /// Excess data after the last row is ignored.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the stride is smaller than the width or if there isn't
+    /// enough data. See [`Self::try_with_stride`].
+    pub fn with_stride(shape: Shape, stride: usize, data: &'a [T]) -> Self {
+        Self::try_with_stride(shape, stride, data)
+            .expect("The data must match the shape and stride of the view.")
+    }
+    pub fn try_with_stride(shape: Shape, stride: usize, data: &'a [T]) -> Result<Self, ImageError> {
+        let len = check_strided(shape.size(), stride, shape.channels, data.len())?;
+        Ok(Self {
+            data: &data[..len],
+            shape,
+            stride,
+        })
+    }
+
+    pub fn shape(&self) -> Shape {
+        self.shape
+    }
+    pub fn size(&self) -> Size {
+        self.shape().size()
+    }
+    pub fn width(&self) -> usize {
+        self.shape().width
+    }
+    pub fn height(&self) -> usize {
+        self.shape().height
+    }
+    pub fn channels(&self) -> usize {
+        self.shape().channels
+    }
+
+    /// The number of pixels between the starts of 2 consecutive rows.
+    pub fn stride(&self) -> usize {
+        self.stride
+    }
+    /// Whether the rows of the view directly follow each other in memory.
+    pub fn is_contiguous(&self) -> bool {
+        self.data.len() == self.shape.len()
+    }
+
+    /// The pixel data of the image.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the view isn't contiguous. Use [`Self::rows`] to access the
+    /// pixels of any view.
+    pub fn data(&self) -> &'a [T] {
+        assert!(
+            self.is_contiguous(),
+            "The pixel data of a non-contiguous view can only be accessed by row."
+        );
+        self.data
+    }
+
+    /// The pixel data of the image. The data is only copied if the view isn't
+    /// contiguous.
+    pub fn contiguous_data(&self) -> Cow<'a, [T]>
+    where
+        T: Clone,
+    {
+        if self.is_contiguous() {
+            return Cow::Borrowed(self.data);
+        }
+        Cow::Owned(self.into_owned().take())
+    }
+
+    /// The data of all rows including the gaps between them. Row `y` starts
+    /// at `y * stride()` pixels.
+    pub fn strided_data(&self) -> &'a [T] {
+        self.data
+    }
+
+    /// The pixel data of a single row of the image.
+    pub fn row(&self, y: usize) -> &'a [T] {
+        assert!(y < self.height());
+        let c = self.channels();
+        if self.width() * c == 0 {
+            return &[];
+        }
+        let start = y * self.stride * c;
+        &self.data[start..start + self.width() * c]
+    }
+
+    pub fn rows(
+        &self,
+    ) -> impl DoubleEndedIterator<Item = &'a [T]> + ExactSizeIterator + Clone + 'a {
+        let view = *self;
+        (0..self.height()).map(move |y| view.row(y))
+    }
+
+    /// Returns a view of the given rectangle of the image without copying.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the rectangle isn't inside the image.
+    pub fn sub_view(&self, rect: Rect) -> NDimView<'a, T> {
+        assert!(
+            rect.right() <= self.width() && rect.bottom() <= self.height(),
+            "The rectangle {rect:?} is not inside an image of size {:?}.",
+            self.size()
+        );
+
+        let shape = Shape::from_size(rect.size(), self.channels());
+        if shape.is_empty() {
+            return NDimView::with_stride(shape, rect.width, &[]);
+        }
+        let start = (rect.y * self.stride + rect.x) * self.channels();
+        NDimView::with_stride(shape, self.stride, &self.data[start..])
+    }
+
+    pub fn into_owned(&self) -> NDimImage<T>
+    where
+        T: Clone,
+    {
+        if self.is_contiguous() {
+            return NDimImage::new(self.shape, self.data.to_vec());
+        }
+
+        let mut data = Vec::with_capacity(self.shape.len());
+        for row in self.rows() {
+            data.extend_from_slice(row);
+        }
+        NDimImage::new(self.shape, data)
+    }
+}
+
+/// A mutably borrowed (part of a) 3D image.
+///
+/// This is the mutable counterpart of [`NDimView`] and uses the same layout.
+#[derive(Debug)]
+pub struct NDimViewMut<'a, T = f32> {
+    data: &'a mut [T],
     shape: Shape,
+    stride: usize,
 }
 
-impl<'a> NDimView<'a> {
-    pub fn new(shape: Shape, data: &'a [f32]) -> Self {
-        assert!(shape.len() == data.len());
-        Self { data, shape }
+impl<'a, T> NDimViewMut<'a, T> {
+    /// # Panics
+    ///
+    /// Panics if the number of elements doesn't match the shape. See
+    /// [`Self::try_new`].
+    pub fn new(shape: Shape, data: &'a mut [T]) -> Self {
+        Self::try_new(shape, data).expect("The data must match the shape of the image.")
+    }
+    pub fn try_new(shape: Shape, data: &'a mut [T]) -> Result<Self, ImageError> {
+        check_len(shape.len(), data.len())?;
+        Ok(Self {
+            data,
+            shape,
+            stride: shape.width,
+        })
+    }
+    /// Creates a view of an image whose rows are `stride` pixels apart.
+    ///
+    /// Excess data after the last row is ignored.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the stride is smaller than the width or if there isn't
+    /// enough data. See [`Self::try_with_stride`].
+    pub fn with_stride(shape: Shape, stride: usize, data: &'a mut [T]) -> Self {
+        Self::try_with_stride(shape, stride, data)
+            .expect("The data must match the shape and stride of the view.")
+    }
+    pub fn try_with_stride(
+        shape: Shape,
+        stride: usize,
+        data: &'a mut [T],
+    ) -> Result<Self, ImageError> {
+        let len = check_strided(shape.size(), stride, shape.channels, data.len())?;
+        Ok(Self {
+            data: &mut data[..len],
+            shape,
+            stride,
+        })
     }
 
     pub fn shape(&self) -> Shape {
@@ -121,18 +355,122 @@ impl<'a> NDimView<'a> {
         self.shape().channels
     }
 
-    pub fn data(&self) -> &'a [f32] {
+    /// The number of pixels between the starts of 2 consecutive rows.
+    pub fn stride(&self) -> usize {
+        self.stride
+    }
+    /// Whether the rows of the view directly follow each other in memory.
+    pub fn is_contiguous(&self) -> bool {
+        self.data.len() == self.shape.len()
+    }
+
+    pub fn view(&self) -> NDimView<'_, T> {
+        NDimView::with_stride(self.shape, self.stride, self.data)
+    }
+    /// Returns a shorter-lived mutable view of the same pixels.
+    pub fn reborrow(&mut self) -> NDimViewMut<'_, T> {
+        NDimViewMut {
+            data: self.data,
+            shape: self.shape,
+            stride: self.stride,
+        }
+    }
+
+    /// The pixel data of the image.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the view isn't contiguous.
+    pub fn data(&self) -> &[T] {
+        self.view().data()
+    }
+    /// The pixel data of the image.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the view isn't contiguous. Use [`Self::rows_mut`] to access
+    /// the pixels of any view.
+    pub fn data_mut(&mut self) -> &mut [T] {
+        assert!(
+            self.is_contiguous(),
+            "The pixel data of a non-contiguous view can only be accessed by row."
+        );
+        self.data
+    }
+
+    /// The pixel data of a single row of the image.
+    pub fn row(&self, y: usize) -> &[T] {
+        self.view().row(y)
+    }
+    /// The pixel data of a single row of the image.
+    pub fn row_mut(&mut self, y: usize) -> &mut [T] {
+        assert!(y < self.height());
+        let c = self.channels();
+        if self.width() * c == 0 {
+            return &mut [];
+        }
+        let start = y * self.stride * c;
+        let end = start + self.width() * c;
+        &mut self.data[start..end]
+    }
+
+    pub fn rows_mut(
+        &mut self,
+    ) -> impl DoubleEndedIterator<Item = &mut [T]> + ExactSizeIterator + '_ {
+        let row_len = self.width() * self.channels();
+        // the last row is exactly `row_len` elements long
         self.data
+            .chunks_mut((self.stride * self.channels()).max(1))
+            .map(move |row| &mut row[..row_len])
+    }
+
+    /// Returns a mutable view of the given rectangle of the image.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the rectangle isn't inside the image.
+    pub fn sub_view_mut(&mut self, rect: Rect) -> NDimViewMut<'_, T> {
+        self.reborrow().into_sub_view(rect)
+    }
+    /// Same as [`Self::sub_view_mut`], but keeps the lifetime of the view.
+    pub fn into_sub_view(self, rect: Rect) -> NDimViewMut<'a, T> {
+        assert!(
+            rect.right() <= self.width() && rect.bottom() <= self.height(),
+            "The rectangle {rect:?} is not inside an image of size {:?}.",
+            self.size()
+        );
+
+        let shape = Shape::from_size(rect.size(), self.channels());
+        if shape.is_empty() {
+            return NDimViewMut::with_stride(shape, rect.width, &mut []);
+        }
+        let start = (rect.y * self.stride + rect.x) * self.channels();
+        NDimViewMut::with_stride(shape, self.stride, &mut self.data[start..])
+    }
+
+    /// Copies all pixels of the given image into this view.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the shapes of the images differ.
+    pub fn copy_from(&mut self, src: NDimView<T>)
+    where
+        T: Copy,
+    {
+        assert_eq!(self.shape(), src.shape());
+        for (dst, src) in self.rows_mut().zip(src.rows()) {
+            dst.copy_from_slice(src);
+        }
     }
 }
 
 #[derive(Debug)]
-pub enum NDimCow<'a> {
-    Image(NDimImage),
-    View(NDimView<'a>),
+pub enum NDimCow<'a, T = f32> {
+    Image(NDimImage<T>),
+    View(NDimView<'a, T>),
 }
 
-impl<'a> NDimCow<'a> {
+impl<'a, T> NDimCow<'a, T> {
     pub fn shape(&self) -> Shape {
         match self {
             Self::Image(image) => image.shape(),
@@ -152,20 +490,28 @@ impl<'a> NDimCow<'a> {
         self.shape().channels
     }
 
-    pub fn view(&self) -> NDimView {
+    pub fn view(&self) -> NDimView<'_, T> {
         match self {
             Self::Image(image) => image.view(),
             Self::View(view) => *view,
         }
     }
-    pub fn into_owned(self) -> NDimImage {
+    pub fn into_owned(self) -> NDimImage<T>
+    where
+        T: Clone,
+    {
         match self {
             Self::Image(image) => image,
-            Self::View(view) => NDimImage::new(view.shape, view.data.to_vec()),
+            Self::View(view) => view.into_owned(),
         }
     }
 
-    pub fn data(&self) -> &[f32] {
+    /// The pixel data of the image.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the image is a non-contiguous view.
+    pub fn data(&self) -> &[T] {
         match self {
             Self::Image(image) => image.data(),
             Self::View(view) => view.data(),
@@ -173,17 +519,40 @@ impl<'a> NDimCow<'a> {
     }
 }
 
-impl From<NDimImage> for NDimCow<'static> {
-    fn from(value: NDimImage) -> Self {
+impl<T> From<NDimImage<T>> for NDimCow<'static, T> {
+    fn from(value: NDimImage<T>) -> Self {
         Self::Image(value)
     }
 }
-impl<'a> From<NDimView<'a>> for NDimCow<'a> {
-    fn from(value: NDimView<'a>) -> Self {
+impl<'a, T> From<NDimView<'a, T>> for NDimCow<'a, T> {
+    fn from(value: NDimView<'a, T>) -> Self {
         Self::View(value)
     }
 }
 
+// Conversions between sample types
+
+impl<T: Sample> NDimImage<T> {
+    /// Converts all samples of the image to another sample type.
+    ///
+    /// See [`Sample`] for how values are mapped.
+    pub fn convert<U: Sample>(&self) -> NDimImage<U> {
+        self.view().convert()
+    }
+}
+impl<T: Sample> NDimView<'_, T> {
+    /// Converts all samples of the image to another sample type.
+    ///
+    /// See [`Sample`] for how values are mapped.
+    pub fn convert<U: Sample>(&self) -> NDimImage<U> {
+        let mut data = Vec::with_capacity(self.shape.len());
+        for row in self.rows() {
+            data.extend(row.iter().map(|&v| U::from_f32(v.to_f32())));
+        }
+        NDimImage::new(self.shape, data)
+    }
+}
+
 // Conversions from Image to NDimImage
 
 impl<P: Flatten> From<Image<P>> for NDimImage {
@@ -229,6 +598,9 @@ where
     fn into_pixels(self) -> Result<Image<P>, ShapeMismatch> {
         let size = self.size();
         let channels = self.channels();
+        if !self.is_contiguous() {
+            return self.into_owned().into_pixels();
+        }
         match P::from_flat_slice(self.data(), channels) {
             Ok(data) => Ok(Image::new(size, data.into_owned())),
             Err(e) => Err(ShapeMismatch {
@@ -238,3 +610,161 @@ where
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::ImageError;
+
+    use super::{NDimImage, NDimView, NDimViewMut, Rect, Shape};
+
+    /// A 3x3 image with 2 channels and the values 0 to 17.
+    fn small() -> NDimImage<u8> {
+        NDimImage::new(Shape::new(3, 3, 2), (0..18).collect())
+    }
+
+    #[test]
+    fn try_new() {
+        let shape = Shape::new(2, 2, 3);
+        assert!(NDimImage::try_new(shape, vec![0; 12]).is_ok());
+        assert_eq!(
+            NDimImage::try_new(shape, vec![0; 4]).err(),
+            Some(ImageError::DataLength {
+                expected: 12,
+                actual: 4
+            })
+        );
+
+        let data = [0; 15];
+        assert!(NDimView::try_new(shape, &data[..12]).is_ok());
+        assert_eq!(
+            NDimView::try_new(shape, &data).err(),
+            Some(ImageError::DataLength {
+                expected: 12,
+                actual: 15
+            })
+        );
+        assert!(NDimView::try_with_stride(shape, 3, &data).is_ok());
+        assert_eq!(
+            NDimView::try_with_stride(shape, 3, &data[..14]).err(),
+            Some(ImageError::DataLength {
+                expected: 15,
+                actual: 14
+            })
+        );
+        assert_eq!(
+            NDimView::try_with_stride(shape, 1, &data).err(),
+            Some(ImageError::StrideTooSmall {
+                width: 2,
+                stride: 1
+            })
+        );
+
+        let mut data = [0; 15];
+        assert_eq!(
+            NDimViewMut::try_with_stride(shape, 1, &mut data).err(),
+            Some(ImageError::StrideTooSmall {
+                width: 2,
+                stride: 1
+            })
+        );
+    }
+
+    #[test]
+    fn strided_view() {
+        // a 1x2 view with 2 channels and 1 pixel of padding between rows
+        let data = [0, 1, 8, 9, 2, 3];
+        let view = NDimView::with_stride(Shape::new(1, 2, 2), 2, &data);
+        assert_eq!(view.stride(), 2);
+        assert!(!view.is_contiguous());
+        assert_eq!(view.strided_data(), &data);
+        assert_eq!(view.row(1), &[2, 3]);
+        assert_eq!(view.rows().collect::<Vec<_>>(), [&[0, 1], &[2, 3]]);
+        assert_eq!(view.contiguous_data().as_ref(), &[0, 1, 2, 3]);
+        assert_eq!(view.into_owned().data(), &[0, 1, 2, 3]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn strided_view_data() {
+        NDimView::with_stride(Shape::new(1, 2, 2), 2, &[0, 1, 8, 9, 2, 3]).data();
+    }
+
+    #[test]
+    fn sub_view() {
+        let img = small();
+        let view = img.sub_view(Rect::new(1, 1, 2, 2));
+        assert_eq!(view.shape(), Shape::new(2, 2, 2));
+        assert_eq!(view.stride(), 3);
+        assert!(!view.is_contiguous());
+        assert_eq!(view.strided_data(), &[8, 9, 10, 11, 12, 13, 14, 15, 16, 17]);
+        assert_eq!(
+            view.rows().collect::<Vec<_>>(),
+            [&[8, 9, 10, 11], &[14, 15, 16, 17]]
+        );
+        assert_eq!(
+            view.contiguous_data().as_ref(),
+            &[8, 9, 10, 11, 14, 15, 16, 17]
+        );
+
+        let inner = view.sub_view(Rect::new(0, 1, 1, 1));
+        assert!(inner.is_contiguous());
+        assert_eq!(inner.data(), &[14, 15]);
+
+        let rows = img.sub_view(Rect::new(0, 2, 3, 1));
+        assert!(rows.is_contiguous());
+        assert_eq!(rows.data(), &[12, 13, 14, 15, 16, 17]);
+    }
+
+    #[test]
+    fn empty_sub_view() {
+        let mut img = small();
+        let view = img.sub_view(Rect::new(1, 0, 0, 3));
+        assert_eq!(view.shape(), Shape::new(0, 3, 2));
+        assert_eq!(view.row(2), &[] as &[u8]);
+        assert_eq!(view.rows().count(), 3);
+        assert_eq!(view.into_owned().shape(), Shape::new(0, 3, 2));
+
+        let mut view = img.sub_view_mut(Rect::new(3, 1, 0, 2));
+        assert_eq!(view.row_mut(1), &mut [] as &mut [u8]);
+        view.copy_from(NDimView::new(Shape::new(0, 2, 2), &[]));
+        assert_eq!(img.data(), small().data());
+
+        // zero channels
+        let img = NDimImage::<u8>::new(Shape::new(3, 2, 0), vec![]);
+        let view = img.sub_view(Rect::new(1, 0, 2, 2));
+        assert_eq!(view.rows().collect::<Vec<_>>(), [&[] as &[u8]; 2]);
+    }
+
+    #[test]
+    fn sub_view_mut() {
+        let mut img = small();
+        let src = NDimImage::new(Shape::new(1, 2, 2), vec![20, 21, 22, 23]);
+        img.sub_view_mut(Rect::new(2, 1, 1, 2))
+            .copy_from(src.view());
+        #[rustfmt::skip]
+        assert_eq!(img.data(), &[
+            0, 1, 2, 3, 4, 5,
+            6, 7, 8, 9, 20, 21,
+            12, 13, 14, 15, 22, 23,
+        ]);
+
+        let mut img = small();
+        let mut view = img.sub_view_mut(Rect::new(0, 0, 2, 2));
+        view.row_mut(1).fill(99);
+        #[rustfmt::skip]
+        assert_eq!(img.data(), &[
+            0, 1, 2, 3, 4, 5,
+            99, 99, 99, 99, 10, 11,
+            12, 13, 14, 15, 16, 17,
+        ]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn copy_from_mismatched_shape() {
+        let mut img = small();
+        let src = NDimImage::new(Shape::new(2, 2, 1), vec![0; 4]);
+        img.sub_view_mut(Rect::new(0, 0, 2, 2))
+            .copy_from(src.view());
+    }
+}
@@ -0,0 +1,54 @@
+pub use half::f16;
+
+/// The type of a single channel value of an image.
+///
+/// Integer samples represent values in the range 0 to 1, with 0 being `0` and
+/// 1 being the maximum value of the type. Converting a sample to `f32` and
+/// back is lossless.
+pub trait Sample: Copy + Default + PartialEq + Send + Sync + 'static {
+    fn to_f32(self) -> f32;
+    /// Converts the given value into the closest sample. Integer samples clamp
+    /// values outside of the range 0 to 1.
+    fn from_f32(value: f32) -> Self;
+}
+
+impl Sample for f32 {
+    #[inline]
+    fn to_f32(self) -> f32 {
+        self
+    }
+    #[inline]
+    fn from_f32(value: f32) -> Self {
+        value
+    }
+}
+impl Sample for f16 {
+    #[inline]
+    fn to_f32(self) -> f32 {
+        f16::to_f32(self)
+    }
+    #[inline]
+    fn from_f32(value: f32) -> Self {
+        f16::from_f32(value)
+    }
+}
+impl Sample for u8 {
+    #[inline]
+    fn to_f32(self) -> f32 {
+        self as f32 / u8::MAX as f32
+    }
+    #[inline]
+    fn from_f32(value: f32) -> Self {
+        (value.clamp(0.0, 1.0) * u8::MAX as f32).round() as u8
+    }
+}
+impl Sample for u16 {
+    #[inline]
+    fn to_f32(self) -> f32 {
+        self as f32 / u16::MAX as f32
+    }
+    #[inline]
+    fn from_f32(value: f32) -> Self {
+        (value.clamp(0.0, 1.0) * u16::MAX as f32).round() as u16
+    }
+}
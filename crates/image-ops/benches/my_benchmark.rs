@@ -8,6 +8,7 @@ use image_ops::{
     fill_alpha::{fill_alpha, FillMode},
     fragment_blur::{fragment_blur, fragment_blur_alpha},
     palette::extract_unique_ndim,
+    scale::{scale, Filter},
     threshold::{binary_threshold, AntiAliasing},
 };
 use test_util::data::{
@@ -39,7 +40,7 @@ fn criterion_benchmark(c: &mut Criterion) {
         b.iter(|| {
             let mut i = img_t.clone();
             fill_alpha(
-                &mut i,
+                i.view_mut(),
                 0.15,
                 FillMode::Fragment {
                     iterations: 8,
@@ -54,7 +55,7 @@ fn criterion_benchmark(c: &mut Criterion) {
         b.iter(|| {
             let mut i = img_t.clone();
             fill_alpha(
-                &mut i,
+                i.view_mut(),
                 0.15,
                 FillMode::ExtendColor { iterations: 1000 },
                 None,
@@ -66,7 +67,7 @@ fn criterion_benchmark(c: &mut Criterion) {
         b.iter(|| {
             let mut i = img_t.clone();
             fill_alpha(
-                &mut i,
+                i.view_mut(),
                 0.15,
                 FillMode::Nearest {
                     min_radius: u32::MAX,
@@ -85,31 +86,42 @@ fn criterion_benchmark(c: &mut Criterion) {
     });
     c.bench_function("error diffusion dither map", |b| {
         b.iter(|| {
-            error_diffusion_dither_map(&img, FloydSteinberg, &ChannelQuantization::new(4), None);
+            error_diffusion_dither_map(
+                img.view(),
+                FloydSteinberg,
+                &ChannelQuantization::new(4),
+                ScanOrder::Raster,
+                None,
+            );
         })
     });
     c.bench_function("error diffusion dither", |b| {
         let mut img = img.clone();
         b.iter(|| {
-            error_diffusion_dither(&mut img, FloydSteinberg, &ChannelQuantization::new(4));
+            error_diffusion_dither(
+                img.view_mut(),
+                FloydSteinberg,
+                &ChannelQuantization::new(4),
+                ScanOrder::Raster,
+            );
         })
     });
     c.bench_function("riemersma dither", |b| {
         let mut img = img.clone();
         b.iter(|| {
-            riemersma_dither(&mut img, 16, 1.0 / 16.0, &ChannelQuantization::new(4));
+            riemersma_dither(img.view_mut(), 16, 1.0 / 16.0, &ChannelQuantization::new(4));
         })
     });
     c.bench_function("ordered dither", |b| {
         let mut flower_nd: NDimImage = img.clone().into();
         b.iter(|| {
-            ordered_dither(&mut flower_nd, 4, ChannelQuantization::new(2));
+            ordered_dither(flower_nd.view_mut(), 4, ChannelQuantization::new(2));
         })
     });
     c.bench_function("quantize", |b| {
         let mut flower_nd: NDimImage = img.clone().into();
         b.iter(|| {
-            quantize_ndim(&mut flower_nd, ChannelQuantization::new(4));
+            quantize_ndim(flower_nd.view_mut(), ChannelQuantization::new(4));
         })
     });
     c.bench_function("error diffusion dither palette", |b| {
@@ -117,7 +129,7 @@ fn criterion_benchmark(c: &mut Criterion) {
         let palette = black_box(read_flower_palette());
         let quant = ColorPalette::new(RGB, palette.row(0).iter().copied(), BoundError);
         b.iter(|| {
-            error_diffusion_dither(&mut img, FloydSteinberg, &quant);
+            error_diffusion_dither(img.view_mut(), FloydSteinberg, &quant, ScanOrder::Raster);
         })
     });
 
@@ -140,6 +152,27 @@ fn criterion_benchmark(c: &mut Criterion) {
             image_ops::gamma::gamma_ndim(&mut img, 2.2);
         })
     });
+
+    let single_thread = rayon::ThreadPoolBuilder::new()
+        .num_threads(1)
+        .build()
+        .unwrap();
+    let lion_4x = img_lion.size().scale(4.);
+    c.bench_function("resize 4x lanczos3", |b| {
+        b.iter(|| scale(img_lion.view(), lion_4x, Filter::Lanczos3))
+    });
+    c.bench_function("resize 4x lanczos3 single-threaded", |b| {
+        b.iter(|| single_thread.install(|| scale(img_lion.view(), lion_4x, Filter::Lanczos3)))
+    });
+    let lion_quarter = img_lion.size().scale(0.25);
+    c.bench_function("resize 0.25x cubic catrom", |b| {
+        b.iter(|| scale(img_lion.view(), lion_quarter, Filter::CubicCatrom))
+    });
+    c.bench_function("resize 0.25x cubic catrom single-threaded", |b| {
+        b.iter(|| {
+            single_thread.install(|| scale(img_lion.view(), lion_quarter, Filter::CubicCatrom))
+        })
+    });
 }
 
 criterion_group!(benches, criterion_benchmark);
@@ -1,5 +1,5 @@
 use glam::Vec4;
-use image_core::Image;
+use image_core::{Image, ImageView};
 
 /// Overlays the given image with itself `n` times.
 ///
@@ -27,15 +27,13 @@ pub fn overlay_self_mut(img: &mut Image<Vec4>, n: u32) {
     }
 }
 
-pub fn overlay_mut(img: &mut Image<Vec4>, top: &Image<Vec4>) {
+pub fn overlay_mut(img: &mut Image<Vec4>, top: ImageView<Vec4>) {
     assert!(img.size() == top.size());
 
     let data = img.data_mut();
     assert!(data.len() == top.len());
 
-    for (i, a) in data.iter_mut().enumerate() {
-        let b = top.data()[i];
-
+    for (a, b) in data.iter_mut().zip(top.rows().flatten().copied()) {
         let final_alpha = 1. - (1. - a.w) * (1. - b.w);
 
         let mut rgb = b * b.w + *a * a.w * (1. - b.w);
@@ -60,7 +58,7 @@ mod tests {
     fn overlay_mut() {
         let mut original = read_abstract_transparent();
         let other = original.clone();
-        super::overlay_mut(&mut original, &other);
+        super::overlay_mut(&mut original, other.view());
         original.snapshot("overlay_mut");
     }
 }
@@ -0,0 +1,376 @@
+use glam::Vec4;
+use image_core::{Image, ImageView, NDimImage, NDimView, Shape};
+
+use crate::{
+    border::BorderMode,
+    scale::{FloatPixelFormat, PixelFormat},
+    util::{filter_rows, read_channel_plane, set_channel_plane},
+};
+
+/// A separable blur.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Blur {
+    /// A Gaussian blur with the given standard deviation per axis. The kernel
+    /// is cut off at 3 sigma.
+    Gaussian { sigma_x: f32, sigma_y: f32 },
+    /// A box blur with the given radius per axis, computed using running sums.
+    /// The cost per pixel doesn't depend on the radius.
+    Box { radius_x: usize, radius_y: usize },
+    /// A stack blur with the given radius per axis. This is equivalent to a
+    /// convolution with a triangle kernel, but computed using running sums,
+    /// so the cost per pixel doesn't depend on the radius.
+    Stack { radius_x: usize, radius_y: usize },
+}
+
+impl Blur {
+    fn line_filters(self) -> (LineFilter, LineFilter) {
+        match self {
+            Blur::Gaussian { sigma_x, sigma_y } => {
+                (LineFilter::gaussian(sigma_x), LineFilter::gaussian(sigma_y))
+            }
+            Blur::Box { radius_x, radius_y } => {
+                (LineFilter::Box(radius_x), LineFilter::Box(radius_y))
+            }
+            Blur::Stack { radius_x, radius_y } => {
+                (LineFilter::Stack(radius_x), LineFilter::Stack(radius_y))
+            }
+        }
+    }
+}
+
+/// A filter that is applied to single rows of an image.
+enum LineFilter {
+    Kernel(Vec<f32>),
+    Box(usize),
+    Stack(usize),
+}
+
+impl LineFilter {
+    fn gaussian(sigma: f32) -> Self {
+        if !sigma.is_finite() || sigma <= 0.0 {
+            return LineFilter::Box(0);
+        }
+
+        let radius = (sigma * 3.0).ceil() as isize;
+        let mut kernel: Vec<f32> = (-radius..=radius)
+            .map(|x| (-(x * x) as f32 / (2.0 * sigma * sigma)).exp())
+            .collect();
+        let sum: f32 = kernel.iter().sum();
+        kernel.iter_mut().for_each(|w| *w /= sum);
+        LineFilter::Kernel(kernel)
+    }
+
+    fn radius(&self) -> usize {
+        match self {
+            LineFilter::Kernel(kernel) => kernel.len() / 2,
+            LineFilter::Box(r) | LineFilter::Stack(r) => *r,
+        }
+    }
+
+    fn is_identity(&self) -> bool {
+        self.radius() == 0
+    }
+
+    /// Filters the given padded row. `src` has `radius` pixels of padding on
+    /// the left and `radius + 1` pixels of padding on the right.
+    fn apply<P>(&self, src: &[P], dst: &mut [P])
+    where
+        P: Copy,
+        FloatPixelFormat<P>: PixelFormat<InputPixel = P, OutputPixel = P>,
+    {
+        type F<P> = FloatPixelFormat<P>;
+        let format = F::<P>::default();
+        let r = self.radius();
+
+        match self {
+            LineFilter::Kernel(kernel) => {
+                for (i, dst) in dst.iter_mut().enumerate() {
+                    let mut acc = F::<P>::new_acc();
+                    for (w, p) in kernel.iter().zip(&src[i..]) {
+                        format.add_pixel_scaled(&mut acc, *p, *w);
+                    }
+                    *dst = format.acc_to_pixel(acc);
+                }
+            }
+            LineFilter::Box(_) => {
+                let scale = 1.0 / (2 * r + 1) as f32;
+
+                let mut sum = F::<P>::new_acc();
+                for p in &src[..=2 * r] {
+                    format.add_pixel(&mut sum, *p);
+                }
+
+                for (i, dst) in dst.iter_mut().enumerate() {
+                    if i > 0 {
+                        format.add_pixel_scaled(&mut sum, src[i + 2 * r], 1.0);
+                        format.add_pixel_scaled(&mut sum, src[i - 1], -1.0);
+                    }
+                    *dst = format.acc_to_pixel_scaled(sum, scale);
+                }
+            }
+            LineFilter::Stack(_) => {
+                let scale = 1.0 / ((r + 1) * (r + 1)) as f32;
+
+                // `sum` is the weighted sum of the current window. `sum_out`
+                // contains the left half (including the center), which loses
+                // weight when the window moves, and `sum_in` the pixels right
+                // of the center, which gain weight.
+                let mut sum = F::<P>::new_acc();
+                let mut sum_out = F::<P>::new_acc();
+                let mut sum_in = F::<P>::new_acc();
+                for (k, p) in src[..=2 * r].iter().enumerate() {
+                    let weight = (r + 1 - k.abs_diff(r)) as f32;
+                    format.add_pixel_scaled(&mut sum, *p, weight);
+                }
+                for p in &src[..=r] {
+                    format.add_pixel(&mut sum_out, *p);
+                }
+                for p in &src[r + 1..=2 * r + 1] {
+                    format.add_pixel(&mut sum_in, *p);
+                }
+
+                let n = dst.len();
+                for (i, dst) in dst.iter_mut().enumerate() {
+                    *dst = format.acc_to_pixel_scaled(sum, scale);
+
+                    if i + 1 < n {
+                        F::<P>::add_acc_scaled(&mut sum, sum_in, 1.0);
+                        F::<P>::add_acc_scaled(&mut sum, sum_out, -1.0);
+                        format.add_pixel_scaled(&mut sum_out, src[i + r + 1], 1.0);
+                        format.add_pixel_scaled(&mut sum_out, src[i], -1.0);
+                        format.add_pixel_scaled(&mut sum_in, src[i + 2 * r + 2], 1.0);
+                        format.add_pixel_scaled(&mut sum_in, src[i + r + 1], -1.0);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Blurs the given image.
+///
+/// Each axis is processed separately, and rows are processed in parallel.
+pub fn blur<P>(img: ImageView<P>, kind: Blur, border: BorderMode) -> Image<P>
+where
+    P: Copy + Default + Send + Sync,
+    FloatPixelFormat<P>: PixelFormat<InputPixel = P, OutputPixel = P>,
+{
+    let (filter_x, filter_y) = kind.line_filters();
+
+    let horizontal = if filter_x.is_identity() {
+        img.into_owned()
+    } else {
+        filter_rows(img, filter_x.radius(), border, |src, dst| {
+            filter_x.apply(src, dst)
+        })
+    };
+
+    if filter_y.is_identity() {
+        return horizontal;
+    }
+
+    // the vertical pass is done on the transposed image to make it cache
+    // friendly and parallelize it the same way
+    let transposed = horizontal.transpose();
+    std::mem::drop(horizontal);
+    let vertical = filter_rows(transposed.view(), filter_y.radius(), border, |src, dst| {
+        filter_y.apply(src, dst)
+    });
+    std::mem::drop(transposed);
+    vertical.transpose()
+}
+
+/// Blurs the given RGBA image with premultiplied alpha.
+///
+/// This prevents the colors of transparent pixels from bleeding into
+/// opaque pixels.
+pub fn blur_premultiplied_alpha(
+    img: ImageView<Vec4>,
+    kind: Blur,
+    border: BorderMode,
+) -> Image<Vec4> {
+    let premultiplied = img.map(|p| (*p * p.w).truncate().extend(p.w));
+    let mut result = blur(premultiplied.view(), kind, border);
+
+    // drop image now to free up memory asap
+    std::mem::drop(premultiplied);
+
+    result.data_mut().iter_mut().for_each(|p| {
+        let rgb = if p.w > 0.0 {
+            p.truncate() / p.w
+        } else {
+            Default::default()
+        };
+        *p = rgb.extend(p.w);
+    });
+
+    result
+}
+
+/// Blurs an image with any number of channels.
+///
+/// Each channel is blurred independently. The result is the same as blurring
+/// each channel on its own with [`blur`].
+pub fn blur_ndim(img: NDimView, kind: Blur, border: BorderMode) -> NDimImage {
+    let channels = img.channels();
+
+    let mut dest = NDimImage::zeros(Shape::from_size(img.size(), channels));
+    let mut plane = Vec::with_capacity(img.size().len());
+
+    for c in 0..channels {
+        // de-interleave the channel, blur it, and interleave it again
+        read_channel_plane(img, c, &mut plane);
+        let blurred = blur(ImageView::new(img.size(), &plane), kind, border);
+        set_channel_plane(&mut dest, c, blurred.data());
+    }
+
+    dest
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::Vec3A;
+    use image_core::{Image, NDimImage, Size};
+    use test_util::{
+        data::{read_flower_transparent, read_portrait},
+        snap::ImageSnapshot,
+    };
+
+    use crate::border::BorderMode;
+
+    use super::{blur, blur_ndim, blur_premultiplied_alpha, Blur};
+
+    /// A naive convolution with the given 1D kernel on both axes.
+    fn naive(img: &Image<Vec3A>, kernel: &[f32], border: BorderMode) -> Image<Vec3A> {
+        let r = (kernel.len() / 2) as isize;
+        let size = img.size();
+        let get = |x: isize, y: isize| match (border.map(x, size.width), border.map(y, size.height))
+        {
+            (Some(x), Some(y)) => img.row(y)[x],
+            _ => Vec3A::ZERO,
+        };
+
+        Image::from_fn(size, |x, y| {
+            let mut sum = Vec3A::ZERO;
+            for (j, wy) in kernel.iter().enumerate() {
+                for (i, wx) in kernel.iter().enumerate() {
+                    let p = get(x as isize + i as isize - r, y as isize + j as isize - r);
+                    sum += p * *wx * *wy;
+                }
+            }
+            sum
+        })
+    }
+
+    fn assert_close(a: &Image<Vec3A>, b: &Image<Vec3A>) {
+        assert_eq!(a.size(), b.size());
+        for (a, b) in a.data().iter().zip(b.data()) {
+            assert!((*a - *b).abs().max_element() < 1e-4, "{a} != {b}");
+        }
+    }
+
+    #[test]
+    fn running_sums_match_naive() {
+        let original = read_portrait();
+        let original = Image::from_fn(Size::new(60, 40), |x, y| original.row(y * 3)[x * 3]);
+
+        for border in [
+            BorderMode::Transparent,
+            BorderMode::Clamp,
+            BorderMode::Reflect,
+            BorderMode::Wrap,
+        ] {
+            let r = 4;
+            let box_kernel = vec![1.0 / (2 * r + 1) as f32; 2 * r + 1];
+            let blurred = blur(
+                original.view(),
+                Blur::Box {
+                    radius_x: r,
+                    radius_y: r,
+                },
+                border,
+            );
+            assert_close(&blurred, &naive(&original, &box_kernel, border));
+
+            let tent_kernel: Vec<f32> = (0..=2 * r)
+                .map(|k| (r + 1 - k.abs_diff(r)) as f32 / ((r + 1) * (r + 1)) as f32)
+                .collect();
+            let blurred = blur(
+                original.view(),
+                Blur::Stack {
+                    radius_x: r,
+                    radius_y: r,
+                },
+                border,
+            );
+            assert_close(&blurred, &naive(&original, &tent_kernel, border));
+        }
+    }
+
+    #[test]
+    fn gaussian() {
+        let original = read_portrait();
+        blur(
+            original.view(),
+            Blur::Gaussian {
+                sigma_x: 8.0,
+                sigma_y: 2.0,
+            },
+            BorderMode::Reflect,
+        )
+        .snapshot("blur_gaussian_8x2_reflect");
+    }
+
+    #[test]
+    fn box_and_stack() {
+        let original = read_portrait();
+        blur(
+            original.view(),
+            Blur::Box {
+                radius_x: 10,
+                radius_y: 10,
+            },
+            BorderMode::Clamp,
+        )
+        .snapshot("blur_box_10_clamp");
+        blur(
+            original.view(),
+            Blur::Stack {
+                radius_x: 10,
+                radius_y: 0,
+            },
+            BorderMode::Wrap,
+        )
+        .snapshot("blur_stack_10x0_wrap");
+    }
+
+    #[test]
+    fn premultiplied_alpha() {
+        let original = read_flower_transparent();
+        blur_premultiplied_alpha(
+            original.view(),
+            Blur::Gaussian {
+                sigma_x: 5.0,
+                sigma_y: 5.0,
+            },
+            BorderMode::Transparent,
+        )
+        .snapshot("blur_gaussian_5_premultiplied_alpha");
+    }
+
+    #[test]
+    fn ndim() {
+        let original = read_portrait();
+        let options = Blur::Stack {
+            radius_x: 3,
+            radius_y: 5,
+        };
+        let expected: NDimImage = blur(original.view(), options, BorderMode::Reflect).into();
+        let actual = blur_ndim(
+            NDimImage::from(original).view(),
+            options,
+            BorderMode::Reflect,
+        );
+        assert_eq!(actual.data(), expected.data());
+    }
+}
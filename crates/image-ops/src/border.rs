@@ -0,0 +1,29 @@
+use image_core::PadMode;
+
+/// Determines which color is used for samples outside of the source image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BorderMode {
+    /// Pixels outside the image are `P::default()` (transparent black for RGBA).
+    Transparent,
+    /// The nearest edge pixel is used.
+    Clamp,
+    /// The image is mirrored at its edges (`cba|abcd|dcb`).
+    Reflect,
+    /// The image is repeated (`bcd|abcd|abc`).
+    Wrap,
+}
+
+impl BorderMode {
+    /// Maps the given pixel index into `0..len`. Returns `None` if the pixel
+    /// is transparent.
+    #[inline]
+    pub(crate) fn map(self, i: isize, len: usize) -> Option<usize> {
+        let mode = match self {
+            BorderMode::Transparent => PadMode::Constant(()),
+            BorderMode::Clamp => PadMode::Edge,
+            BorderMode::Reflect => PadMode::Reflect,
+            BorderMode::Wrap => PadMode::Wrap,
+        };
+        mode.map_index(i, len)
+    }
+}
@@ -0,0 +1,285 @@
+use glam::Vec2;
+use image_core::{Image, ImageView, Size};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Connectivity {
+    /// Pixels are connected to their horizontal and vertical neighbors.
+    Four,
+    /// Pixels are connected to their horizontal, vertical, and diagonal
+    /// neighbors.
+    Eight,
+}
+
+/// A connected region of a mask.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Region {
+    /// The label of the region's pixels in the label image. Labels start at 1.
+    pub label: u32,
+    /// The number of pixels in the region.
+    pub area: usize,
+    /// The left edge of the bounding box.
+    pub x: usize,
+    /// The top edge of the bounding box.
+    pub y: usize,
+    /// The size of the bounding box.
+    pub size: Size,
+    /// The mean position of the region's pixels.
+    pub centroid: Vec2,
+}
+
+/// The result of [`label_components`].
+#[derive(Debug, Clone)]
+pub struct Components {
+    /// The label of each pixel. Background pixels are 0.
+    pub labels: Image<u32>,
+    /// All regions ordered by label. `regions[i]` has the label `i + 1`.
+    pub regions: Vec<Region>,
+}
+
+struct DisjointSet {
+    parents: Vec<u32>,
+}
+
+impl DisjointSet {
+    fn new() -> Self {
+        // 0 is the background
+        Self { parents: vec![0] }
+    }
+
+    fn make(&mut self) -> u32 {
+        let id = self.parents.len() as u32;
+        self.parents.push(id);
+        id
+    }
+
+    fn find(&mut self, mut id: u32) -> u32 {
+        while self.parents[id as usize] != id {
+            let parent = self.parents[id as usize];
+            // path halving
+            self.parents[id as usize] = self.parents[parent as usize];
+            id = parent;
+        }
+        id
+    }
+
+    fn union(&mut self, a: u32, b: u32) -> u32 {
+        let a = self.find(a);
+        let b = self.find(b);
+        let root = a.min(b);
+        self.parents[a.max(b) as usize] = root;
+        root
+    }
+}
+
+/// Labels the connected regions of the given mask.
+///
+/// Regions with fewer than `min_area` pixels are removed from the label image
+/// (their pixels become background). The remaining regions are labeled in the
+/// order their first pixel appears in the mask (row by row).
+pub fn label_components(
+    mask: ImageView<bool>,
+    connectivity: Connectivity,
+    min_area: usize,
+) -> Components {
+    let size = mask.size();
+    let w = size.width;
+
+    // first pass: provisional labels
+    let mut sets = DisjointSet::new();
+    let mut labels = vec![0_u32; size.len()];
+    for y in 0..size.height {
+        for x in 0..w {
+            if !mask.row(y)[x] {
+                continue;
+            }
+
+            let mut neighbors = [0_u32; 4];
+            if x > 0 {
+                neighbors[0] = labels[y * w + x - 1];
+            }
+            if y > 0 {
+                let above = (y - 1) * w;
+                neighbors[1] = labels[above + x];
+                if connectivity == Connectivity::Eight {
+                    if x > 0 {
+                        neighbors[2] = labels[above + x - 1];
+                    }
+                    if x + 1 < w {
+                        neighbors[3] = labels[above + x + 1];
+                    }
+                }
+            }
+
+            let mut label = 0;
+            for n in neighbors.into_iter().filter(|n| *n != 0) {
+                label = if label == 0 { n } else { sets.union(label, n) };
+            }
+            if label == 0 {
+                label = sets.make();
+            }
+            labels[y * w + x] = label;
+        }
+    }
+
+    // second pass: resolve labels and collect statistics
+    let mut final_labels = vec![0_u32; sets.parents.len()];
+    let mut regions: Vec<Region> = Vec::new();
+    let mut sums: Vec<(f64, f64)> = Vec::new();
+    for y in 0..size.height {
+        for x in 0..w {
+            let provisional = labels[y * w + x];
+            if provisional == 0 {
+                continue;
+            }
+
+            let root = sets.find(provisional) as usize;
+            if final_labels[root] == 0 {
+                regions.push(Region {
+                    label: regions.len() as u32 + 1,
+                    area: 0,
+                    x,
+                    y,
+                    size: Size::new(1, 1),
+                    centroid: Vec2::ZERO,
+                });
+                sums.push((0.0, 0.0));
+                final_labels[root] = regions.len() as u32;
+            }
+
+            let label = final_labels[root];
+            labels[y * w + x] = label;
+
+            let region = &mut regions[label as usize - 1];
+            region.area += 1;
+            let right = (region.x + region.size.width).max(x + 1);
+            let bottom = (region.y + region.size.height).max(y + 1);
+            region.x = region.x.min(x);
+            region.y = region.y.min(y);
+            region.size = Size::new(right - region.x, bottom - region.y);
+
+            let sum = &mut sums[label as usize - 1];
+            sum.0 += x as f64;
+            sum.1 += y as f64;
+        }
+    }
+
+    for (region, (sx, sy)) in regions.iter_mut().zip(sums) {
+        let area = region.area as f64;
+        region.centroid = Vec2::new((sx / area) as f32, (sy / area) as f32);
+    }
+
+    // remove small regions and make the remaining labels consecutive
+    if regions.iter().any(|r| r.area < min_area) {
+        let mut relabel = vec![0_u32; regions.len() + 1];
+        regions.retain(|r| r.area >= min_area);
+        for (i, region) in regions.iter_mut().enumerate() {
+            relabel[region.label as usize] = i as u32 + 1;
+            region.label = i as u32 + 1;
+        }
+        for label in labels.iter_mut() {
+            *label = relabel[*label as usize];
+        }
+    }
+
+    Components {
+        labels: Image::new(size, labels),
+        regions,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::Vec2;
+    use image_core::{Image, Size};
+    use test_util::{data::read_at, snap::ImageSnapshot};
+
+    use super::{label_components, Connectivity};
+
+    fn parse(rows: &[&str]) -> Image<bool> {
+        let size = Size::new(rows[0].len(), rows.len());
+        Image::new(
+            size,
+            rows.iter()
+                .flat_map(|r| r.chars().map(|c| c == '#'))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn connectivity() {
+        let mask = parse(&[
+            "##..#", //
+            "..#.#", //
+            ".#..#", //
+            "#..##", //
+        ]);
+
+        let four = label_components(mask.view(), Connectivity::Four, 0);
+        assert_eq!(four.regions.len(), 5);
+        assert_eq!(
+            four.labels.data(),
+            &[
+                1, 1, 0, 0, 2, //
+                0, 0, 3, 0, 2, //
+                0, 4, 0, 0, 2, //
+                5, 0, 0, 2, 2, //
+            ]
+        );
+
+        let eight = label_components(mask.view(), Connectivity::Eight, 0);
+        assert_eq!(eight.regions.len(), 2);
+        let r = &eight.regions[0];
+        assert_eq!((r.label, r.area, r.x, r.y), (1, 5, 0, 0));
+        assert_eq!(r.size, Size::new(3, 4));
+        assert_eq!(r.centroid, Vec2::new(0.8, 1.2));
+        let r = &eight.regions[1];
+        assert_eq!((r.label, r.area, r.x, r.y), (2, 5, 3, 0));
+        assert_eq!(r.size, Size::new(2, 4));
+
+        // U shapes that are only connected at the bottom
+        let u = parse(&[
+            "#.#.#", //
+            "#.#.#", //
+            "#####", //
+        ]);
+        let result = label_components(u.view(), Connectivity::Four, 0);
+        assert_eq!(result.regions.len(), 1);
+        assert_eq!(result.regions[0].area, 11);
+    }
+
+    #[test]
+    fn min_area() {
+        let mask = parse(&[
+            "#..##", //
+            "...##", //
+            ".#...", //
+            "##..#", //
+        ]);
+        let result = label_components(mask.view(), Connectivity::Four, 3);
+        assert_eq!(result.regions.len(), 2);
+        assert_eq!(
+            result.labels.data(),
+            &[
+                0, 0, 0, 1, 1, //
+                0, 0, 0, 1, 1, //
+                0, 2, 0, 0, 0, //
+                2, 2, 0, 0, 0, //
+            ]
+        );
+        assert_eq!(result.regions[1].label, 2);
+        assert_eq!(result.regions[1].area, 3);
+    }
+
+    #[test]
+    fn labels() {
+        let img = read_at();
+        let mask = img.map(|p| *p > 0.5);
+        let result = label_components(mask.view(), Connectivity::Eight, 0);
+
+        let count = result.regions.len() as f32;
+        result
+            .labels
+            .map(|l| *l as f32 / count)
+            .snapshot("components_at_8");
+    }
+}
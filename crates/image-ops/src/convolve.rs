@@ -0,0 +1,368 @@
+use image_core::{Image, ImageView, NDimImage, NDimView, Shape, Size};
+use rayon::prelude::*;
+
+use crate::{
+    border::BorderMode,
+    scale::{FloatPixelFormat, PixelFormat},
+    util::{filter_rows, read_channel_plane, set_channel_plane},
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConvolveError {
+    /// The kernel is empty, its data doesn't match its size, or it contains
+    /// non-finite values.
+    InvalidKernel,
+    /// The number of per-channel kernels doesn't match the number of channels
+    /// of the image.
+    ChannelMismatch { expected: usize, actual: usize },
+}
+
+/// A 2D convolution kernel.
+///
+/// The anchor of the kernel is at `(width / 2, height / 2)`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Kernel {
+    size: Size,
+    data: Vec<f32>,
+}
+
+impl Kernel {
+    /// Creates a new kernel from its row-major weights.
+    pub fn new(size: Size, data: Vec<f32>) -> Result<Self, ConvolveError> {
+        if size.is_empty() || size.len() != data.len() || !data.iter().all(|w| w.is_finite()) {
+            return Err(ConvolveError::InvalidKernel);
+        }
+        Ok(Self { size, data })
+    }
+
+    pub fn size(&self) -> Size {
+        self.size
+    }
+    pub fn data(&self) -> &[f32] {
+        &self.data
+    }
+    fn row(&self, y: usize) -> &[f32] {
+        &self.data[y * self.size.width..(y + 1) * self.size.width]
+    }
+    fn anchor(&self) -> (usize, usize) {
+        (self.size.width / 2, self.size.height / 2)
+    }
+
+    /// Decomposes the kernel into a column and a row vector whose outer
+    /// product is the kernel, if possible.
+    pub fn separate(&self) -> Option<(Vec<f32>, Vec<f32>)> {
+        let (pivot, max) = self
+            .data
+            .iter()
+            .map(|w| w.abs())
+            .enumerate()
+            .fold((0, 0.0), |acc, (i, w)| if w > acc.1 { (i, w) } else { acc });
+        if max == 0.0 {
+            return None;
+        }
+
+        let (px, py) = (pivot % self.size.width, pivot / self.size.width);
+        let column: Vec<f32> = (0..self.size.height).map(|y| self.row(y)[px]).collect();
+        let row: Vec<f32> = self.row(py).iter().map(|w| w / self.data[pivot]).collect();
+
+        let tolerance = max * 1e-6;
+        for (y, c) in column.iter().enumerate() {
+            for (w, r) in self.row(y).iter().zip(&row) {
+                if (w - c * r).abs() > tolerance {
+                    return None;
+                }
+            }
+        }
+
+        Some((column, row))
+    }
+}
+
+/// Correlates the given padded row (see [`filter_rows`]) with the given 1D
+/// kernel.
+fn correlate_row<P>(src: &[P], dst: &mut [P], kernel: &[f32], offset: usize)
+where
+    P: Copy,
+    FloatPixelFormat<P>: PixelFormat<InputPixel = P, OutputPixel = P>,
+{
+    let format = FloatPixelFormat::<P>::default();
+    for (i, dst) in dst.iter_mut().enumerate() {
+        let mut acc = FloatPixelFormat::<P>::new_acc();
+        for (w, p) in kernel.iter().zip(&src[offset + i..]) {
+            format.add_pixel_scaled(&mut acc, *p, *w);
+        }
+        *dst = format.acc_to_pixel(acc);
+    }
+}
+
+fn convolve_separable<P>(
+    img: ImageView<P>,
+    column: &[f32],
+    row: &[f32],
+    border: BorderMode,
+) -> Image<P>
+where
+    P: Copy + Default + Send + Sync,
+    FloatPixelFormat<P>: PixelFormat<InputPixel = P, OutputPixel = P>,
+{
+    let pass = |img: ImageView<P>, kernel: &[f32]| {
+        let anchor = kernel.len() / 2;
+        let radius = anchor.max(kernel.len() - 1 - anchor);
+        filter_rows(img, radius, border, |src, dst| {
+            correlate_row(src, dst, kernel, radius - anchor)
+        })
+    };
+
+    let horizontal = if row == [1.0] {
+        img.into_owned()
+    } else {
+        pass(img, row)
+    };
+    if column == [1.0] {
+        return horizontal;
+    }
+
+    let transposed = horizontal.transpose();
+    std::mem::drop(horizontal);
+    let vertical = pass(transposed.view(), column);
+    std::mem::drop(transposed);
+    vertical.transpose()
+}
+
+fn convolve_direct<P>(img: ImageView<P>, kernel: &Kernel, border: BorderMode) -> Image<P>
+where
+    P: Copy + Default + Send + Sync,
+    FloatPixelFormat<P>: PixelFormat<InputPixel = P, OutputPixel = P>,
+{
+    let size = img.size();
+    let mut data = vec![P::default(); size.len()];
+    if size.is_empty() {
+        return Image::new(size, data);
+    }
+
+    let (ax, ay) = kernel.anchor();
+    let k_size = kernel.size();
+
+    // pad all rows horizontally once, so the inner loop doesn't have to
+    // deal with borders
+    let padded_width = size.width + k_size.width - 1;
+    let mut padded = vec![P::default(); padded_width * size.height];
+    padded
+        .par_chunks_exact_mut(padded_width)
+        .enumerate()
+        .for_each(|(y, dst)| {
+            let src = img.row(y);
+            for (i, p) in dst.iter_mut().enumerate() {
+                if let Some(x) = border.map(i as isize - ax as isize, size.width) {
+                    *p = src[x];
+                }
+            }
+        });
+
+    let format = FloatPixelFormat::<P>::default();
+    data.par_chunks_exact_mut(size.width)
+        .enumerate()
+        .for_each_init(Vec::new, |acc, (y, dst)| {
+            acc.clear();
+            acc.resize(size.width, FloatPixelFormat::<P>::new_acc());
+
+            for ky in 0..k_size.height {
+                let Some(sy) = border.map((y + ky) as isize - ay as isize, size.height) else {
+                    continue;
+                };
+                let src = &padded[sy * padded_width..(sy + 1) * padded_width];
+
+                for (kx, w) in kernel.row(ky).iter().enumerate() {
+                    if *w == 0.0 {
+                        continue;
+                    }
+                    for (acc, p) in acc.iter_mut().zip(&src[kx..]) {
+                        format.add_pixel_scaled(acc, *p, *w);
+                    }
+                }
+            }
+
+            for (dst, acc) in dst.iter_mut().zip(acc.iter()) {
+                *dst = format.acc_to_pixel(*acc);
+            }
+        });
+
+    Image::new(size, data)
+}
+
+/// Convolves the image with the given kernel.
+///
+/// Like OpenCV's `filter2D`, this computes a correlation, so the kernel is not
+/// flipped. Separable kernels are automatically detected and applied as two
+/// 1D passes.
+pub fn convolve<P>(img: ImageView<P>, kernel: &Kernel, border: BorderMode) -> Image<P>
+where
+    P: Copy + Default + Send + Sync,
+    FloatPixelFormat<P>: PixelFormat<InputPixel = P, OutputPixel = P>,
+{
+    match kernel.separate() {
+        Some((column, row)) => convolve_separable(img, &column, &row, border),
+        None => convolve_direct(img, kernel, border),
+    }
+}
+
+/// The kernels applied to the channels of an image.
+#[derive(Debug, Clone, Copy)]
+pub enum ChannelKernels<'a> {
+    /// The same kernel is applied to all channels.
+    All(&'a Kernel),
+    /// Each channel has its own kernel.
+    PerChannel(&'a [Kernel]),
+}
+
+/// Convolves an image with any number of channels.
+///
+/// Each channel is convolved independently. See [`convolve`].
+pub fn convolve_ndim(
+    img: NDimView,
+    kernels: ChannelKernels,
+    border: BorderMode,
+) -> Result<NDimImage, ConvolveError> {
+    let channels = img.channels();
+    if let ChannelKernels::PerChannel(kernels) = kernels {
+        if kernels.len() != channels {
+            return Err(ConvolveError::ChannelMismatch {
+                expected: channels,
+                actual: kernels.len(),
+            });
+        }
+    }
+
+    let mut dest = NDimImage::zeros(Shape::from_size(img.size(), channels));
+    let mut plane = Vec::with_capacity(img.size().len());
+
+    for c in 0..channels {
+        let kernel = match kernels {
+            ChannelKernels::All(kernel) => kernel,
+            ChannelKernels::PerChannel(kernels) => &kernels[c],
+        };
+
+        // de-interleave the channel, convolve it, and interleave it again
+        read_channel_plane(img, c, &mut plane);
+        let convolved = convolve(ImageView::new(img.size(), &plane), kernel, border);
+        set_channel_plane(&mut dest, c, convolved.data());
+    }
+
+    Ok(dest)
+}
+
+#[cfg(test)]
+mod tests {
+    use image_core::{NDimImage, Size};
+    use test_util::{data::read_portrait, snap::ImageSnapshot};
+
+    use crate::border::BorderMode;
+
+    use super::{convolve, convolve_direct, convolve_ndim, ChannelKernels, Kernel};
+
+    #[test]
+    fn separable_detection() {
+        let sobel = Kernel::new(
+            Size::new(3, 3),
+            vec![-1.0, 0.0, 1.0, -2.0, 0.0, 2.0, -1.0, 0.0, 1.0],
+        )
+        .unwrap();
+        let (column, row) = sobel.separate().unwrap();
+        for (y, c) in column.iter().enumerate() {
+            for (r, w) in row.iter().zip(sobel.row(y)) {
+                assert!((c * r - w).abs() < 1e-6);
+            }
+        }
+
+        let laplace = Kernel::new(
+            Size::new(3, 3),
+            vec![0.0, 1.0, 0.0, 1.0, -4.0, 1.0, 0.0, 1.0, 0.0],
+        )
+        .unwrap();
+        assert!(laplace.separate().is_none());
+
+        assert!(Kernel::new(Size::new(2, 2), vec![1.0; 3]).is_err());
+        assert!(Kernel::new(Size::new(1, 1), vec![f32::NAN]).is_err());
+    }
+
+    #[test]
+    fn separable_matches_direct() {
+        let original = read_portrait();
+        // an asymmetric kernel with an even width
+        let column = [1.0, 2.0, 0.5];
+        let row = [0.25, -1.0, 0.5, 0.75];
+        let kernel = Kernel::new(
+            Size::new(4, 3),
+            column
+                .iter()
+                .flat_map(|c| row.iter().map(move |r| c * r))
+                .collect(),
+        )
+        .unwrap();
+        assert!(kernel.separate().is_some());
+
+        for border in [
+            BorderMode::Transparent,
+            BorderMode::Clamp,
+            BorderMode::Reflect,
+            BorderMode::Wrap,
+        ] {
+            let separable = convolve(original.view(), &kernel, border);
+            let direct = convolve_direct(original.view(), &kernel, border);
+            for (a, b) in separable.data().iter().zip(direct.data()) {
+                assert!((*a - *b).abs().max_element() < 1e-5, "{border:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn emboss() {
+        let original = read_portrait();
+        let kernel = Kernel::new(
+            Size::new(3, 3),
+            vec![-2.0, -1.0, 0.0, -1.0, 1.0, 1.0, 0.0, 1.0, 2.0],
+        )
+        .unwrap();
+        convolve(original.view(), &kernel, BorderMode::Clamp).snapshot("convolve_emboss");
+    }
+
+    #[test]
+    fn per_channel() {
+        let original: NDimImage = read_portrait().into();
+        let identity = Kernel::new(Size::new(1, 1), vec![1.0]).unwrap();
+        let shift = Kernel::new(Size::new(9, 1), {
+            let mut k = vec![0.0; 9];
+            k[8] = 1.0;
+            k
+        })
+        .unwrap();
+        let kernels = [shift.clone(), identity.clone(), shift];
+
+        let result = convolve_ndim(
+            original.view(),
+            ChannelKernels::PerChannel(&kernels),
+            BorderMode::Wrap,
+        )
+        .unwrap();
+        result.snapshot("convolve_per_channel_shift");
+
+        // the green channel is unchanged
+        for (a, b) in result.data().iter().zip(original.data()).skip(1).step_by(3) {
+            assert_eq!(a, b);
+        }
+
+        assert!(convolve_ndim(
+            original.view(),
+            ChannelKernels::PerChannel(&kernels[..2]),
+            BorderMode::Wrap,
+        )
+        .is_err());
+        let all = convolve_ndim(
+            original.view(),
+            ChannelKernels::All(&identity),
+            BorderMode::Wrap,
+        )
+        .unwrap();
+        assert_eq!(all.data(), original.data());
+    }
+}
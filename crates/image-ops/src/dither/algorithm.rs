@@ -1,8 +1,18 @@
+use image_core::ImageView;
+
+use super::DitherError;
+
 pub trait Diffuser {
     fn assign_weight(&mut self, y: usize, x: isize, weight: f32);
 }
 pub trait DiffusionAlgorithm {
     fn define_weights(&self, diffuser: impl Diffuser);
+    /// Defines the weights for a pixel with the given luminance. Only
+    /// algorithms with variable coefficients need to implement this.
+    fn define_weights_for(&self, diffuser: impl Diffuser, luminance: f32) {
+        let _ = luminance;
+        self.define_weights(diffuser);
+    }
 }
 
 pub struct Atkinson;
@@ -33,6 +43,17 @@ impl DiffusionAlgorithm for Burkes {
     }
 }
 
+pub struct Fan;
+impl DiffusionAlgorithm for Fan {
+    fn define_weights(&self, mut diffuser: impl Diffuser) {
+        diffuser.assign_weight(0, 1, 7_f32 / 16_f32);
+
+        diffuser.assign_weight(1, -2, 1_f32 / 16_f32);
+        diffuser.assign_weight(1, -1, 3_f32 / 16_f32);
+        diffuser.assign_weight(1, 0, 5_f32 / 16_f32);
+    }
+}
+
 pub struct FloydSteinberg;
 impl DiffusionAlgorithm for FloydSteinberg {
     fn define_weights(&self, mut diffuser: impl Diffuser) {
@@ -64,6 +85,90 @@ impl DiffusionAlgorithm for JarvisJudiceNinke {
     }
 }
 
+/// Ostromoukhov's variable-coefficient error diffusion.
+///
+/// The weights depend on the luminance of the pixel and were optimized to
+/// reduce artifacts at every gray level. The algorithm was designed for
+/// serpentine scanning.
+pub struct Ostromoukhov;
+impl DiffusionAlgorithm for Ostromoukhov {
+    fn define_weights(&self, diffuser: impl Diffuser) {
+        self.define_weights_for(diffuser, 0.5);
+    }
+    fn define_weights_for(&self, mut diffuser: impl Diffuser, luminance: f32) {
+        let level = (luminance.clamp(0.0, 1.0) * 255.0).round() as usize;
+        let [right, down_left, down] = OSTROMOUKHOV_COEFFICIENTS[level.min(255 - level)];
+        let sum = (right + down_left + down) as f32;
+
+        diffuser.assign_weight(0, 1, right as f32 / sum);
+
+        diffuser.assign_weight(1, -1, down_left as f32 / sum);
+        diffuser.assign_weight(1, 0, down as f32 / sum);
+    }
+}
+
+/// The right, down-left and down coefficients of the gray levels 0 to 127.
+/// Levels 128 to 255 are symmetric.
+#[rustfmt::skip]
+const OSTROMOUKHOV_COEFFICIENTS: [[u16; 3]; 128] = [
+    [13, 0, 5], [13, 0, 5], [21, 0, 10], [7, 0, 4],
+    [8, 0, 5], [47, 3, 28], [23, 3, 13], [15, 3, 8],
+    [22, 6, 11], [43, 15, 20], [7, 3, 3], [501, 224, 211],
+    [249, 116, 103], [165, 80, 67], [123, 62, 49], [489, 256, 191],
+    [81, 44, 31], [483, 272, 181], [60, 35, 22], [53, 32, 19],
+    [237, 148, 83], [471, 304, 161], [3, 2, 1], [481, 314, 185],
+    [354, 226, 155], [1389, 866, 685], [227, 138, 125], [267, 158, 163],
+    [327, 188, 220], [61, 34, 45], [627, 338, 505], [1227, 638, 1075],
+    [20, 10, 19], [1937, 1000, 1767], [977, 520, 855], [657, 360, 551],
+    [71, 40, 57], [2005, 1160, 1539], [337, 200, 247], [2039, 1240, 1425],
+    [257, 160, 171], [691, 440, 437], [1045, 680, 627], [301, 200, 171],
+    [177, 120, 95], [2141, 1480, 1083], [1079, 760, 513], [725, 520, 323],
+    [137, 100, 57], [2209, 1640, 855], [53, 40, 19], [2243, 1720, 741],
+    [565, 440, 171], [759, 600, 209], [1147, 920, 285], [2311, 1880, 513],
+    [97, 80, 19], [335, 280, 57], [1181, 1000, 171], [793, 680, 95],
+    [599, 520, 57], [2413, 2120, 171], [405, 360, 19], [2447, 2200, 57],
+    [11, 10, 0], [158, 151, 3], [178, 179, 7], [1030, 1091, 63],
+    [248, 277, 21], [318, 375, 35], [458, 571, 63], [878, 1159, 147],
+    [5, 7, 1], [172, 181, 37], [97, 76, 22], [72, 41, 17],
+    [119, 47, 29], [4, 1, 1], [4, 1, 1], [4, 1, 1],
+    [4, 1, 1], [4, 1, 1], [4, 1, 1], [4, 1, 1],
+    [4, 1, 1], [4, 1, 1], [65, 18, 17], [95, 29, 26],
+    [185, 62, 53], [30, 11, 9], [35, 14, 11], [85, 37, 28],
+    [55, 26, 19], [80, 41, 29], [155, 86, 59], [5, 3, 2],
+    [5, 3, 2], [5, 3, 2], [5, 3, 2], [5, 3, 2],
+    [5, 3, 2], [5, 3, 2], [5, 3, 2], [5, 3, 2],
+    [5, 3, 2], [5, 3, 2], [5, 3, 2], [5, 3, 2],
+    [305, 176, 119], [155, 86, 59], [105, 56, 39], [80, 41, 29],
+    [65, 32, 23], [55, 26, 19], [335, 152, 113], [85, 37, 28],
+    [115, 48, 37], [35, 14, 11], [355, 136, 109], [30, 11, 9],
+    [365, 128, 107], [185, 62, 53], [25, 8, 7], [95, 29, 26],
+    [385, 112, 103], [65, 18, 17], [395, 104, 101], [4, 1, 1],
+];
+
+pub struct ShiauFan;
+impl DiffusionAlgorithm for ShiauFan {
+    fn define_weights(&self, mut diffuser: impl Diffuser) {
+        diffuser.assign_weight(0, 1, 4_f32 / 8_f32);
+
+        diffuser.assign_weight(1, -2, 1_f32 / 8_f32);
+        diffuser.assign_weight(1, -1, 1_f32 / 8_f32);
+        diffuser.assign_weight(1, 0, 2_f32 / 8_f32);
+    }
+}
+
+/// The 5-cell variant of [`ShiauFan`].
+pub struct ShiauFan2;
+impl DiffusionAlgorithm for ShiauFan2 {
+    fn define_weights(&self, mut diffuser: impl Diffuser) {
+        diffuser.assign_weight(0, 1, 8_f32 / 16_f32);
+
+        diffuser.assign_weight(1, -3, 1_f32 / 16_f32);
+        diffuser.assign_weight(1, -2, 1_f32 / 16_f32);
+        diffuser.assign_weight(1, -1, 2_f32 / 16_f32);
+        diffuser.assign_weight(1, 0, 4_f32 / 16_f32);
+    }
+}
+
 pub struct Sierra;
 impl DiffusionAlgorithm for Sierra {
     fn define_weights(&self, mut diffuser: impl Diffuser) {
@@ -125,3 +230,69 @@ impl DiffusionAlgorithm for Stucki {
         diffuser.assign_weight(2, 2, 1_f32 / 42_f32);
     }
 }
+
+/// The maximum number of rows below the current row that a diffusion kernel
+/// can spread the error to.
+pub(super) const MAX_KERNEL_ROWS_BELOW: usize = 2;
+/// The maximum horizontal distance that a diffusion kernel can spread the
+/// error.
+pub(super) const MAX_KERNEL_RADIUS: usize = 3;
+
+/// A custom diffusion kernel.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiffusionKernel {
+    /// The row, horizontal offset and weight of all non-zero weights.
+    weights: Vec<(usize, isize, f32)>,
+}
+
+impl DiffusionKernel {
+    /// Creates a kernel from a matrix of weights.
+    ///
+    /// The first row of the matrix is the row of the current pixel and
+    /// `origin` is the column of the current pixel. Pixels that were already
+    /// processed (the current pixel and all pixels to its left in the first
+    /// row) must have a weight of 0. The weights are used as is, so they
+    /// should usually add up to 1.
+    pub fn new(weights: ImageView<f32>, origin: usize) -> Result<Self, DitherError> {
+        let size = weights.size();
+        if origin >= size.width {
+            return Err(DitherError::InvalidKernelOrigin {
+                origin,
+                width: size.width,
+            });
+        }
+        if size.height > MAX_KERNEL_ROWS_BELOW + 1
+            || origin > MAX_KERNEL_RADIUS
+            || size.width - 1 - origin > MAX_KERNEL_RADIUS
+        {
+            return Err(DitherError::KernelTooLarge {
+                width: size.width,
+                height: size.height,
+                origin,
+            });
+        }
+
+        let mut result = Vec::new();
+        for (y, row) in weights.rows().enumerate() {
+            for (x, &weight) in row.iter().enumerate() {
+                let processed = y == 0 && x <= origin;
+                if !weight.is_finite() || (processed && weight != 0.0) {
+                    return Err(DitherError::InvalidKernelWeight { x, y, weight });
+                }
+                if weight != 0.0 {
+                    result.push((y, x as isize - origin as isize, weight));
+                }
+            }
+        }
+
+        Ok(Self { weights: result })
+    }
+}
+
+impl DiffusionAlgorithm for DiffusionKernel {
+    fn define_weights(&self, mut diffuser: impl Diffuser) {
+        for &(y, x, weight) in &self.weights {
+            diffuser.assign_weight(y, x, weight);
+        }
+    }
+}
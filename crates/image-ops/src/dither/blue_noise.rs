@@ -0,0 +1,258 @@
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use image_core::{Image, Size};
+
+use crate::util::Random;
+
+/// The standard deviation of the Gaussian filter used to find clusters and
+/// voids. 1.5 is the value recommended by Ulichney.
+const SIGMA: f64 = 1.5;
+/// The radius of the Gaussian filter. The filter is truncated at about 3
+/// standard deviations, beyond which its values are negligible.
+const RADIUS: usize = (3.0 * SIGMA) as usize + 1;
+/// The seed of the initial random pattern. Changing it changes all maps.
+const SEED: u64 = 0x5EED_B1DE_0015_E000;
+
+/// The number of most recently generated maps that are kept in the cache.
+const CACHE_CAPACITY: usize = 4;
+
+static CACHE: Mutex<Vec<Arc<Image<f32>>>> = Mutex::new(Vec::new());
+
+/// Returns a blue-noise threshold map of size `size`x`size` for ordered
+/// dithering.
+///
+/// The map is generated with Ulichney's void-and-cluster method. It is
+/// deterministic and tiles seamlessly. Generating a map takes time
+/// proportional to `size^4`, so the most recently generated maps are cached.
+///
+/// # Panics
+///
+/// Panics if `size` is 0.
+pub fn blue_noise_map(size: usize) -> Arc<Image<f32>> {
+    assert!(size > 0, "The map size must be at least 1.");
+
+    let cached = |cache: &[Arc<Image<f32>>]| cache.iter().find(|m| m.width() == size).cloned();
+    if let Some(map) = cached(&lock_cache()) {
+        return map;
+    }
+
+    // generating takes a while, so other maps can be looked up in the meantime
+    let map = Arc::new(void_and_cluster(size));
+
+    let mut cache = lock_cache();
+    // another thread may have generated the same map in the meantime
+    if let Some(map) = cached(&cache) {
+        return map;
+    }
+    if cache.len() >= CACHE_CAPACITY {
+        cache.remove(0);
+    }
+    cache.push(map.clone());
+    map
+}
+
+fn lock_cache() -> MutexGuard<'static, Vec<Arc<Image<f32>>>> {
+    CACHE.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// A binary pattern on a torus along with the energy of every pixel, which is
+/// the sum of a Gaussian filter centered at every 1 of the pattern.
+#[derive(Clone)]
+struct Pattern {
+    size: usize,
+    radius: usize,
+    /// The filter value of every offset from `-radius` to `radius`.
+    kernel: Vec<f64>,
+    bits: Vec<bool>,
+    energy: Vec<f64>,
+}
+
+impl Pattern {
+    fn new(size: usize) -> Self {
+        let area = size * size;
+        // offsets must not wrap around to the same pixel twice
+        let radius = RADIUS.min((size - 1) / 2);
+        let diameter = 2 * radius + 1;
+        let mut kernel = vec![0.0; diameter * diameter];
+        for ky in 0..diameter {
+            for kx in 0..diameter {
+                let y = ky as f64 - radius as f64;
+                let x = kx as f64 - radius as f64;
+                kernel[ky * diameter + kx] = (-(x * x + y * y) / (2.0 * SIGMA * SIGMA)).exp();
+            }
+        }
+
+        Self {
+            size,
+            radius,
+            kernel,
+            bits: vec![false; area],
+            energy: vec![0.0; area],
+        }
+    }
+
+    fn toggle(&mut self, index: usize) {
+        let sign = if self.bits[index] { -1.0 } else { 1.0 };
+        self.bits[index] = !self.bits[index];
+
+        let size = self.size;
+        let diameter = 2 * self.radius + 1;
+        let (px, py) = (index % size, index / size);
+        for ky in 0..diameter {
+            let y = (py + size + ky - self.radius) % size;
+            for kx in 0..diameter {
+                let x = (px + size + kx - self.radius) % size;
+                self.energy[y * size + x] += sign * self.kernel[ky * diameter + kx];
+            }
+        }
+    }
+
+    /// Returns the index of the pixel with the highest or lowest energy among
+    /// all pixels with the given value. Ties go to the first pixel.
+    fn find(&self, value: bool, highest: bool) -> Option<usize> {
+        let mut best: Option<usize> = None;
+        for (i, (&bit, &energy)) in self.bits.iter().zip(&self.energy).enumerate() {
+            if bit != value {
+                continue;
+            }
+            let better = match best {
+                None => true,
+                Some(b) if highest => energy > self.energy[b],
+                Some(b) => energy < self.energy[b],
+            };
+            if better {
+                best = Some(i);
+            }
+        }
+        best
+    }
+    /// The 1 with the most 1s around it.
+    fn tightest_cluster(&self) -> Option<usize> {
+        self.find(true, true)
+    }
+    /// The 0 with the fewest 1s around it.
+    fn largest_void(&self) -> Option<usize> {
+        self.find(false, false)
+    }
+}
+
+fn void_and_cluster(size: usize) -> Image<f32> {
+    let area = size * size;
+    let mut pattern = Pattern::new(size);
+
+    // initial binary pattern: random 1s, which are then evenly distributed by
+    // moving the tightest cluster into the largest void
+    let ones = (area / 10).max(1);
+    let mut random = Random::new(SEED);
+    let mut placed = 0;
+    while placed < ones {
+        let index = (random.next_u64() % area as u64) as usize;
+        if !pattern.bits[index] {
+            pattern.toggle(index);
+            placed += 1;
+        }
+    }
+    for _ in 0..area {
+        let cluster = pattern.tightest_cluster().unwrap();
+        pattern.toggle(cluster);
+        let void = pattern.largest_void().unwrap();
+        pattern.toggle(void);
+        if void == cluster {
+            break;
+        }
+    }
+
+    let mut rank = vec![0; area];
+
+    // phase 1: remove the 1s of the prototype, tightest clusters first
+    let mut removed = pattern.clone();
+    for r in (0..ones).rev() {
+        let cluster = removed.tightest_cluster().unwrap();
+        removed.toggle(cluster);
+        rank[cluster] = r;
+    }
+
+    // phases 2 and 3: fill the largest voids of the prototype
+    for r in ones..area {
+        let void = pattern.largest_void().unwrap();
+        pattern.toggle(void);
+        rank[void] = r;
+    }
+
+    let mut result = Image::from_const(Size::new(size, size), 0.0);
+    for (t, r) in result.data_mut().iter_mut().zip(rank) {
+        *t = r as f32 / area as f32;
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use image_core::NDimImage;
+
+    use super::*;
+    use crate::dither::{ordered_dither_with_map, ChannelQuantization};
+    use test_util::{data::read_flower, snap::ImageSnapshot};
+
+    #[test]
+    fn blue_noise_map_ranks() {
+        for size in [1, 2, 5, 16] {
+            let map = void_and_cluster(size);
+            let area = size * size;
+
+            let mut ranks: Vec<usize> = map
+                .data()
+                .iter()
+                .map(|t| (t * area as f32).round() as usize)
+                .collect();
+            ranks.sort_unstable();
+            assert_eq!(ranks, (0..area).collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn blue_noise_map_is_deterministic_and_cached() {
+        let map = blue_noise_map(16);
+        assert!(map.data() == void_and_cluster(16).data());
+        assert!(Arc::ptr_eq(&map, &blue_noise_map(16)));
+        assert_eq!(blue_noise_map(8).size(), Size::new(8, 8));
+    }
+
+    #[test]
+    fn blue_noise_map_is_even() {
+        // every threshold level should spread its 1s over the whole map
+        let size = 32;
+        let map = blue_noise_map(size);
+        for level in [0.1, 0.25, 0.5] {
+            let block = size / 4;
+            let expected = (block * block) as f32 * level;
+            for by in 0..4 {
+                for bx in 0..4 {
+                    let count = (0..block)
+                        .flat_map(|y| (0..block).map(move |x| (x, y)))
+                        .filter(|(x, y)| {
+                            map.data()[(by * block + y) * size + bx * block + x] < level
+                        })
+                        .count() as f32;
+                    assert!(
+                        (count - expected).abs() <= expected * 0.5 + 1.0,
+                        "level {level}: {count} vs {expected}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn ordered_dither_blue_noise() {
+        let map = blue_noise_map(32);
+
+        let mut img: NDimImage = read_flower().into();
+        ordered_dither_with_map(img.view_mut(), map.view(), ChannelQuantization::new(4));
+        img.snapshot("ordered_4_blue_noise_32");
+
+        let mut img: NDimImage = read_flower().into();
+        ordered_dither_with_map(img.view_mut(), map.view(), ChannelQuantization::new(2));
+        img.snapshot("ordered_2_blue_noise_32");
+    }
+}
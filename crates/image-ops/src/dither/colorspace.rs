@@ -0,0 +1,369 @@
+use glam::{Vec2, Vec3, Vec3A, Vec4};
+use rstar::Point;
+
+use crate::gamma::srgb_to_linear;
+
+/// A color space in which the colors of a palette are compared.
+pub trait ColorSpace<P> {
+    type Coord: Point<Scalar = f32>;
+
+    fn get_coordinate(&self, color: P) -> Self::Coord;
+
+    /// Returns the squared distance between two coordinates.
+    ///
+    /// The default is the squared Euclidean distance. Color spaces that
+    /// override this must return `false` from [`Self::is_euclidean`].
+    fn distance_2(&self, a: &Self::Coord, b: &Self::Coord) -> f32 {
+        euclidean_2(a, b)
+    }
+    /// Whether [`Self::distance_2`] is the squared Euclidean distance.
+    ///
+    /// Only Euclidean color spaces can use a spatial index to look up the
+    /// nearest color of large palettes.
+    fn is_euclidean(&self) -> bool {
+        true
+    }
+}
+
+fn euclidean_2<G: Point<Scalar = f32>>(a: &G, b: &G) -> f32 {
+    (0..G::DIMENSIONS)
+        .map(|i| a.nth(i) - b.nth(i))
+        .map(|d| d * d)
+        .sum()
+}
+
+/// Compares colors by their sRGB values.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct RGB;
+impl ColorSpace<f32> for RGB {
+    type Coord = [f32; 1];
+
+    fn get_coordinate(&self, color: f32) -> Self::Coord {
+        [color]
+    }
+}
+impl<const N: usize> ColorSpace<[f32; N]> for RGB {
+    type Coord = [f32; N];
+
+    fn get_coordinate(&self, color: [f32; N]) -> Self::Coord {
+        color
+    }
+}
+
+macro_rules! impl_srgb_into {
+    ($t:ty, $n:literal) => {
+        impl ColorSpace<$t> for RGB {
+            type Coord = [f32; $n];
+
+            fn get_coordinate(&self, color: $t) -> Self::Coord {
+                color.into()
+            }
+        }
+    };
+}
+impl_srgb_into!(Vec2, 2);
+impl_srgb_into!(Vec3, 3);
+impl_srgb_into!(Vec3A, 3);
+impl_srgb_into!(Vec4, 4);
+
+/// Compares colors by their linear RGB values.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct LinearRGB;
+
+/// Compares colors in the OKLab color space, in which Euclidean distances
+/// approximate perceived color differences.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct OkLab;
+
+/// The formula used to compute the difference of two CIELAB colors.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
+pub enum DeltaE {
+    /// The Euclidean distance (CIE76).
+    #[default]
+    Cie76,
+    /// The CIEDE2000 color difference. It is more accurate than CIE76 for
+    /// saturated and dark colors, but nearest-color lookups are slower for
+    /// large palettes.
+    Cie2000,
+}
+
+/// Compares colors in the CIELAB color space with a D65 white point.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
+pub struct CieLab {
+    pub delta_e: DeltaE,
+}
+
+/// A color space whose coordinates are computed from RGB colors.
+///
+/// Grayscale colors are treated as RGB colors with equal channels and only
+/// use the first coordinate. Alpha is scaled by `ALPHA_SCALE` and appended as
+/// the last coordinate.
+trait RgbTransform {
+    const ALPHA_SCALE: f32;
+
+    fn transform(&self, rgb: [f32; 3]) -> [f32; 3];
+
+    fn color_distance_2(&self, a: [f32; 3], b: [f32; 3]) -> f32 {
+        euclidean_2(&a, &b)
+    }
+    fn is_color_euclidean(&self) -> bool {
+        true
+    }
+}
+
+impl RgbTransform for LinearRGB {
+    const ALPHA_SCALE: f32 = 1.0;
+
+    fn transform(&self, rgb: [f32; 3]) -> [f32; 3] {
+        rgb.map(srgb_to_linear)
+    }
+}
+
+impl RgbTransform for OkLab {
+    const ALPHA_SCALE: f32 = 1.0;
+
+    fn transform(&self, rgb: [f32; 3]) -> [f32; 3] {
+        let [r, g, b] = rgb.map(srgb_to_linear);
+
+        let l = (0.41222147 * r + 0.53633254 * g + 0.051445995 * b).cbrt();
+        let m = (0.2119035 * r + 0.6806995 * g + 0.10739696 * b).cbrt();
+        let s = (0.08830246 * r + 0.28171884 * g + 0.6299787 * b).cbrt();
+
+        [
+            0.21045426 * l + 0.7936178 * m - 0.004072047 * s,
+            1.9779985 * l - 2.4285922 * m + 0.4505937 * s,
+            0.025904037 * l + 0.78277177 * m - 0.80867577 * s,
+        ]
+    }
+}
+
+impl RgbTransform for CieLab {
+    // L ranges from 0 to 100
+    const ALPHA_SCALE: f32 = 100.0;
+
+    fn transform(&self, rgb: [f32; 3]) -> [f32; 3] {
+        let [r, g, b] = rgb.map(srgb_to_linear);
+
+        // The white point is the XYZ color of RGB white, so grays have a=b=0.
+        const WHITE: [f32; 3] = [0.95047, 1.0, 1.08883];
+        let x = (0.4124564 * r + 0.3575761 * g + 0.1804375 * b) / WHITE[0];
+        let y = 0.2126729 * r + 0.7151522 * g + 0.0721750 * b;
+        let z = (0.0193339 * r + 0.119192 * g + 0.9503041 * b) / WHITE[2];
+
+        fn f(t: f32) -> f32 {
+            const DELTA: f32 = 6.0 / 29.0;
+            if t > DELTA * DELTA * DELTA {
+                t.cbrt()
+            } else {
+                t / (3.0 * DELTA * DELTA) + 4.0 / 29.0
+            }
+        }
+        let (fx, fy, fz) = (f(x), f(y), f(z));
+
+        [116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz)]
+    }
+
+    fn color_distance_2(&self, a: [f32; 3], b: [f32; 3]) -> f32 {
+        match self.delta_e {
+            DeltaE::Cie76 => euclidean_2(&a, &b),
+            DeltaE::Cie2000 => ciede2000_2(a, b),
+        }
+    }
+    fn is_color_euclidean(&self) -> bool {
+        self.delta_e == DeltaE::Cie76
+    }
+}
+
+macro_rules! impl_rgb_transform {
+    ($space:ty) => {
+        impl ColorSpace<f32> for $space {
+            type Coord = [f32; 1];
+
+            fn get_coordinate(&self, color: f32) -> Self::Coord {
+                [self.transform([color; 3])[0]]
+            }
+            fn distance_2(&self, a: &Self::Coord, b: &Self::Coord) -> f32 {
+                self.color_distance_2([a[0], 0.0, 0.0], [b[0], 0.0, 0.0])
+            }
+            fn is_euclidean(&self) -> bool {
+                self.is_color_euclidean()
+            }
+        }
+        impl ColorSpace<Vec3> for $space {
+            type Coord = [f32; 3];
+
+            fn get_coordinate(&self, color: Vec3) -> Self::Coord {
+                self.transform(color.into())
+            }
+            fn distance_2(&self, a: &Self::Coord, b: &Self::Coord) -> f32 {
+                self.color_distance_2(*a, *b)
+            }
+            fn is_euclidean(&self) -> bool {
+                self.is_color_euclidean()
+            }
+        }
+        impl ColorSpace<Vec3A> for $space {
+            type Coord = [f32; 3];
+
+            fn get_coordinate(&self, color: Vec3A) -> Self::Coord {
+                self.transform(color.into())
+            }
+            fn distance_2(&self, a: &Self::Coord, b: &Self::Coord) -> f32 {
+                self.color_distance_2(*a, *b)
+            }
+            fn is_euclidean(&self) -> bool {
+                self.is_color_euclidean()
+            }
+        }
+        impl ColorSpace<Vec4> for $space {
+            type Coord = [f32; 4];
+
+            fn get_coordinate(&self, color: Vec4) -> Self::Coord {
+                let [x, y, z] = self.transform(color.truncate().into());
+                [x, y, z, color.w * <$space>::ALPHA_SCALE]
+            }
+            fn distance_2(&self, a: &Self::Coord, b: &Self::Coord) -> f32 {
+                let alpha = a[3] - b[3];
+                self.color_distance_2([a[0], a[1], a[2]], [b[0], b[1], b[2]]) + alpha * alpha
+            }
+            fn is_euclidean(&self) -> bool {
+                self.is_color_euclidean()
+            }
+        }
+    };
+}
+impl_rgb_transform!(LinearRGB);
+impl_rgb_transform!(OkLab);
+impl_rgb_transform!(CieLab);
+
+/// Returns the squared CIEDE2000 color difference of two CIELAB colors.
+fn ciede2000_2(lab1: [f32; 3], lab2: [f32; 3]) -> f32 {
+    let [l1, a1, b1] = lab1;
+    let [l2, a2, b2] = lab2;
+    const POW25_7: f32 = 6103515625.0;
+
+    let c_mean = (a1.hypot(b1) + a2.hypot(b2)) * 0.5;
+    let c_mean_7 = c_mean.powi(7);
+    let g = 0.5 * (1.0 - (c_mean_7 / (c_mean_7 + POW25_7)).sqrt());
+    let a1 = a1 * (1.0 + g);
+    let a2 = a2 * (1.0 + g);
+
+    let c1 = a1.hypot(b1);
+    let c2 = a2.hypot(b2);
+    let hue = |b: f32, a: f32| {
+        if a == 0.0 && b == 0.0 {
+            0.0
+        } else {
+            b.atan2(a).to_degrees().rem_euclid(360.0)
+        }
+    };
+    let h1 = hue(b1, a1);
+    let h2 = hue(b2, a2);
+
+    let delta_l = l2 - l1;
+    let delta_c = c2 - c1;
+    let chroma_product = c1 * c2;
+    let delta_h = if chroma_product == 0.0 {
+        0.0
+    } else {
+        let d = h2 - h1;
+        if d > 180.0 {
+            d - 360.0
+        } else if d < -180.0 {
+            d + 360.0
+        } else {
+            d
+        }
+    };
+    let delta_h = 2.0 * chroma_product.sqrt() * (delta_h.to_radians() * 0.5).sin();
+
+    let l_mean = (l1 + l2) * 0.5;
+    let c_mean = (c1 + c2) * 0.5;
+    let h_mean = if chroma_product == 0.0 {
+        h1 + h2
+    } else if (h1 - h2).abs() <= 180.0 {
+        (h1 + h2) * 0.5
+    } else if h1 + h2 < 360.0 {
+        (h1 + h2 + 360.0) * 0.5
+    } else {
+        (h1 + h2 - 360.0) * 0.5
+    };
+
+    let t = 1.0 - 0.17 * (h_mean - 30.0).to_radians().cos()
+        + 0.24 * (2.0 * h_mean).to_radians().cos()
+        + 0.32 * (3.0 * h_mean + 6.0).to_radians().cos()
+        - 0.20 * (4.0 * h_mean - 63.0).to_radians().cos();
+    let delta_theta = 30.0 * (-((h_mean - 275.0) / 25.0).powi(2)).exp();
+    let c_mean_7 = c_mean.powi(7);
+    let r_c = 2.0 * (c_mean_7 / (c_mean_7 + POW25_7)).sqrt();
+    let l_50 = (l_mean - 50.0).powi(2);
+    let s_l = 1.0 + 0.015 * l_50 / (20.0 + l_50).sqrt();
+    let s_c = 1.0 + 0.045 * c_mean;
+    let s_h = 1.0 + 0.015 * c_mean * t;
+    let r_t = -(2.0 * delta_theta).to_radians().sin() * r_c;
+
+    let l = delta_l / s_l;
+    let c = delta_c / s_c;
+    let h = delta_h / s_h;
+    l * l + c * c + h * h + r_t * c * h
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::Vec3A;
+
+    use super::*;
+
+    #[test]
+    fn ciede2000() {
+        // test data from Sharma, Wu and Dalal (2005)
+        let pairs = [
+            ([50.0, 2.6772, -79.7751], [50.0, 0.0, -82.7485], 2.0425),
+            ([50.0, 0.0, 0.0], [50.0, -1.0, 2.0], 2.3669),
+            ([50.0, 2.5, 0.0], [73.0, 25.0, -18.0], 27.1492),
+            ([50.0, 2.5, 0.0], [50.0, 3.2592, 0.335], 1.0),
+            (
+                [60.2574, -34.0099, 36.2677],
+                [60.4626, -34.1751, 39.4387],
+                1.2644,
+            ),
+            (
+                [22.7233, 20.0904, -46.694],
+                [23.0331, 14.973, -42.5619],
+                2.0373,
+            ),
+        ];
+        for (a, b, expected) in pairs {
+            let actual = ciede2000_2(a, b).sqrt();
+            assert!(
+                (actual - expected).abs() < 1e-3,
+                "{a:?} {b:?}: expected {expected}, got {actual}"
+            );
+            assert!((ciede2000_2(b, a).sqrt() - expected).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn lab_of_known_colors() {
+        let close =
+            |a: [f32; 3], b: [f32; 3], eps: f32| a.iter().zip(b).all(|(a, b)| (a - b).abs() < eps);
+
+        let white = Vec3A::ONE;
+        let red = Vec3A::new(1.0, 0.0, 0.0);
+        let gray = Vec3A::splat(0.5);
+
+        let lab = CieLab::default();
+        assert!(close(lab.get_coordinate(white), [100.0, 0.0, 0.0], 1e-3));
+        assert!(close(lab.get_coordinate(red), [53.24, 80.09, 67.20], 1e-2));
+        let [_, a, b] = lab.get_coordinate(gray);
+        assert!(a.abs() < 1e-3 && b.abs() < 1e-3);
+
+        assert!(close(OkLab.get_coordinate(white), [1.0, 0.0, 0.0], 1e-3));
+        assert!(close(
+            OkLab.get_coordinate(red),
+            [0.628, 0.2249, 0.1258],
+            1e-3
+        ));
+        assert_eq!(OkLab.get_coordinate(0.5)[0], OkLab.get_coordinate(gray)[0]);
+    }
+}
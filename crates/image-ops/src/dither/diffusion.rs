@@ -1,46 +1,81 @@
-use image_core::Image;
+use image_core::{Image, ImageView, ImageViewMut};
 
 use crate::util::from_const;
 
-use super::{Diffuser, DiffusionAlgorithm, Pixel, Quantizer};
+use super::{
+    algorithm::{MAX_KERNEL_RADIUS, MAX_KERNEL_ROWS_BELOW},
+    Diffuser, DiffusionAlgorithm, Luminance, Pixel, Quantizer,
+};
+
+/// The order in which the pixels of a row are processed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum ScanOrder {
+    /// Every row is processed from left to right.
+    #[default]
+    Raster,
+    /// Rows are processed alternating from left to right and right to left.
+    /// The diffusion kernel is mirrored for right-to-left rows. This reduces
+    /// directional artifacts.
+    Serpentine,
+}
+
+impl ScanOrder {
+    /// Returns the x coordinate of the `i`-th pixel in the given row and the
+    /// horizontal direction of the kernel.
+    #[inline(always)]
+    fn get_x(self, y: usize, i: usize, width: usize) -> (usize, isize) {
+        match self {
+            ScanOrder::Serpentine if y % 2 == 1 => (width - 1 - i, -1),
+            _ => (i, 1),
+        }
+    }
+}
 
-pub fn error_diffusion_dither<P: Pixel>(
-    src: &mut Image<P>,
+pub fn error_diffusion_dither<P: Pixel + Luminance>(
+    mut src: ImageViewMut<P>,
     algorithm: impl DiffusionAlgorithm,
     quant: &impl Quantizer<P, P>,
+    scan: ScanOrder,
 ) {
     let w = src.width();
     let h = src.height();
-    let data = src.data_mut();
 
     let mut error_rows = ErrorRows::<P>::new(w);
 
     for y in 0..h {
         error_rows.rotate();
 
-        for x in 0..w {
-            let index = y * w + x;
+        let row = src.row_mut(y);
+        for i in 0..w {
+            let (x, direction) = scan.get_x(y, i, w);
+            let pixel = &mut row[x];
             let error_x = x + ERROR_ROW_OFFSET;
 
-            let color = quant.combine_error(data[index], error_rows.0[error_x]);
+            let color = quant.combine_error(*pixel, error_rows.0[error_x]);
             let nearest = quant.get_nearest_color(color);
             let error = quant.get_error(color, nearest);
 
-            data[index] = nearest;
-
-            algorithm.define_weights(StandardDiffuser {
-                rows: [&mut *error_rows.0, &mut *error_rows.1, &mut *error_rows.2],
-                x: error_x,
-                error,
-            });
+            let luminance = pixel.luminance();
+            *pixel = nearest;
+
+            algorithm.define_weights_for(
+                StandardDiffuser {
+                    rows: [&mut *error_rows.0, &mut *error_rows.1, &mut *error_rows.2],
+                    x: error_x,
+                    direction,
+                    error,
+                },
+                luminance,
+            );
         }
     }
 }
 
-pub fn error_diffusion_dither_map<P: Pixel, N>(
-    src: &Image<P>,
+pub fn error_diffusion_dither_map<P: Pixel + Luminance, N>(
+    src: ImageView<P>,
     algorithm: impl DiffusionAlgorithm,
     quant: &impl Quantizer<P, N>,
+    scan: ScanOrder,
     out: Option<Image<N>>,
 ) -> Image<N>
 where
@@ -51,35 +86,40 @@ where
 
     let w = src.width();
     let h = src.height();
-    let data = src.data();
 
     let mut error_rows = ErrorRows::<P>::new(w);
 
     for y in 0..h {
         error_rows.rotate();
 
-        for x in 0..w {
+        for i in 0..w {
+            let (x, direction) = scan.get_x(y, i, w);
             let index = y * w + x;
             let error_x = x + ERROR_ROW_OFFSET;
 
-            let color = quant.combine_error(data[index], error_rows.0[error_x]);
+            let pixel = src.row(y)[x];
+            let color = quant.combine_error(pixel, error_rows.0[error_x]);
             let nearest = quant.get_nearest_color(color);
             let error = quant.get_error(color, nearest.clone());
 
             dest_data[index] = nearest;
 
-            algorithm.define_weights(StandardDiffuser {
-                rows: [&mut *error_rows.0, &mut *error_rows.1, &mut *error_rows.2],
-                x: error_x,
-                error,
-            });
+            algorithm.define_weights_for(
+                StandardDiffuser {
+                    rows: [&mut *error_rows.0, &mut *error_rows.1, &mut *error_rows.2],
+                    x: error_x,
+                    direction,
+                    error,
+                },
+                pixel.luminance(),
+            );
         }
     }
 
     dest
 }
 
-const ERROR_ROW_OFFSET: usize = 2;
+const ERROR_ROW_OFFSET: usize = MAX_KERNEL_RADIUS;
 
 struct ErrorRows<P>(Box<[P]>, Box<[P]>, Box<[P]>);
 
@@ -102,21 +142,25 @@ impl<P: Clone + Default> ErrorRows<P> {
 struct StandardDiffuser<'a, P: Pixel> {
     rows: [&'a mut [P]; 3],
     x: usize,
+    /// 1 for left-to-right rows and -1 for right-to-left rows.
+    direction: isize,
     error: P,
 }
 impl<'a, P: Pixel> Diffuser for StandardDiffuser<'a, P> {
     #[inline(always)]
     fn assign_weight(&mut self, y: usize, x: isize, weight: f32) {
-        assert!(y < 3);
+        assert!(y <= MAX_KERNEL_ROWS_BELOW);
         assert!(-(ERROR_ROW_OFFSET as isize) <= x && x <= ERROR_ROW_OFFSET as isize);
 
-        let x = (self.x as isize + x) as usize;
+        let x = (self.x as isize + x * self.direction) as usize;
         self.rows[y][x] += self.error * weight;
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use image_core::Size;
+
     use super::{super::*, *};
     use test_util::{
         data::{read_flower, read_flower_palette},
@@ -126,7 +170,12 @@ mod tests {
     #[test]
     fn error_diffusion() {
         let mut original = read_flower();
-        error_diffusion_dither(&mut original, FloydSteinberg, &ChannelQuantization::new(4));
+        error_diffusion_dither(
+            original.view_mut(),
+            FloydSteinberg,
+            &ChannelQuantization::new(4),
+            ScanOrder::Raster,
+        );
         original.snapshot("error_diffusion_fs_4");
     }
     #[test]
@@ -134,38 +183,48 @@ mod tests {
         let original = read_flower();
 
         error_diffusion_dither_map(
-            &original,
+            original.view(),
             FloydSteinberg,
             &ChannelQuantization::new(2),
+            ScanOrder::Raster,
             None,
         )
         .snapshot("error_diffusion_map_fs_2");
 
         error_diffusion_dither_map(
-            &original,
+            original.view(),
             FloydSteinberg,
             &ChannelQuantization::new(4),
+            ScanOrder::Raster,
             None,
         )
         .snapshot("error_diffusion_map_fs_4");
 
         error_diffusion_dither_map(
-            &original,
+            original.view(),
             JarvisJudiceNinke,
             &ChannelQuantization::new(4),
+            ScanOrder::Raster,
             None,
         )
         .snapshot("error_diffusion_map_jjn_4");
 
         error_diffusion_dither_map(
-            &original,
+            original.view(),
             FloydSteinberg,
             &ChannelQuantization::new(16),
+            ScanOrder::Raster,
             None,
         )
         .snapshot("error_diffusion_map_flower_fs_16");
-        error_diffusion_dither_map(&original, Atkinson, &ChannelQuantization::new(16), None)
-            .snapshot("error_diffusion_map_atk_16");
+        error_diffusion_dither_map(
+            original.view(),
+            Atkinson,
+            &ChannelQuantization::new(16),
+            ScanOrder::Raster,
+            None,
+        )
+        .snapshot("error_diffusion_map_atk_16");
     }
 
     #[test]
@@ -175,7 +234,137 @@ mod tests {
 
         let palette = ColorPalette::new(RGB, palette_img.row(0).iter().copied(), BoundError);
 
-        error_diffusion_dither_map(&img, FloydSteinberg, &palette, None)
-            .snapshot("error_diffusion_palette_fs");
+        error_diffusion_dither_map(
+            img.view(),
+            FloydSteinberg,
+            &palette,
+            ScanOrder::Raster,
+            None,
+        )
+        .snapshot("error_diffusion_palette_fs");
+    }
+
+    #[test]
+    fn error_diffusion_serpentine() {
+        let original = read_flower();
+
+        error_diffusion_dither_map(
+            original.view(),
+            FloydSteinberg,
+            &ChannelQuantization::new(2),
+            ScanOrder::Serpentine,
+            None,
+        )
+        .snapshot("error_diffusion_map_fs_2_serpentine");
+
+        // both versions scan the same way
+        let mut img = original.clone();
+        error_diffusion_dither(
+            img.view_mut(),
+            Ostromoukhov,
+            &ChannelQuantization::new(2),
+            ScanOrder::Serpentine,
+        );
+        img.snapshot("error_diffusion_ostromoukhov_2_serpentine");
+        let map = error_diffusion_dither_map(
+            original.view(),
+            Ostromoukhov,
+            &ChannelQuantization::new(2),
+            ScanOrder::Serpentine,
+            None,
+        );
+        assert!(map.data() == img.data());
+    }
+
+    #[test]
+    fn error_diffusion_kernels() {
+        let original = read_flower();
+
+        error_diffusion_dither_map(
+            original.view(),
+            Fan,
+            &ChannelQuantization::new(2),
+            ScanOrder::Raster,
+            None,
+        )
+        .snapshot("error_diffusion_map_fan_2");
+        error_diffusion_dither_map(
+            original.view(),
+            ShiauFan,
+            &ChannelQuantization::new(2),
+            ScanOrder::Raster,
+            None,
+        )
+        .snapshot("error_diffusion_map_shiau_fan_2");
+        error_diffusion_dither_map(
+            original.view(),
+            ShiauFan2,
+            &ChannelQuantization::new(2),
+            ScanOrder::Raster,
+            None,
+        )
+        .snapshot("error_diffusion_map_shiau_fan_2_2");
+    }
+
+    #[test]
+    fn error_diffusion_custom_kernel() {
+        let original = read_flower();
+
+        // the same weights as Floyd-Steinberg
+        let weights = Image::from_fn(Size::new(3, 2), |x, y| {
+            [[0.0, 0.0, 7.0], [3.0, 5.0, 1.0]][y][x] / 16.0
+        });
+        let kernel = DiffusionKernel::new(weights.view(), 1).unwrap();
+        for scan in [ScanOrder::Raster, ScanOrder::Serpentine] {
+            let expected = error_diffusion_dither_map(
+                original.view(),
+                FloydSteinberg,
+                &ChannelQuantization::new(4),
+                scan,
+                None,
+            );
+            let actual = error_diffusion_dither_map(
+                original.view(),
+                kernel.clone(),
+                &ChannelQuantization::new(4),
+                scan,
+                None,
+            );
+            assert!(expected.data() == actual.data());
+        }
+
+        assert_eq!(
+            DiffusionKernel::new(weights.view(), 3),
+            Err(DitherError::InvalidKernelOrigin {
+                origin: 3,
+                width: 3
+            })
+        );
+        assert_eq!(
+            DiffusionKernel::new(weights.view(), 2),
+            Err(DitherError::InvalidKernelWeight {
+                x: 2,
+                y: 0,
+                weight: 7.0 / 16.0
+            })
+        );
+        let wide = Image::from_const(Size::new(5, 1), 0.0);
+        assert_eq!(
+            DiffusionKernel::new(wide.view(), 0),
+            Err(DitherError::KernelTooLarge {
+                width: 5,
+                height: 1,
+                origin: 0
+            })
+        );
+        let tall = Image::from_const(Size::new(1, 4), 0.0);
+        assert_eq!(
+            DiffusionKernel::new(tall.view(), 0),
+            Err(DitherError::KernelTooLarge {
+                width: 1,
+                height: 4,
+                origin: 0
+            })
+        );
     }
 }
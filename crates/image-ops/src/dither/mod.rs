@@ -1,4 +1,6 @@
 mod algorithm;
+mod blue_noise;
+mod colorspace;
 mod diffusion;
 mod ordered;
 mod quant;
@@ -6,8 +8,48 @@ mod riemersma;
 mod util;
 
 pub use algorithm::*;
+pub use blue_noise::blue_noise_map;
+pub use colorspace::*;
 pub use diffusion::*;
-pub use ordered::ordered_dither;
+pub use ordered::{
+    ordered_dither, ordered_dither_with_map, palette_ordered_dither,
+    palette_ordered_dither_with_map, try_ordered_dither, try_ordered_dither_with_map,
+    try_palette_ordered_dither, try_palette_ordered_dither_with_map,
+};
 pub use quant::*;
 pub use riemersma::*;
-pub use util::Pixel;
+pub use util::{Luminance, Pixel};
+
+/// Invalid parameters of a quantization or dithering operation.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DitherError {
+    /// Uniform quantization needs at least 2 colors per channel.
+    TooFewColorsPerChannel { per_channel: usize },
+    /// A color palette must contain at least one color.
+    EmptyPalette,
+    /// The size of the threshold map of ordered dithering must be a power
+    /// of 2.
+    InvalidMapSize { map_size: usize },
+    /// A threshold map must contain at least one threshold.
+    EmptyThresholdMap,
+    /// The thresholds of a threshold map must be between 0 (inclusive) and 1
+    /// (exclusive).
+    InvalidThreshold { threshold: f32 },
+    /// Riemersma dithering needs a history of at least 2 errors.
+    HistoryTooShort { history_length: usize },
+    /// The decay ratio of Riemersma dithering must be between 0 and 1
+    /// (exclusive).
+    InvalidDecayRatio { decay_ratio: f32 },
+    /// The origin of a diffusion kernel must be one of its columns.
+    InvalidKernelOrigin { origin: usize, width: usize },
+    /// A diffusion kernel can spread the error at most 2 rows down and 3
+    /// pixels to the left and right of the current pixel.
+    KernelTooLarge {
+        width: usize,
+        height: usize,
+        origin: usize,
+    },
+    /// The weights of a diffusion kernel must be finite, and pixels that were
+    /// already processed must have a weight of 0.
+    InvalidKernelWeight { x: usize, y: usize, weight: f32 },
+}
@@ -1,6 +1,7 @@
-use image_core::{Image, NDimImage, Size};
+use image_core::{Image, ImageView, ImageViewMut, NDimViewMut, Size};
+use rayon::prelude::*;
 
-use super::ChannelQuantization;
+use super::{ChannelQuantization, DitherError, Luminance, Pixel, Quantizer};
 
 /// Creates a threshold map for ordered dithering.
 ///
@@ -86,11 +87,66 @@ fn tile_x<P: Copy + Default>(img: &Image<P>, new_width: usize) -> Image<P> {
     result
 }
 
-pub fn ordered_dither(img: &mut NDimImage, n: usize, quant: ChannelQuantization) {
-    assert!(n.is_power_of_two());
+/// Checks that the threshold map isn't empty and that all thresholds are in
+/// the range 0 (inclusive) to 1 (exclusive).
+fn check_threshold_map(map: ImageView<f32>) -> Result<(), DitherError> {
+    if map.is_empty() {
+        return Err(DitherError::EmptyThresholdMap);
+    }
+    for &threshold in map.rows().flatten() {
+        if !(0.0..1.0).contains(&threshold) {
+            return Err(DitherError::InvalidThreshold { threshold });
+        }
+    }
+    Ok(())
+}
+
+/// Applies ordered dithering with a threshold map of size `n`x`n`.
+///
+/// # Panics
+///
+/// Panics if `n` isn't a power of 2. See [`try_ordered_dither`].
+pub fn ordered_dither(img: NDimViewMut, n: usize, quant: ChannelQuantization) {
+    try_ordered_dither(img, n, quant).expect("The map size must be a power of 2.")
+}
+pub fn try_ordered_dither(
+    img: NDimViewMut,
+    n: usize,
+    quant: ChannelQuantization,
+) -> Result<(), DitherError> {
+    if !n.is_power_of_two() {
+        return Err(DitherError::InvalidMapSize { map_size: n });
+    }
+
+    ordered_dither_impl(img, create_threshold_map(n).view(), quant);
+    Ok(())
+}
 
+/// Applies ordered dithering with the given threshold map, which is repeated
+/// across the image.
+///
+/// # Panics
+///
+/// Panics if the map is empty or a threshold isn't in the range 0 (inclusive)
+/// to 1 (exclusive). See [`try_ordered_dither_with_map`].
+pub fn ordered_dither_with_map(img: NDimViewMut, map: ImageView<f32>, quant: ChannelQuantization) {
+    try_ordered_dither_with_map(img, map, quant).expect("Invalid threshold map.")
+}
+pub fn try_ordered_dither_with_map(
+    img: NDimViewMut,
+    map: ImageView<f32>,
+    quant: ChannelQuantization,
+) -> Result<(), DitherError> {
+    check_threshold_map(map)?;
+
+    ordered_dither_impl(img, map, quant);
+    Ok(())
+}
+
+fn ordered_dither_impl(mut img: NDimViewMut, map: ImageView<f32>, quant: ChannelQuantization) {
     if quant.per_channel() == 2 {
-        return binary_ordered_dither(img, n, 0.5);
+        binary_ordered_dither(img, map, 0.5);
+        return;
     }
 
     let f = (quant.per_channel() - 1) as f32;
@@ -99,18 +155,13 @@ pub fn ordered_dither(img: &mut NDimImage, n: usize, quant: ChannelQuantization)
     // This allows us to zip the current threshold row with the current image row, which
     // gets rid of the inner channel loop, which makes the code around 25% faster.
     let threshold_map = tile_x(
-        &stretch_x(&create_threshold_map(n), img.channels()),
+        &stretch_x(&map.into_owned(), img.channels()),
         img.width() * img.channels(),
     );
-    let n_mask = n - 1;
-
-    let shape = img.shape();
-    let data = img.data_mut();
+    let map_height = threshold_map.height();
 
-    for y in 0..shape.height {
-        let threshold_row = threshold_map.row(y & n_mask);
-        let data_row =
-            &mut data[(y * shape.width * shape.channels)..((y + 1) * shape.width * shape.channels)];
+    for (y, data_row) in img.rows_mut().enumerate() {
+        let threshold_row = threshold_map.row(y % map_height);
         assert_eq!(threshold_row.len(), data_row.len());
 
         for (data, threshold) in data_row.iter_mut().zip(threshold_row.iter()) {
@@ -119,28 +170,115 @@ pub fn ordered_dither(img: &mut NDimImage, n: usize, quant: ChannelQuantization)
     }
 }
 
-fn binary_ordered_dither(img: &mut NDimImage, n: usize, bin_threshold: f32) {
-    assert!(n.is_power_of_two());
+/// The maximum number of candidate colors of a pixel in
+/// [`palette_ordered_dither`].
+const MAX_CANDIDATES: usize = 64;
+/// How much of the accumulated error is added to the color when picking the
+/// next candidate.
+const ERROR_MULTIPLIER: f32 = 0.5;
 
+/// Applies ordered dithering with a threshold map of size `n`x`n` using the
+/// colors of any quantizer, e.g. a color palette.
+///
+/// This is Thomas Knoll's pattern dithering. Every pixel gets a list of
+/// candidate colors that mix to its color, sorted by luminance, and the
+/// threshold map picks one of them. Since the result only depends on the
+/// color and position of a pixel, patterns stay in place across the frames
+/// of an animation.
+///
+/// # Panics
+///
+/// Panics if `n` isn't a power of 2. See [`try_palette_ordered_dither`].
+pub fn palette_ordered_dither<P: Pixel + Luminance + Send + Sync>(
+    img: ImageViewMut<P>,
+    n: usize,
+    quant: &(impl Quantizer<P, P> + Sync),
+) {
+    try_palette_ordered_dither(img, n, quant).expect("The map size must be a power of 2.")
+}
+pub fn try_palette_ordered_dither<P: Pixel + Luminance + Send + Sync>(
+    img: ImageViewMut<P>,
+    n: usize,
+    quant: &(impl Quantizer<P, P> + Sync),
+) -> Result<(), DitherError> {
+    if !n.is_power_of_two() {
+        return Err(DitherError::InvalidMapSize { map_size: n });
+    }
+
+    palette_ordered_dither_impl(img, create_threshold_map(n).view(), quant);
+    Ok(())
+}
+
+/// Same as [`palette_ordered_dither`], but with the given threshold map,
+/// which is repeated across the image.
+///
+/// # Panics
+///
+/// Panics if the map is empty or a threshold isn't in the range 0 (inclusive)
+/// to 1 (exclusive). See [`try_palette_ordered_dither_with_map`].
+pub fn palette_ordered_dither_with_map<P: Pixel + Luminance + Send + Sync>(
+    img: ImageViewMut<P>,
+    map: ImageView<f32>,
+    quant: &(impl Quantizer<P, P> + Sync),
+) {
+    try_palette_ordered_dither_with_map(img, map, quant).expect("Invalid threshold map.")
+}
+pub fn try_palette_ordered_dither_with_map<P: Pixel + Luminance + Send + Sync>(
+    img: ImageViewMut<P>,
+    map: ImageView<f32>,
+    quant: &(impl Quantizer<P, P> + Sync),
+) -> Result<(), DitherError> {
+    check_threshold_map(map)?;
+
+    palette_ordered_dither_impl(img, map, quant);
+    Ok(())
+}
+
+fn palette_ordered_dither_impl<P: Pixel + Luminance + Send + Sync>(
+    mut img: ImageViewMut<P>,
+    map: ImageView<f32>,
+    quant: &(impl Quantizer<P, P> + Sync),
+) {
+    let count = map.len().min(MAX_CANDIDATES);
+
+    let rows: Vec<&mut [P]> = img.rows_mut().collect();
+    rows.into_par_iter().enumerate().for_each_init(
+        || Vec::with_capacity(count),
+        |candidates: &mut Vec<P>, (y, row)| {
+            let threshold_row = map.row(y % map.height());
+
+            for (x, pixel) in row.iter_mut().enumerate() {
+                let original = *pixel;
+
+                candidates.clear();
+                let mut error = P::default();
+                for _ in 0..count {
+                    let color = quant.combine_error(original, error * ERROR_MULTIPLIER);
+                    let nearest = quant.get_nearest_color(color);
+                    error += quant.get_error(original, nearest);
+                    candidates.push(nearest);
+                }
+                candidates.sort_by(|a, b| a.luminance().total_cmp(&b.luminance()));
+
+                let threshold = threshold_row[x % map.width()];
+                *pixel = candidates[((threshold * count as f32) as usize).min(count - 1)];
+            }
+        },
+    );
+}
+
+fn binary_ordered_dither(mut img: NDimViewMut, map: ImageView<f32>, bin_threshold: f32) {
     // Same idea as in the regular ordered dither, but we get even more out of it.
     // The inner channel loop prevented effective vectorization.
     // Binary ordered dithering is about 5x faster with this trick.
     let threshold_map = tile_x(
-        &stretch_x(
-            &create_threshold_map(n).map(|f| bin_threshold + 0.5 - f),
-            img.channels(),
-        ),
+        &stretch_x(&map.map(|f| bin_threshold + 0.5 - f), img.channels()),
         img.width() * img.channels(),
     );
-    let n_mask = n - 1;
+    let map_height = threshold_map.height();
 
-    let shape = img.shape();
-    let data = img.data_mut();
-
-    for y in 0..shape.height {
-        let threshold_row = threshold_map.row(y & n_mask);
-        let data_row =
-            &mut data[(y * shape.width * shape.channels)..((y + 1) * shape.width * shape.channels)];
+    for (y, data_row) in img.rows_mut().enumerate() {
+        let threshold_row = threshold_map.row(y % map_height);
         assert_eq!(threshold_row.len(), data_row.len());
 
         for (data, threshold) in data_row.iter_mut().zip(threshold_row.iter()) {
@@ -154,25 +292,99 @@ fn binary_ordered_dither(img: &mut NDimImage, n: usize, bin_threshold: f32) {
 
 #[cfg(test)]
 mod tests {
+    use image_core::{NDimImage, Rect};
+
     use super::*;
-    use test_util::{data::read_flower, snap::ImageSnapshot};
+    use crate::dither::{BoundError, ColorPalette, RGB};
+    use test_util::{
+        data::{read_flower, read_flower_palette},
+        snap::ImageSnapshot,
+    };
 
     #[test]
     fn ordered_dither_channels() {
-        let mut img = read_flower().into();
-        ordered_dither(&mut img, 4, ChannelQuantization::new(4));
+        let mut img: NDimImage = read_flower().into();
+        ordered_dither(img.view_mut(), 4, ChannelQuantization::new(4));
         img.snapshot("ordered_4_4x4");
 
-        let mut img = read_flower().into();
-        ordered_dither(&mut img, 16, ChannelQuantization::new(4));
+        let mut img: NDimImage = read_flower().into();
+        ordered_dither(img.view_mut(), 16, ChannelQuantization::new(4));
         img.snapshot("ordered_4_16x16");
 
-        let mut img = read_flower().into();
-        ordered_dither(&mut img, 64, ChannelQuantization::new(4));
+        let mut img: NDimImage = read_flower().into();
+        ordered_dither(img.view_mut(), 64, ChannelQuantization::new(4));
         img.snapshot("ordered_4_64x64");
 
-        let mut img = read_flower().into();
-        ordered_dither(&mut img, 4, ChannelQuantization::new(2));
+        let mut img: NDimImage = read_flower().into();
+        ordered_dither(img.view_mut(), 4, ChannelQuantization::new(2));
         img.snapshot("ordered_2_4x4");
     }
+
+    #[test]
+    fn ordered_dither_palette() {
+        let mut img = read_flower();
+        let palette_img = read_flower_palette();
+        let palette = ColorPalette::new(RGB, palette_img.row(0).iter().copied(), BoundError);
+
+        palette_ordered_dither(img.view_mut(), 4, &palette);
+        img.snapshot("ordered_palette_4x4");
+    }
+    #[test]
+    fn ordered_dither_palette_is_stable() {
+        let palette_img = read_flower_palette();
+        let palette = ColorPalette::new(RGB, palette_img.row(0).iter().copied(), BoundError);
+
+        // shifting the image by a multiple of the map size shifts the result
+        let original = read_flower();
+        let mut img = original.crop(Rect::new(0, 0, 100, 100));
+        palette_ordered_dither(img.view_mut(), 8, &palette);
+        let mut shifted = original.crop(Rect::new(8, 16, 92, 84));
+        palette_ordered_dither(shifted.view_mut(), 8, &palette);
+        assert!(shifted.data() == img.crop(Rect::new(8, 16, 92, 84)).data());
+
+        assert!(img.data().iter().all(|c| palette_img.data().contains(c)));
+        assert_eq!(
+            try_palette_ordered_dither(img.view_mut(), 6, &palette),
+            Err(DitherError::InvalidMapSize { map_size: 6 })
+        );
+    }
+
+    #[test]
+    fn ordered_dither_custom_map() {
+        // a Bayer map passed explicitly gives the same result
+        let mut expected: NDimImage = read_flower().into();
+        ordered_dither(expected.view_mut(), 8, ChannelQuantization::new(4));
+        let mut img: NDimImage = read_flower().into();
+        let map = create_threshold_map(8);
+        ordered_dither_with_map(img.view_mut(), map.view(), ChannelQuantization::new(4));
+        assert!(img.data() == expected.data());
+
+        // maps don't have to be square or a power of 2
+        let map = Image::from_fn(Size::new(3, 2), |x, y| (y * 3 + x) as f32 / 6.0);
+        let mut img = read_flower();
+        palette_ordered_dither_with_map(img.view_mut(), map.view(), &ChannelQuantization::new(3));
+        let mut shifted = read_flower().crop(Rect::new(3, 4, 100, 100));
+        palette_ordered_dither_with_map(
+            shifted.view_mut(),
+            map.view(),
+            &ChannelQuantization::new(3),
+        );
+        assert!(shifted.data() == img.crop(Rect::new(3, 4, 100, 100)).data());
+
+        let empty = Image::<f32>::from_const(Size::new(0, 0), 0.0);
+        let mut ndim: NDimImage = read_flower().into();
+        assert_eq!(
+            try_ordered_dither_with_map(ndim.view_mut(), empty.view(), ChannelQuantization::new(4)),
+            Err(DitherError::EmptyThresholdMap)
+        );
+        let invalid = Image::from_const(Size::new(2, 2), 1.0);
+        assert_eq!(
+            try_palette_ordered_dither_with_map(
+                img.view_mut(),
+                invalid.view(),
+                &ChannelQuantization::new(4)
+            ),
+            Err(DitherError::InvalidThreshold { threshold: 1.0 })
+        );
+    }
 }
@@ -1,8 +1,8 @@
 use glam::{Vec2, Vec3, Vec3A, Vec4};
-use image_core::{Image, NDimImage};
+use image_core::{ImageViewMut, NDimViewMut, Sample};
 use rstar::{primitives::GeomWithData, Point, RTree};
 
-use super::Pixel;
+use super::{ColorSpace, DitherError, Pixel};
 
 pub trait ErrorCombinator<P> {
     fn combine_error(&self, color: P, error: P) -> P;
@@ -49,15 +49,24 @@ pub struct ChannelQuantization {
 }
 
 impl ChannelQuantization {
+    /// # Panics
+    ///
+    /// Panics if there are fewer than 2 colors per channel. See
+    /// [`Self::try_new`].
     pub fn new(per_channel: usize) -> Self {
-        assert!(per_channel >= 2);
+        Self::try_new(per_channel).expect("There must be at least 2 colors per channel.")
+    }
+    pub fn try_new(per_channel: usize) -> Result<Self, DitherError> {
+        if per_channel < 2 {
+            return Err(DitherError::TooFewColorsPerChannel { per_channel });
+        }
 
         let factor = (per_channel - 1) as f32;
-        Self {
+        Ok(Self {
             per_channel,
             factor,
             factor_inv: 1.0 / factor,
-        }
+        })
     }
 
     pub fn per_channel(&self) -> usize {
@@ -110,53 +119,16 @@ impl_channels_vec!(Vec3);
 impl_channels_vec!(Vec3A);
 impl_channels_vec!(Vec4);
 
-pub trait ColorSpace<P> {
-    type Coord: Point<Scalar = f32>;
-
-    fn get_coordinate(&self, color: P) -> Self::Coord;
-}
-
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
-pub struct RGB;
-impl ColorSpace<f32> for RGB {
-    type Coord = [f32; 1];
-
-    fn get_coordinate(&self, color: f32) -> Self::Coord {
-        [color]
-    }
-}
-impl<const N: usize> ColorSpace<[f32; N]> for RGB {
-    type Coord = [f32; N];
-
-    fn get_coordinate(&self, color: [f32; N]) -> Self::Coord {
-        color
-    }
-}
-
-macro_rules! impl_srgb_into {
-    ($t:ty, $n:literal) => {
-        impl ColorSpace<$t> for RGB {
-            type Coord = [f32; $n];
-
-            fn get_coordinate(&self, color: $t) -> Self::Coord {
-                color.into()
-            }
-        }
-    };
-}
-impl_srgb_into!(Vec2, 2);
-impl_srgb_into!(Vec3, 3);
-impl_srgb_into!(Vec3A, 3);
-impl_srgb_into!(Vec4, 4);
-
 #[derive(Debug, Clone)]
 enum Lookup<G: Point<Scalar = f32>, P> {
     Linear(Vec<GeomWithData<G, P>>),
     Tree(RTree<GeomWithData<G, P>>),
 }
 impl<G: Point<Scalar = f32>, P: Clone> Lookup<G, P> {
-    pub fn new(colors: Vec<GeomWithData<G, P>>) -> Self {
-        if colors.len() < 300 {
+    /// The tree lookup is only correct if `distance_2` of
+    /// [`Self::get_nearest_color`] is the squared Euclidean distance.
+    pub fn new(colors: Vec<GeomWithData<G, P>>, euclidean: bool) -> Self {
+        if colors.len() < 300 || !euclidean {
             // linear lookup is really fast for small palettes
             Self::Linear(colors)
         } else {
@@ -164,20 +136,13 @@ impl<G: Point<Scalar = f32>, P: Clone> Lookup<G, P> {
         }
     }
 
-    fn distance_2(a: &G, b: &G) -> f32 {
-        (0..G::DIMENSIONS)
-            .map(|i| a.nth(i) - b.nth(i))
-            .map(|d| d * d)
-            .sum()
-    }
-
-    pub fn get_nearest_color(&self, color: G) -> P {
+    pub fn get_nearest_color(&self, color: G, distance_2: impl Fn(&G, &G) -> f32) -> P {
         match self {
             Self::Linear(colors) => {
                 let mut nearest = &colors[0];
-                let mut nearest_dist = Self::distance_2(nearest.geom(), &color);
+                let mut nearest_dist = distance_2(nearest.geom(), &color);
                 for c in colors.iter().skip(1) {
-                    let dist = Self::distance_2(c.geom(), &color);
+                    let dist = distance_2(c.geom(), &color);
                     if dist < nearest_dist {
                         nearest = c;
                         nearest_dist = dist;
@@ -201,7 +166,17 @@ pub struct ColorPalette<P: Clone, C: ColorSpace<P>, E> {
 }
 
 impl<P: Copy, C: ColorSpace<P>, E: ErrorCombinator<P>> ColorPalette<P, C, E> {
+    /// # Panics
+    ///
+    /// Panics if there are no colors. See [`Self::try_new`].
     pub fn new(colorspace: C, colors: impl IntoIterator<Item = P>, error: E) -> Self {
+        Self::try_new(colorspace, colors, error).expect("palette must contain at least one color")
+    }
+    pub fn try_new(
+        colorspace: C,
+        colors: impl IntoIterator<Item = P>,
+        error: E,
+    ) -> Result<Self, DitherError> {
         let colors: Vec<GeomWithData<C::Coord, P>> = colors
             .into_iter()
             .map(|color| {
@@ -210,17 +185,16 @@ impl<P: Copy, C: ColorSpace<P>, E: ErrorCombinator<P>> ColorPalette<P, C, E> {
             })
             .collect();
 
-        assert!(
-            !colors.is_empty(),
-            "palette must contain at least one color"
-        );
-        let lookup = Lookup::new(colors);
+        if colors.is_empty() {
+            return Err(DitherError::EmptyPalette);
+        }
+        let lookup = Lookup::new(colors, colorspace.is_euclidean());
 
-        Self {
+        Ok(Self {
             colorspace,
             lookup,
             error,
-        }
+        })
     }
 }
 
@@ -232,7 +206,8 @@ where
 
     fn get_nearest_color(&self, color: P) -> Self::Nearest {
         let coord = self.colorspace.get_coordinate(color);
-        self.lookup.get_nearest_color(coord)
+        self.lookup
+            .get_nearest_color(coord, |a, b| self.colorspace.distance_2(a, b))
     }
 
     #[inline(always)]
@@ -250,28 +225,31 @@ impl<P: Clone, C: ColorSpace<P>, E: ErrorCombinator<P>> ErrorCombinator<P>
     }
 }
 
-pub fn quantize<P: Clone>(img: &mut Image<P>, quant: &impl ColorLookup<P, Nearest = P>) {
-    for p in img.data_mut() {
+pub fn quantize<P: Clone>(mut img: ImageViewMut<P>, quant: &impl ColorLookup<P, Nearest = P>) {
+    for p in img.rows_mut().flatten() {
         *p = quant.get_nearest_color(p.clone());
     }
 }
-pub fn quantize_ndim(img: &mut NDimImage, quant: ChannelQuantization) {
+pub fn quantize_ndim<T: Sample>(mut img: NDimViewMut<T>, quant: ChannelQuantization) {
     if quant.per_channel() == 2 {
-        for p in img.data_mut() {
-            *p = if *p >= 0.5 { 1.0 } else { 0.0 };
+        let (zero, one) = (T::from_f32(0.0), T::from_f32(1.0));
+        for p in img.rows_mut().flatten() {
+            *p = if p.to_f32() >= 0.5 { one } else { zero };
         }
     } else {
         let f = (quant.per_channel() - 1) as f32;
         let f_inv = 1_f32 / f;
-        for p in img.data_mut() {
-            *p = (*p * f + 0.5).floor() * f_inv;
+        for p in img.rows_mut().flatten() {
+            *p = T::from_f32((p.to_f32() * f + 0.5).floor() * f_inv);
         }
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::dither::ChannelQuantization;
+    use crate::dither::{ChannelQuantization, CieLab, DeltaE, LinearRGB, OkLab, RGB};
+
+    use image_core::{NDimImage, Rect};
 
     use super::*;
     use test_util::{data::read_flower, snap::ImageSnapshot};
@@ -279,13 +257,72 @@ mod tests {
     #[test]
     fn quantize_image() {
         let mut img = read_flower();
-        quantize(&mut img, &ChannelQuantization::new(4));
+        quantize(img.view_mut(), &ChannelQuantization::new(4));
         img.snapshot("quantize_4");
     }
     #[test]
     fn quantize_ndim_image() {
         let mut img: NDimImage = read_flower().into();
-        quantize_ndim(&mut img, ChannelQuantization::new(4));
+        quantize_ndim(img.view_mut(), ChannelQuantization::new(4));
         img.snapshot("quantize_ndim_4");
     }
+    #[test]
+    fn quantize_sub_view() {
+        let original: NDimImage = read_flower().into();
+        let rect = Rect::new(20, 10, 50, 40);
+
+        let mut img = original.clone();
+        quantize_ndim(img.sub_view_mut(rect), ChannelQuantization::new(4));
+
+        let mut expected = original.crop(rect);
+        quantize_ndim(expected.view_mut(), ChannelQuantization::new(4));
+        assert!(img.crop(rect).data() == expected.data());
+
+        // pixels outside the rectangle are unchanged
+        let below = Rect::new(0, rect.bottom(), original.width(), 5);
+        assert!(img.crop(below).data() == original.crop(below).data());
+    }
+    #[test]
+    fn palette_color_spaces() {
+        fn check<C: ColorSpace<Vec3A>>(colorspace: C) {
+            let img = read_flower();
+            // more than enough colors for a tree lookup
+            let colors: Vec<Vec3A> = img.data().iter().step_by(7).take(400).copied().collect();
+            let palette = ColorPalette::new(colorspace, colors.iter().copied(), BoundError);
+
+            for &color in img.data().iter().step_by(13) {
+                let coord = palette.colorspace.get_coordinate(color);
+                let distance = |c: Vec3A| {
+                    let c = palette.colorspace.get_coordinate(c);
+                    palette.colorspace.distance_2(&c, &coord)
+                };
+                let min = colors
+                    .iter()
+                    .map(|&c| distance(c))
+                    .fold(f32::INFINITY, f32::min);
+                let nearest = palette.get_nearest_color(color);
+                assert!(distance(nearest) <= min * (1.0 + 1e-5) + 1e-9);
+            }
+        }
+
+        check(RGB);
+        check(LinearRGB);
+        check(OkLab);
+        check(CieLab {
+            delta_e: DeltaE::Cie76,
+        });
+        check(CieLab {
+            delta_e: DeltaE::Cie2000,
+        });
+    }
+    #[test]
+    fn quantize_u8() {
+        let original: NDimImage = read_flower().into();
+        let mut img = original.convert::<u8>();
+        quantize_ndim(img.view_mut(), ChannelQuantization::new(4));
+
+        let mut expected = original.convert::<u8>().convert::<f32>();
+        quantize_ndim(expected.view_mut(), ChannelQuantization::new(4));
+        assert!(img.data() == expected.convert::<u8>().data());
+    }
 }
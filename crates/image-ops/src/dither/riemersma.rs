@@ -1,21 +1,44 @@
-use image_core::Image;
+use image_core::{Image, ImageView, ImageViewMut};
 
 use crate::util::from_const;
 
-use super::{Pixel, Quantizer};
+use super::{DitherError, Pixel, Quantizer};
 
+/// Checks the parameters given the factor by which errors decay each step.
+fn check_params(history_length: usize, decay_ratio: f32, base: f32) -> Result<(), DitherError> {
+    if history_length < 2 {
+        return Err(DitherError::HistoryTooShort { history_length });
+    }
+    if !(0.0 < base && base < 1.0) {
+        return Err(DitherError::InvalidDecayRatio { decay_ratio });
+    }
+    Ok(())
+}
+
+/// # Panics
+///
+/// Panics if the history is too short or the decay ratio isn't between 0 and
+/// 1. See [`try_riemersma_dither`].
 pub fn riemersma_dither<P: Pixel>(
-    src: &mut Image<P>,
+    src: ImageViewMut<P>,
     history_length: usize,
     decay_ratio: f32,
     quant: &impl Quantizer<P, P>,
 ) {
+    try_riemersma_dither(src, history_length, decay_ratio, quant)
+        .expect("Invalid history length or decay ratio.")
+}
+pub fn try_riemersma_dither<P: Pixel>(
+    mut src: ImageViewMut<P>,
+    history_length: usize,
+    decay_ratio: f32,
+    quant: &impl Quantizer<P, P>,
+) -> Result<(), DitherError> {
     let w = src.width();
     let h = src.height();
-    let data = src.data_mut();
 
     let base = f32::exp(decay_ratio.ln() / (history_length as f32 - 1.0));
-    assert!(0.0 < base && base < 1.0);
+    check_params(history_length, decay_ratio, base)?;
 
     let mut history: Box<[P]> = vec![Default::default(); history_length].into_boxed_slice();
     let history = &mut *history;
@@ -32,22 +55,28 @@ pub fn riemersma_dither<P: Pixel>(
             *error = *error * base;
         }
 
-        let index = y * w + x;
+        let pixel = &mut src.row_mut(y)[x];
 
-        let original = data[index];
+        let original = *pixel;
         let color = quant.combine_error(original, current_error);
         let nearest = quant.get_nearest_color(color);
         let error = quant.get_error(original, nearest);
 
-        data[index] = nearest;
+        *pixel = nearest;
 
         history[history_index] = error;
         history_index = (history_index + 1) % history_length;
     }
+
+    Ok(())
 }
 
+/// # Panics
+///
+/// Panics if the history is too short or the decay ratio isn't between 0 and
+/// 1. See [`try_riemersma_dither_map`].
 pub fn riemersma_dither_map<P: Pixel, N>(
-    src: &Image<P>,
+    src: ImageView<P>,
     history_length: usize,
     decay_ratio: f32,
     quant: &impl Quantizer<P, N>,
@@ -56,15 +85,27 @@ pub fn riemersma_dither_map<P: Pixel, N>(
 where
     N: Clone + Default,
 {
+    try_riemersma_dither_map(src, history_length, decay_ratio, quant, out)
+        .expect("Invalid history length or decay ratio.")
+}
+pub fn try_riemersma_dither_map<P: Pixel, N>(
+    src: ImageView<P>,
+    history_length: usize,
+    decay_ratio: f32,
+    quant: &impl Quantizer<P, N>,
+    out: Option<Image<N>>,
+) -> Result<Image<N>, DitherError>
+where
+    N: Clone + Default,
+{
+    let base = 1.0 / f32::exp((1.0 / decay_ratio).ln() / (history_length as f32 - 1.0));
+    check_params(history_length, decay_ratio, base)?;
+
     let mut dest = from_const(src.size(), Default::default(), out);
     let dest_data = dest.data_mut();
 
     let w = src.width();
     let h = src.height();
-    let data = src.data();
-
-    let base = 1.0 / f32::exp((1.0 / decay_ratio).ln() / (history_length as f32 - 1.0));
-    assert!(0.0 < base && base < 1.0);
 
     let mut history: Box<[P]> = vec![Default::default(); history_length].into_boxed_slice();
     let history = &mut *history;
@@ -83,7 +124,7 @@ where
 
         let index = y * w + x;
 
-        let original = data[index];
+        let original = src.row(y)[x];
         let color = quant.combine_error(original, current_error);
         let nearest = quant.get_nearest_color(color);
         let error = quant.get_error(original, nearest.clone());
@@ -94,7 +135,7 @@ where
         history_index = (history_index + 1) % history_length;
     }
 
-    dest
+    Ok(dest)
 }
 
 #[cfg(test)]
@@ -108,7 +149,12 @@ mod tests {
     #[test]
     fn riemersma() {
         let mut original = read_flower();
-        riemersma_dither(&mut original, 16, 1.0 / 16.0, &ChannelQuantization::new(4));
+        riemersma_dither(
+            original.view_mut(),
+            16,
+            1.0 / 16.0,
+            &ChannelQuantization::new(4),
+        );
         original.snapshot("riemersma_flower_4");
     }
     #[test]
@@ -116,7 +162,7 @@ mod tests {
         let original = read_flower();
 
         riemersma_dither_map(
-            &original,
+            original.view(),
             16,
             1.0 / 16.0,
             &ChannelQuantization::new(4),
@@ -131,6 +177,24 @@ mod tests {
 
         let palette = ColorPalette::new(RGB, palette_img.row(0).iter().copied(), BoundError);
 
-        riemersma_dither_map(&img, 16, 1.0 / 16.0, &palette, None).snapshot("riemersma_palette");
+        riemersma_dither_map(img.view(), 16, 1.0 / 16.0, &palette, None)
+            .snapshot("riemersma_palette");
+    }
+    #[test]
+    fn riemersma_invalid_params() {
+        let mut img = read_flower();
+        let quant = ChannelQuantization::new(4);
+
+        assert_eq!(
+            try_riemersma_dither(img.view_mut(), 1, 0.5, &quant),
+            Err(DitherError::HistoryTooShort { history_length: 1 })
+        );
+        for decay_ratio in [0.0, 1.0, 2.0, f32::NAN] {
+            assert!(matches!(
+                try_riemersma_dither(img.view_mut(), 16, decay_ratio, &quant),
+                Err(DitherError::InvalidDecayRatio { .. })
+            ));
+        }
+        assert!(img.data() == read_flower().data());
     }
 }
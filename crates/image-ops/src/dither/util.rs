@@ -1,3 +1,37 @@
+use glam::{Vec2, Vec3, Vec3A, Vec4};
+
 pub trait Pixel: Copy + Default + std::ops::AddAssign + std::ops::Mul<f32, Output = Self> {}
 impl<P> Pixel for P where P: Copy + Default + std::ops::AddAssign + std::ops::Mul<f32, Output = Self>
 {}
+
+/// The brightness of a color, used to order the colors of dithering patterns.
+pub trait Luminance {
+    fn luminance(&self) -> f32;
+}
+impl Luminance for f32 {
+    #[inline]
+    fn luminance(&self) -> f32 {
+        *self
+    }
+}
+impl Luminance for Vec2 {
+    /// Gray with alpha. Alpha is ignored.
+    #[inline]
+    fn luminance(&self) -> f32 {
+        self.x
+    }
+}
+macro_rules! impl_luminance_rgb {
+    ($t:ty) => {
+        impl Luminance for $t {
+            /// The Rec. 709 luminance. Alpha is ignored.
+            #[inline]
+            fn luminance(&self) -> f32 {
+                self.x * 0.2126 + self.y * 0.7152 + self.z * 0.0722
+            }
+        }
+    };
+}
+impl_luminance_rgb!(Vec3);
+impl_luminance_rgb!(Vec3A);
+impl_luminance_rgb!(Vec4);
@@ -1,5 +1,5 @@
 use glam::Vec4;
-use image_core::{Image, Size};
+use image_core::{Image, ImageView, ImageViewMut, Size};
 use rstar::{primitives::GeomWithData, RTree};
 use std::ops::Range;
 
@@ -9,6 +9,12 @@ use crate::{
     util::{div_ceil, from_image_cow, move_range, Grid},
 };
 
+#[derive(Debug, Clone, PartialEq)]
+pub enum FillAlphaError {
+    /// Fragment blur needs between 1 and 255 fragments.
+    InvalidFragmentCount { fragment_count: u32 },
+}
+
 pub enum FillMode {
     Fragment {
         iterations: u32,
@@ -23,25 +29,53 @@ pub enum FillMode {
     },
 }
 
+/// # Panics
+///
+/// Panics if the parameters of the fill mode are invalid. See
+/// [`try_fill_alpha`].
 pub fn fill_alpha(
-    image: &mut Image<Vec4>,
+    image: ImageViewMut<Vec4>,
     threshold: f32,
     mode: FillMode,
     temp: Option<&mut Image<Vec4>>,
 ) {
+    try_fill_alpha(image, threshold, mode, temp).expect("Invalid fill mode.")
+}
+pub fn try_fill_alpha(
+    mut image: ImageViewMut<Vec4>,
+    threshold: f32,
+    mode: FillMode,
+    temp: Option<&mut Image<Vec4>>,
+) -> Result<(), FillAlphaError> {
+    if let FillMode::Fragment { fragment_count, .. } = mode {
+        if !(1..=255).contains(&fragment_count) {
+            return Err(FillAlphaError::InvalidFragmentCount { fragment_count });
+        }
+    }
+
+    if !image.is_contiguous() {
+        // all fill modes index pixels directly, so they need contiguous data
+        let mut owned = image.view().into_owned();
+        try_fill_alpha(owned.view_mut(), threshold, mode, temp)?;
+        image.copy_from(owned.view());
+        return Ok(());
+    }
+
     make_binary_alpha(image.data_mut(), threshold);
 
     match mode {
         FillMode::Fragment {
             iterations,
             fragment_count,
-        } => fill_alpha_fragment_blur(image, iterations, fragment_count, temp),
-        FillMode::ExtendColor { iterations } => fill_alpha_extend(image, iterations as usize),
+        } => fill_alpha_fragment_blur(&mut image, iterations, fragment_count, temp),
+        FillMode::ExtendColor { iterations } => fill_alpha_extend(&mut image, iterations as usize),
         FillMode::Nearest {
             min_radius: radius,
             anti_aliasing,
-        } => fill_alpha_nearest(image, radius, anti_aliasing),
+        } => fill_alpha_nearest(&mut image, radius, anti_aliasing),
     }
+
+    Ok(())
 }
 
 fn make_binary_alpha(pixels: &mut [Vec4], threshold: f32) {
@@ -53,7 +87,7 @@ fn make_binary_alpha(pixels: &mut [Vec4], threshold: f32) {
 }
 
 fn fill_alpha_fragment_blur(
-    image: &mut Image<Vec4>,
+    image: &mut ImageViewMut<Vec4>,
     iterations: u32,
     fragment_count: u32,
     temp: Option<&mut Image<Vec4>>,
@@ -62,7 +96,7 @@ fn fill_alpha_fragment_blur(
         return;
     }
 
-    let original = &*from_image_cow(image, temp);
+    let original = &*from_image_cow(image.view(), temp);
     let mut buffer: Image<Vec4> = Image::from_const(image.size(), Vec4::ZERO);
 
     for i in 0..iterations {
@@ -77,14 +111,14 @@ fn fill_alpha_fragment_blur(
             Some(buffer),
         );
         overlay_self_mut(&mut buffer, 2);
-        overlay_mut(&mut buffer, image);
-        std::mem::swap(&mut buffer, image);
+        overlay_mut(&mut buffer, image.view());
+        image.copy_from(buffer.view());
     }
 
     make_binary_alpha(image.data_mut(), 0.01);
 }
 
-fn is_to_fill(image: &Image<Vec4>, x: usize, y: usize) -> bool {
+fn is_to_fill(image: ImageView<Vec4>, x: usize, y: usize) -> bool {
     let data = image.data();
     let w = image.width();
     let h = image.height();
@@ -97,7 +131,7 @@ fn is_to_fill(image: &Image<Vec4>, x: usize, y: usize) -> bool {
             || y < h - 1 && data[i + w].w != 0.)
 }
 
-fn get_fill(image: &Image<Vec4>, i: usize, x: usize, y: usize) -> Option<Vec4> {
+fn get_fill(image: ImageView<Vec4>, i: usize, x: usize, y: usize) -> Option<Vec4> {
     let w = image.width();
     let h = image.height();
     let data = image.data();
@@ -124,7 +158,7 @@ fn get_fill(image: &Image<Vec4>, i: usize, x: usize, y: usize) -> Option<Vec4> {
 
     None
 }
-unsafe fn get_fill_unchecked(image: &Image<Vec4>, i: usize) -> Option<Vec4> {
+unsafe fn get_fill_unchecked(image: ImageView<Vec4>, i: usize) -> Option<Vec4> {
     let w = image.width();
     let data = image.data();
 
@@ -140,27 +174,27 @@ unsafe fn get_fill_unchecked(image: &Image<Vec4>, i: usize) -> Option<Vec4> {
 
     None
 }
-fn is_transparent(image: &Image<Vec4>, i: usize) -> bool {
+fn is_transparent(image: ImageView<Vec4>, i: usize) -> bool {
     image.data()[i].w == 0.
 }
 
-fn fill_alpha_extend(image: &mut Image<Vec4>, iterations: usize) {
+fn fill_alpha_extend(image: &mut ImageViewMut<Vec4>, iterations: usize) {
     if iterations == 0 {
         return;
     }
 
     let mut grid: Grid<8> = Grid::new(image.size());
-    grid.fill_with_pixels(|x, y| is_to_fill(image, x, y));
+    grid.fill_with_pixels(|x, y| is_to_fill(image.view(), x, y));
 
     let mut fills = Vec::with_capacity(image.width().max(image.height()) * 4);
 
     for i in 0..iterations {
         if i > 0 && i % grid.cell_size() == 0 {
-            grid.and_any(|x, y| is_to_fill(image, x, y));
+            grid.and_any(|x, y| is_to_fill(image.view(), x, y));
         }
         if i % grid.cell_size() == 1 {
             grid.expand_one();
-            grid.and_any_index(|i| is_transparent(image, i));
+            grid.and_any_index(|i| is_transparent(image.view(), i));
         }
 
         grid.for_each_true(|x_range, y_range, is_inner| {
@@ -170,7 +204,7 @@ fn fill_alpha_extend(image: &mut Image<Vec4>, iterations: usize) {
                     for i in move_range(&x_range, y * image.width()) {
                         // SAFETY: This is an inner cell, so we are guaranteed to have at least one neighboring
                         // pixel in all directions.
-                        if let Some(fill) = unsafe { get_fill_unchecked(image, i) } {
+                        if let Some(fill) = unsafe { get_fill_unchecked(image.view(), i) } {
                             fills.push((i, fill));
                         }
                     }
@@ -180,7 +214,7 @@ fn fill_alpha_extend(image: &mut Image<Vec4>, iterations: usize) {
                 for y in y_range {
                     for x in x_range.clone() {
                         let i = y * image.width() + x;
-                        if let Some(fill) = get_fill(image, i, x, y) {
+                        if let Some(fill) = get_fill(image.view(), i, x, y) {
                             fills.push((i, fill));
                         }
                     }
@@ -228,7 +262,7 @@ fn within_radius_grid<const N: usize>(
     opaque_grid
 }
 
-fn fill_alpha_nearest(image: &mut Image<Vec4>, radius: u32, anti_aliasing: bool) {
+fn fill_alpha_nearest(image: &mut ImageViewMut<Vec4>, radius: u32, anti_aliasing: bool) {
     let w = image.width();
     let h = image.height();
     let data = image.data_mut();
@@ -404,7 +438,7 @@ mod tests {
     fn fill_alpha_texture() {
         let mut original = read_flower_transparent();
         super::fill_alpha(
-            &mut original,
+            original.view_mut(),
             0.15,
             super::FillMode::Fragment {
                 iterations: 6,
@@ -419,7 +453,7 @@ mod tests {
     fn fill_alpha_color() {
         let mut original = read_flower_transparent();
         super::fill_alpha(
-            &mut original,
+            original.view_mut(),
             0.15,
             super::FillMode::ExtendColor { iterations: 64 },
             None,
@@ -431,7 +465,7 @@ mod tests {
     fn fill_alpha_nearest() {
         let mut original = read_flower_transparent();
         super::fill_alpha(
-            &mut original,
+            original.view_mut(),
             0.15,
             super::FillMode::Nearest {
                 min_radius: 50,
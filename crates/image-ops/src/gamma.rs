@@ -1,29 +1,18 @@
 use image_core::NDimImage;
 use rayon::prelude::*;
 
+/// Applies the given gamma to the color channels of the given image.
+///
+/// If the image has 2 or 4 channels, the last channel is assumed to be alpha
+/// and will not be changed.
 pub fn gamma_ndim(image: &mut NDimImage, gamma: f32) {
     // we want to divide the image into chunks
     const BLOCK_SIZE: usize = 1024 * 8;
 
-    if image.channels() == 4 {
-        // only apply gamma to RGB channels
-        image
-            .data_mut()
-            .par_chunks_mut(BLOCK_SIZE)
-            .for_each(|chunk| {
-                // the AVX implementation is actually slower than the trivial one,
-                // so we don't use it here
-
-                let (chunks, rest) = image_core::util::slice_as_chunks_mut::<f32, 4>(chunk);
-                assert!(rest.is_empty());
-
-                chunks.iter_mut().for_each(|p| {
-                    // only apply gamma to the RGB channels
-                    p[0] = p[0].powf(gamma);
-                    p[1] = p[1].powf(gamma);
-                    p[2] = p[2].powf(gamma);
-                });
-            });
+    if has_alpha(image) {
+        // the AVX implementation is actually slower than the trivial one,
+        // so we don't use it here
+        for_each_color(image, |x| x.powf(gamma));
     } else {
         image
             .data_mut()
@@ -51,6 +40,148 @@ pub fn gamma_ndim(image: &mut NDimImage, gamma: f32) {
     }
 }
 
+/// Converts the given sRGB value to linear RGB using the exact piecewise sRGB
+/// transfer function.
+#[inline]
+pub fn srgb_to_linear(x: f32) -> f32 {
+    if x <= 0.04045 {
+        x * (1.0 / 12.92)
+    } else {
+        ((x + 0.055) * (1.0 / 1.055)).powf(2.4)
+    }
+}
+
+/// Converts the given linear RGB value to sRGB using the exact piecewise sRGB
+/// transfer function.
+#[inline]
+pub fn linear_to_srgb(x: f32) -> f32 {
+    if x <= 0.0031308 {
+        x * 12.92
+    } else {
+        1.055 * x.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// Converts the color channels of the given sRGB image to linear RGB.
+///
+/// If the image has 2 or 4 channels, the last channel is assumed to be alpha
+/// and will not be changed.
+pub fn srgb_to_linear_ndim(image: &mut NDimImage) {
+    transfer_ndim(image, Transfer::SrgbToLinear);
+}
+
+/// Converts the color channels of the given linear RGB image to sRGB.
+///
+/// If the image has 2 or 4 channels, the last channel is assumed to be alpha
+/// and will not be changed.
+pub fn linear_to_srgb_ndim(image: &mut NDimImage) {
+    transfer_ndim(image, Transfer::LinearToSrgb);
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Transfer {
+    SrgbToLinear,
+    LinearToSrgb,
+}
+
+impl Transfer {
+    #[inline]
+    fn apply(self, x: f32) -> f32 {
+        match self {
+            Transfer::SrgbToLinear => srgb_to_linear(x),
+            Transfer::LinearToSrgb => linear_to_srgb(x),
+        }
+    }
+}
+
+fn transfer_ndim(image: &mut NDimImage, transfer: Transfer) {
+    // we want to divide the image into chunks
+    const BLOCK_SIZE: usize = 1024 * 8;
+
+    let channels = image.channels();
+    if channels == 2 {
+        for_each_color(image, |x| transfer.apply(x));
+        return;
+    }
+
+    let scalar = |data: &mut [f32]| {
+        if channels == 4 {
+            data.chunks_exact_mut(4).for_each(|p| {
+                // only apply the transfer function to the RGB channels
+                p[..3].iter_mut().for_each(|x| *x = transfer.apply(*x));
+            });
+        } else {
+            data.iter_mut().for_each(|x| *x = transfer.apply(*x));
+        }
+    };
+
+    image
+        .data_mut()
+        .par_chunks_mut(BLOCK_SIZE)
+        .for_each(|chunk| {
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            {
+                if is_x86_feature_detected!("avx2") {
+                    // BLOCK_SIZE is a multiple of 8, so every group of 8
+                    // values starts at a pixel boundary
+                    let (chunks, rest) = image_core::util::slice_as_chunks_mut::<f32, 8>(chunk);
+
+                    // do the rest first
+                    scalar(rest);
+
+                    chunks.iter_mut().for_each(|f| {
+                        // 8 values are 2 RGBA pixels, so we have to restore
+                        // their alpha afterwards
+                        let alpha = (f[3], f[7]);
+                        unsafe {
+                            match transfer {
+                                Transfer::SrgbToLinear => avx2::srgb_to_linear(f),
+                                Transfer::LinearToSrgb => avx2::linear_to_srgb(f),
+                            }
+                        }
+                        if channels == 4 {
+                            (f[3], f[7]) = alpha;
+                        }
+                    });
+                    return;
+                }
+            }
+
+            // fallback
+            scalar(chunk);
+        });
+}
+
+/// Returns whether the last channel of the image is alpha, which is the case
+/// for gray+alpha and RGBA images.
+fn has_alpha(image: &NDimImage) -> bool {
+    matches!(image.channels(), 2 | 4)
+}
+
+/// Applies the given function to all channels of the image except the last
+/// one, which must be alpha.
+fn for_each_color(image: &mut NDimImage, f: impl Fn(f32) -> f32 + Sync) {
+    fn apply<const N: usize>(data: &mut [f32], f: impl Fn(f32) -> f32 + Sync) {
+        // we want to divide the image into chunks
+        const BLOCK_SIZE: usize = 1024 * 8;
+
+        data.par_chunks_mut(BLOCK_SIZE).for_each(|chunk| {
+            let (chunks, rest) = image_core::util::slice_as_chunks_mut::<f32, N>(chunk);
+            assert!(rest.is_empty());
+
+            chunks.iter_mut().for_each(|p| {
+                p[..N - 1].iter_mut().for_each(|x| *x = f(*x));
+            });
+        });
+    }
+
+    match image.channels() {
+        2 => apply::<2>(image.data_mut(), f),
+        4 => apply::<4>(image.data_mut(), f),
+        c => panic!("Expected an image with alpha, but got {c} channels"),
+    }
+}
+
 #[allow(clippy::excessive_precision)]
 #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
 mod avx2 {
@@ -74,6 +205,45 @@ mod avx2 {
         _mm256_storeu_ps(x as *mut _, p);
     }
 
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn srgb_to_linear(x: &mut [f32; 8]) {
+        const THRESHOLD: __m256 = const_f32(0.04045);
+        const INV_12_92: __m256 = const_f32(1.0 / 12.92);
+        const OFFSET: __m256 = const_f32(0.055);
+        const INV_1_055: __m256 = const_f32(1.0 / 1.055);
+        const EXPONENT: __m256 = const_f32(2.4);
+
+        let x_m = _mm256_loadu_ps(x as *const _);
+
+        let low = _mm256_mul_ps(x_m, INV_12_92);
+        let t = _mm256_mul_ps(_mm256_add_ps(x_m, OFFSET), INV_1_055);
+        let high = pow(t, EXPONENT);
+
+        let is_low = _mm256_cmp_ps(x_m, THRESHOLD, _CMP_LE_OQ);
+        let r = _mm256_blendv_ps(high, low, is_low);
+
+        _mm256_storeu_ps(x as *mut _, r);
+    }
+
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn linear_to_srgb(x: &mut [f32; 8]) {
+        const THRESHOLD: __m256 = const_f32(0.0031308);
+        const SCALE_12_92: __m256 = const_f32(12.92);
+        const OFFSET: __m256 = const_f32(0.055);
+        const SCALE_1_055: __m256 = const_f32(1.055);
+        const EXPONENT: __m256 = const_f32(1.0 / 2.4);
+
+        let x_m = _mm256_loadu_ps(x as *const _);
+
+        let low = _mm256_mul_ps(x_m, SCALE_12_92);
+        let high = _mm256_sub_ps(_mm256_mul_ps(pow(x_m, EXPONENT), SCALE_1_055), OFFSET);
+
+        let is_low = _mm256_cmp_ps(x_m, THRESHOLD, _CMP_LE_OQ);
+        let r = _mm256_blendv_ps(high, low, is_low);
+
+        _mm256_storeu_ps(x as *mut _, r);
+    }
+
     #[target_feature(enable = "avx2")]
     pub unsafe fn pow(x: __m256, y: __m256) -> __m256 {
         let mut t = log(x);
@@ -151,7 +321,7 @@ mod avx2 {
         const EXP_HI: __m256 = const_f32(88.3762626647949);
         const EXP_LO: __m256 = const_f32(-88.3762626647949);
 
-        const LOG2EF: __m256 = const_f32(1.44269504088896341);
+        const LOG2EF: __m256 = const_f32(std::f32::consts::LOG2_E);
         const EXP_C1: __m256 = const_f32(0.693359375);
         const EXP_C2: __m256 = const_f32(-2.12194440e-4);
 
@@ -214,7 +384,7 @@ mod avx2 {
         const MIN_NORM_POS: __m256i = const_i32(0x00800000);
         const INV_MANT_MASK: __m256i = const_i32(!0x7f800000);
 
-        const SQRTHF: __m256 = const_f32(0.707106781186547524);
+        const SQRTHF: __m256 = const_f32(std::f32::consts::FRAC_1_SQRT_2);
         const LOG_P0: __m256 = const_f32(7.0376836292E-2);
         const LOG_P1: __m256 = const_f32(-1.1514610310E-1);
         const LOG_P2: __m256 = const_f32(1.1676998740E-1);
@@ -291,7 +461,7 @@ mod avx2 {
 
 #[cfg(test)]
 mod tests {
-    use image_core::NDimImage;
+    use image_core::{NDimImage, Shape};
     use test_util::{
         data::{read_flower_transparent, read_portrait},
         snap::ImageSnapshot,
@@ -307,4 +477,47 @@ mod tests {
         super::gamma_ndim(&mut img, 2.2);
         img.snapshot("gamma_rgb");
     }
+
+    #[test]
+    fn gamma_keeps_alpha() {
+        let mut img = NDimImage::new(Shape::new(2, 1, 2), vec![0.5, 0.25, 0.8, 0.75]);
+        super::gamma_ndim(&mut img, 2.0);
+        assert_eq!(img.data(), &[0.25, 0.25, 0.8f32.powf(2.0), 0.75]);
+    }
+
+    #[test]
+    fn srgb() {
+        let mut img: NDimImage = read_flower_transparent().into();
+        super::srgb_to_linear_ndim(&mut img);
+        img.snapshot("srgb_to_linear_rgba");
+
+        let mut img: NDimImage = read_portrait().into();
+        super::srgb_to_linear_ndim(&mut img);
+        img.snapshot("srgb_to_linear_rgb");
+
+        let mut img: NDimImage = read_portrait().into();
+        super::linear_to_srgb_ndim(&mut img);
+        img.snapshot("linear_to_srgb_rgb");
+    }
+
+    #[test]
+    fn srgb_keeps_alpha() {
+        // odd number of pixels, so both the SIMD and the scalar path are used
+        let data: Vec<f32> = (0..3 * 4).map(|i| i as f32 / 12.0).collect();
+
+        for channels in [2, 4] {
+            let shape = Shape::new(3, 1, channels);
+            let mut img = NDimImage::new(shape, data[..shape.len()].to_vec());
+            super::srgb_to_linear_ndim(&mut img);
+            super::linear_to_srgb_ndim(&mut img);
+
+            for (i, (&actual, &expected)) in img.data().iter().zip(&data).enumerate() {
+                if i % channels == channels - 1 {
+                    assert_eq!(actual, expected);
+                } else {
+                    assert!((actual - expected).abs() < 1e-4, "{actual} != {expected}");
+                }
+            }
+        }
+    }
 }
@@ -1,11 +1,21 @@
 pub mod blend;
+pub mod blur;
+pub mod border;
+pub mod components;
+pub mod convolve;
 pub mod dither;
 pub mod esdt;
 pub mod fill_alpha;
 pub mod fragment_blur;
 pub mod gamma;
+pub mod morphology;
 pub mod palette;
 pub mod pixel_art;
 pub mod scale;
+pub mod sharpen;
+pub mod smooth;
 pub mod threshold;
+pub mod tile;
+pub mod trim;
 mod util;
+pub mod warp;
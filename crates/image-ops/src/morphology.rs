@@ -0,0 +1,460 @@
+use image_core::{Image, ImageView, NDimImage, NDimView, Size};
+use rayon::prelude::*;
+
+use crate::util::{channel_plane, set_channel_plane, FixedBits, Grid};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MorphologyError {
+    /// The structuring element is empty, its data doesn't match its size, or
+    /// it doesn't contain any pixel.
+    InvalidElement,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Operation {
+    Dilate,
+    Erode,
+    /// Erosion followed by dilation.
+    Open,
+    /// Dilation followed by erosion.
+    Close,
+    /// The difference between the dilation and the erosion.
+    Gradient,
+    /// The difference between the image and its opening.
+    TopHat,
+    /// The difference between the closing and the image.
+    BlackHat,
+}
+
+/// The neighborhood of a morphological operation.
+///
+/// The anchor of the element is at `(width / 2, height / 2)`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StructuringElement {
+    size: Size,
+    data: Vec<bool>,
+}
+
+impl StructuringElement {
+    /// Creates a new structuring element from its row-major pixels.
+    pub fn new(size: Size, data: Vec<bool>) -> Result<Self, MorphologyError> {
+        if size.is_empty() || size.len() != data.len() || !data.iter().any(|b| *b) {
+            return Err(MorphologyError::InvalidElement);
+        }
+        Ok(Self { size, data })
+    }
+
+    /// A `(2 * radius + 1)`² square.
+    pub fn square(radius: usize) -> Self {
+        let diameter = 2 * radius + 1;
+        Self {
+            size: Size::new(diameter, diameter),
+            data: vec![true; diameter * diameter],
+        }
+    }
+
+    /// A disc with the given radius, i.e. all pixels whose center is at most
+    /// `radius` pixels away from the center of the disc. A radius of 0 is a
+    /// single pixel and a radius of 1 is a cross.
+    pub fn disc(radius: usize) -> Self {
+        let diameter = 2 * radius + 1;
+        let r = radius as f32;
+        let size = Size::new(diameter, diameter);
+        let data = size
+            .iter_pos()
+            .map(|(x, y)| {
+                let dx = x as f32 - radius as f32;
+                let dy = y as f32 - radius as f32;
+                dx * dx + dy * dy <= r * r
+            })
+            .collect();
+        Self { size, data }
+    }
+
+    pub fn size(&self) -> Size {
+        self.size
+    }
+    pub fn data(&self) -> &[bool] {
+        &self.data
+    }
+
+    /// Returns the radius if the element is a square.
+    fn square_radius(&self) -> Option<usize> {
+        let Size { width, height } = self.size;
+        if width == height && width % 2 == 1 && self.data.iter().all(|b| *b) {
+            Some(width / 2)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the offsets of all pixels of the element relative to its anchor.
+    fn offsets(&self) -> Vec<(isize, isize)> {
+        let ax = (self.size.width / 2) as isize;
+        let ay = (self.size.height / 2) as isize;
+        self.size
+            .iter_pos()
+            .zip(&self.data)
+            .filter(|(_, b)| **b)
+            .map(|((x, y), _)| (x as isize - ax, y as isize - ay))
+            .collect()
+    }
+}
+
+fn reflect(offsets: &[(isize, isize)]) -> Vec<(isize, isize)> {
+    offsets.iter().map(|(x, y)| (-x, -y)).collect()
+}
+
+/// Sets each pixel to the extremum of `img[x + dx, y + dy]` over all offsets.
+/// Offsets outside the image are ignored, and pixels without any offset inside
+/// the image keep their value.
+fn extremum(
+    img: ImageView<f32>,
+    offsets: &[(isize, isize)],
+    init: f32,
+    f: impl Fn(f32, f32) -> f32 + Sync,
+) -> Image<f32> {
+    let size = img.size();
+    let width = size.width as isize;
+    let height = size.height as isize;
+
+    let mut dst = vec![init; size.len()];
+    dst.par_chunks_exact_mut(size.width)
+        .enumerate()
+        .for_each(|(y, row)| {
+            for &(dx, dy) in offsets {
+                let sy = y as isize + dy;
+                if sy < 0 || sy >= height {
+                    continue;
+                }
+                let start = (-dx).clamp(0, width);
+                let end = (width - dx).clamp(0, width);
+                if start >= end {
+                    continue;
+                }
+
+                let src = &img.row(sy as usize)[(start + dx) as usize..(end + dx) as usize];
+                for (d, s) in row[start as usize..end as usize].iter_mut().zip(src) {
+                    *d = f(*d, *s);
+                }
+            }
+
+            for (d, s) in row.iter_mut().zip(img.row(y)) {
+                if *d == init {
+                    *d = *s;
+                }
+            }
+        });
+
+    Image::new(size, dst)
+}
+
+fn extremum_element(img: ImageView<f32>, element: &StructuringElement, dilate: bool) -> Image<f32> {
+    let (init, f): (f32, fn(f32, f32) -> f32) = if dilate {
+        (f32::NEG_INFINITY, f32::max)
+    } else {
+        (f32::INFINITY, f32::min)
+    };
+
+    if let Some(radius) = element.square_radius() {
+        // squares are separable
+        let r = radius as isize;
+        let horizontal: Vec<_> = (-r..=r).map(|i| (i, 0)).collect();
+        let vertical: Vec<_> = (-r..=r).map(|i| (0, i)).collect();
+        let img = extremum(img, &horizontal, init, f);
+        return extremum(img.view(), &vertical, init, f);
+    }
+
+    let offsets = element.offsets();
+    if dilate {
+        extremum(img, &reflect(&offsets), init, f)
+    } else {
+        extremum(img, &offsets, init, f)
+    }
+}
+
+fn difference(a: &Image<f32>, b: &Image<f32>) -> Image<f32> {
+    Image::new(
+        a.size(),
+        a.data().iter().zip(b.data()).map(|(a, b)| a - b).collect(),
+    )
+}
+
+/// Applies the given morphological operation to the grayscale image.
+///
+/// Pixels outside the image are ignored. So the image is neither extended by
+/// dilating nor shrunk by eroding at its border.
+pub fn morphology(
+    img: ImageView<f32>,
+    operation: Operation,
+    element: &StructuringElement,
+) -> Image<f32> {
+    let dilate = |img: ImageView<f32>| extremum_element(img, element, true);
+    let erode = |img: ImageView<f32>| extremum_element(img, element, false);
+
+    match operation {
+        Operation::Dilate => dilate(img),
+        Operation::Erode => erode(img),
+        Operation::Open => dilate(erode(img).view()),
+        Operation::Close => erode(dilate(img).view()),
+        Operation::Gradient => difference(&dilate(img), &erode(img)),
+        Operation::TopHat => difference(&img.into_owned(), &dilate(erode(img).view())),
+        Operation::BlackHat => difference(&erode(dilate(img).view()), &img.into_owned()),
+    }
+}
+
+/// Applies the given morphological operation to each channel of the image.
+///
+/// See [`morphology`].
+pub fn morphology_ndim(
+    img: NDimView,
+    operation: Operation,
+    element: &StructuringElement,
+) -> NDimImage {
+    let size = img.size();
+
+    let mut result = NDimImage::zeros(img.shape());
+    for c in 0..img.channels() {
+        let plane = channel_plane(img, c);
+        let filtered = morphology(ImageView::new(size, &plane), operation, element);
+        set_channel_plane(&mut result, c, filtered.data());
+    }
+    result
+}
+
+fn to_grid(mask: ImageView<bool>) -> Grid<1> {
+    let mut grid = Grid::new(mask.size());
+    for y in 0..mask.height() {
+        *grid.line_mut(y) = FixedBits::from_slice(mask.row(y), |b| *b);
+    }
+    grid
+}
+
+/// Computes `result[x, y] = any(grid[x + dx, y + dy])` over all offsets.
+fn any_offset(grid: &Grid<1>, offsets: &[(isize, isize)]) -> Grid<1> {
+    let height = grid.height() as isize;
+    let mut result = Grid::new(grid.pixels());
+    for y in 0..height {
+        for &(dx, dy) in offsets {
+            let sy = y + dy;
+            if (0..height).contains(&sy) {
+                let shifted = grid.line(sy as usize).shifted(dx);
+                result.line_mut(y as usize).or(&shifted);
+            }
+        }
+    }
+    result
+}
+
+fn dilate_grid(grid: &Grid<1>, element: &StructuringElement, reflected: bool) -> Grid<1> {
+    if let Some(radius) = element.square_radius() {
+        // squares are separable
+        let r = radius as isize;
+        let horizontal: Vec<_> = (-r..=r).map(|i| (i, 0)).collect();
+        let vertical: Vec<_> = (-r..=r).map(|i| (0, i)).collect();
+        return any_offset(&any_offset(grid, &horizontal), &vertical);
+    }
+
+    let offsets = element.offsets();
+    if reflected {
+        any_offset(grid, &offsets)
+    } else {
+        any_offset(grid, &reflect(&offsets))
+    }
+}
+
+fn erode_grid(grid: &Grid<1>, element: &StructuringElement) -> Grid<1> {
+    // erosion is the complement of the dilation of the complement with the
+    // reflected element
+    let mut inverted = grid.clone();
+    inverted.not();
+    let mut result = dilate_grid(&inverted, element, true);
+    result.not();
+    result
+}
+
+fn and_not(mut a: Grid<1>, b: &Grid<1>) -> Grid<1> {
+    let mut b = b.clone();
+    b.not();
+    a.and(&b);
+    a
+}
+
+/// Applies the given morphological operation to the binary mask.
+///
+/// Pixels outside the mask are ignored. So the mask is neither extended by
+/// dilating nor shrunk by eroding at its border.
+pub fn morphology_binary(
+    mask: ImageView<bool>,
+    operation: Operation,
+    element: &StructuringElement,
+) -> Image<bool> {
+    let grid = to_grid(mask);
+    let dilate = |grid: &Grid<1>| dilate_grid(grid, element, false);
+    let erode = |grid: &Grid<1>| erode_grid(grid, element);
+
+    let result = match operation {
+        Operation::Dilate => dilate(&grid),
+        Operation::Erode => erode(&grid),
+        Operation::Open => dilate(&erode(&grid)),
+        Operation::Close => erode(&dilate(&grid)),
+        Operation::Gradient => and_not(dilate(&grid), &erode(&grid)),
+        Operation::TopHat => and_not(grid.clone(), &dilate(&erode(&grid))),
+        Operation::BlackHat => and_not(erode(&dilate(&grid)), &grid),
+    };
+
+    Image::from_fn(mask.size(), |x, y| result.get(x, y))
+}
+
+/// Applies the given morphological operation to each channel of the image
+/// as a binary mask.
+///
+/// Values >= 0.5 are foreground. The result only contains 0 and 1. See
+/// [`morphology_binary`].
+pub fn morphology_binary_ndim(
+    img: NDimView,
+    operation: Operation,
+    element: &StructuringElement,
+) -> NDimImage {
+    let size = img.size();
+
+    let mut result = NDimImage::zeros(img.shape());
+    for c in 0..img.channels() {
+        let mask: Vec<bool> = channel_plane(img, c).iter().map(|v| *v >= 0.5).collect();
+        let filtered = morphology_binary(ImageView::new(size, &mask), operation, element);
+        let plane: Vec<f32> = filtered
+            .data()
+            .iter()
+            .map(|m| if *m { 1.0 } else { 0.0 })
+            .collect();
+        set_channel_plane(&mut result, c, &plane);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use image_core::{Image, NDimImage, Shape, Size};
+    use test_util::{data::read_at, snap::ImageSnapshot};
+
+    use super::{
+        morphology, morphology_binary, morphology_binary_ndim, morphology_ndim, Operation,
+        StructuringElement,
+    };
+
+    const OPERATIONS: [Operation; 7] = [
+        Operation::Dilate,
+        Operation::Erode,
+        Operation::Open,
+        Operation::Close,
+        Operation::Gradient,
+        Operation::TopHat,
+        Operation::BlackHat,
+    ];
+
+    #[test]
+    fn grayscale() {
+        let img = read_at();
+        morphology(
+            img.view(),
+            Operation::Gradient,
+            &StructuringElement::disc(2),
+        )
+        .snapshot("morphology_gradient_disc_2");
+        morphology(img.view(), Operation::Open, &StructuringElement::square(3))
+            .snapshot("morphology_open_square_3");
+    }
+
+    #[test]
+    fn binary_matches_grayscale() {
+        let img = read_at();
+        let mask = img.map(|p| *p > 0.5);
+        let binary = mask.map(|b| if *b { 1.0 } else { 0.0 });
+
+        let cross = StructuringElement::new(
+            Size::new(3, 3),
+            vec![false, true, false, true, true, true, false, true, false],
+        )
+        .unwrap();
+        // asymmetric, doesn't contain the anchor
+        let custom =
+            StructuringElement::new(Size::new(4, 2), [true, false, false, true].repeat(2)).unwrap();
+
+        for element in [
+            StructuringElement::square(2),
+            StructuringElement::disc(4),
+            cross,
+            custom,
+        ] {
+            for operation in OPERATIONS {
+                let expected = morphology(binary.view(), operation, &element);
+                let actual = morphology_binary(mask.view(), operation, &element);
+                assert_eq!(
+                    actual.map(|b| if *b { 1.0 } else { 0.0 }).data(),
+                    expected.data(),
+                    "{operation:?} with {element:?}"
+                );
+            }
+        }
+
+        morphology_binary(mask.view(), Operation::Close, &StructuringElement::disc(5))
+            .map(|b| if *b { 1.0 } else { 0.0 })
+            .snapshot("morphology_binary_close_disc_5");
+    }
+
+    #[test]
+    fn ndim_matches_single_channel() {
+        let img = read_at();
+        let inverted = img.map(|p| 1.0 - p);
+        let data = img.data().iter().zip(inverted.data());
+        let ndim = NDimImage::new(
+            Shape::from_size(img.size(), 2),
+            data.flat_map(|(a, b)| [*a, *b]).collect(),
+        );
+
+        let element = StructuringElement::disc(2);
+        let gray = morphology_ndim(ndim.view(), Operation::Close, &element);
+        let binary = morphology_binary_ndim(ndim.view(), Operation::Close, &element);
+        for (c, plane) in [img, inverted].iter().enumerate() {
+            let expected = morphology(plane.view(), Operation::Close, &element);
+            let actual: Vec<f32> = gray.data().iter().skip(c).step_by(2).copied().collect();
+            assert_eq!(actual, expected.data());
+
+            let mask = plane.map(|p| *p >= 0.5);
+            let expected = morphology_binary(mask.view(), Operation::Close, &element);
+            let actual: Vec<bool> = binary
+                .data()
+                .iter()
+                .skip(c)
+                .step_by(2)
+                .map(|v| *v == 1.0)
+                .collect();
+            assert_eq!(actual, expected.data());
+        }
+    }
+
+    #[test]
+    fn disc() {
+        assert_eq!(StructuringElement::disc(0), StructuringElement::square(0));
+        assert_ne!(StructuringElement::disc(1), StructuringElement::square(1));
+
+        #[rustfmt::skip]
+        let cross = [
+            false, true, false,
+            true, true, true,
+            false, true, false,
+        ];
+        assert_eq!(StructuringElement::disc(1).data(), &cross);
+    }
+
+    #[test]
+    fn invalid_element() {
+        assert!(StructuringElement::new(Size::new(2, 2), vec![true; 3]).is_err());
+        assert!(StructuringElement::new(Size::new(2, 1), vec![false; 2]).is_err());
+        assert!(StructuringElement::new(Size::new(0, 0), vec![]).is_err());
+
+        let img = Image::from_const(Size::new(3, 3), 0.5);
+        let result = morphology(img.view(), Operation::Erode, &StructuringElement::square(4));
+        assert_eq!(result.data(), img.data());
+    }
+}
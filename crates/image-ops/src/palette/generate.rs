@@ -0,0 +1,435 @@
+use ahash::AHashMap;
+use glam::Vec3;
+use image_core::{
+    util::{slice_as_chunks, vec_into_flattened},
+    NDimImage, NDimView, Shape,
+};
+
+use crate::dither::{CieLab, ColorSpace, LinearRGB, OkLab};
+
+use super::{kmeans::k_means, median_cut::median_cut, octree::octree, sort_palette, wu::wu};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PaletteError {
+    /// A palette must have at least one color.
+    ZeroColors,
+    UnsupportedChannels {
+        channels: usize,
+    },
+}
+
+/// The algorithm used to generate a palette.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PaletteAlgorithm {
+    /// Repeatedly splits the box of colors with the largest error at the
+    /// median of its longest axis.
+    MedianCut,
+    /// Merges the least common colors of an octree (a tree with `2^C`
+    /// children per node for `C` channels) until few enough remain.
+    Octree,
+    /// Xiaolin Wu's variance-minimizing quantizer. Colors are binned into a
+    /// histogram, so it's fast, but very close colors can't be separated.
+    Wu,
+    /// K-means clustering with k-means++ initialization. The slowest, but
+    /// usually the most accurate algorithm. The same seed always produces the
+    /// same palette.
+    KMeans { seed: u64 },
+}
+
+/// The color space in which colors are compared while generating a palette.
+///
+/// The colors of the palette are always averages of the colors of the image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum PaletteSpace {
+    #[default]
+    Rgb,
+    LinearRgb,
+    OkLab,
+    CieLab,
+}
+
+impl PaletteSpace {
+    fn gray(self, value: f32) -> f32 {
+        match self {
+            PaletteSpace::Rgb => value,
+            PaletteSpace::LinearRgb => LinearRGB.get_coordinate(value)[0],
+            PaletteSpace::OkLab => OkLab.get_coordinate(value)[0],
+            PaletteSpace::CieLab => CieLab::default().get_coordinate(value)[0],
+        }
+    }
+    fn rgb(self, rgb: [f32; 3]) -> [f32; 3] {
+        let rgb = Vec3::from(rgb);
+        match self {
+            PaletteSpace::Rgb => rgb.into(),
+            PaletteSpace::LinearRgb => LinearRGB.get_coordinate(rgb),
+            PaletteSpace::OkLab => OkLab.get_coordinate(rgb),
+            PaletteSpace::CieLab => CieLab::default().get_coordinate(rgb),
+        }
+    }
+    /// The scale of alpha relative to the lightness of the color space.
+    fn alpha_scale(self) -> f32 {
+        match self {
+            PaletteSpace::CieLab => 100.0,
+            _ => 1.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PaletteOptions {
+    pub color_space: PaletteSpace,
+    /// Whether the last channel of images with 2 or 4 channels is alpha.
+    /// Defaults to `true`.
+    ///
+    /// Colors are then premultiplied with alpha for comparisons, so that
+    /// (almost) transparent colors are close to each other.
+    pub alpha: bool,
+}
+
+impl Default for PaletteOptions {
+    fn default() -> Self {
+        Self {
+            color_space: PaletteSpace::default(),
+            alpha: true,
+        }
+    }
+}
+
+impl PaletteOptions {
+    fn coordinate<const N: usize>(&self, color: [f32; N]) -> [f32; N] {
+        let alpha = self.alpha && (N == 2 || N == 4);
+        let color_channels = if alpha { N - 1 } else { N };
+
+        let mut coord = color;
+        match color_channels {
+            1 => coord[0] = self.color_space.gray(color[0]),
+            3 => coord[..3].copy_from_slice(&self.color_space.rgb([color[0], color[1], color[2]])),
+            _ => {}
+        }
+        if alpha {
+            let a = color[N - 1];
+            coord[..N - 1].iter_mut().for_each(|c| *c *= a);
+            coord[N - 1] = a * self.color_space.alpha_scale();
+        }
+        coord
+    }
+}
+
+/// A unique color of an image with the number of pixels it has.
+#[derive(Debug, Clone, Copy)]
+pub(super) struct Entry<const N: usize> {
+    /// The coordinate of the color in the color space of the palette.
+    pub coord: [f32; N],
+    pub color: [f32; N],
+    pub weight: f32,
+}
+
+/// Computes the weighted mean of colors.
+#[derive(Debug, Clone, Copy)]
+pub(super) struct ColorSum<const N: usize> {
+    pub sum: [f64; N],
+    pub weight: f64,
+}
+impl<const N: usize> Default for ColorSum<N> {
+    fn default() -> Self {
+        Self {
+            sum: [0.0; N],
+            weight: 0.0,
+        }
+    }
+}
+impl<const N: usize> ColorSum<N> {
+    pub fn add(&mut self, color: [f32; N], weight: f32) {
+        for (s, c) in self.sum.iter_mut().zip(color) {
+            *s += c as f64 * weight as f64;
+        }
+        self.weight += weight as f64;
+    }
+    pub fn add_sum(&mut self, other: &Self) {
+        for (s, o) in self.sum.iter_mut().zip(other.sum) {
+            *s += o;
+        }
+        self.weight += other.weight;
+    }
+    pub fn mean(&self) -> [f32; N] {
+        self.sum.map(|s| (s / self.weight) as f32)
+    }
+}
+
+/// Returns the minimum and maximum of each coordinate.
+pub(super) fn bounds<const N: usize>(entries: &[Entry<N>]) -> ([f32; N], [f32; N]) {
+    let mut min = [f32::INFINITY; N];
+    let mut max = [f32::NEG_INFINITY; N];
+    for e in entries {
+        for ((min, max), c) in min.iter_mut().zip(max.iter_mut()).zip(e.coord) {
+            *min = min.min(c);
+            *max = max.max(c);
+        }
+    }
+    (min, max)
+}
+
+/// Generates a palette of at most `max_colors` colors that represents the
+/// given colors.
+///
+/// If there are at most `max_colors` unique colors, all algorithms return
+/// exactly the unique colors, even if some of them have the same coordinates
+/// (e.g. fully transparent colors with `alpha`). The palette is sorted like
+/// the palettes of [`super::extract_unique_const`].
+pub fn generate_palette_const<const N: usize>(
+    src: impl IntoIterator<Item = [f32; N]>,
+    max_colors: usize,
+    algorithm: PaletteAlgorithm,
+    options: PaletteOptions,
+) -> Result<Vec<[f32; N]>, PaletteError> {
+    assert_ne!(N, 0);
+
+    if max_colors == 0 {
+        return Err(PaletteError::ZeroColors);
+    }
+
+    let mut counts: AHashMap<[u32; N], f32> = AHashMap::new();
+    for color in src {
+        *counts.entry(color.map(f32::to_bits)).or_default() += 1.0;
+    }
+    let mut counts: Vec<([u32; N], f32)> = counts.into_iter().collect();
+    // the order of the hash map is random
+    counts.sort_unstable_by_key(|a| a.0);
+
+    let mut entries: Vec<Entry<N>> = counts
+        .into_iter()
+        .map(|(color, weight)| {
+            let color = color.map(f32::from_bits);
+            Entry {
+                coord: options.coordinate(color),
+                color,
+                weight,
+            }
+        })
+        .collect();
+    if entries.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut palette = if entries.len() <= max_colors {
+        entries.iter().map(|e| e.color).collect()
+    } else {
+        match algorithm {
+            PaletteAlgorithm::MedianCut => median_cut(&mut entries, max_colors),
+            PaletteAlgorithm::Octree => octree(&entries, max_colors),
+            PaletteAlgorithm::Wu => wu(&entries, max_colors),
+            PaletteAlgorithm::KMeans { seed } => k_means(&entries, max_colors, seed),
+        }
+    };
+    sort_palette(&mut palette);
+
+    Ok(palette)
+}
+
+/// Generates a palette for the given image and returns it as an image with a
+/// height of 1.
+pub fn generate_palette_ndim(
+    src: NDimView,
+    max_colors: usize,
+    algorithm: PaletteAlgorithm,
+    options: PaletteOptions,
+) -> Result<NDimImage, PaletteError> {
+    fn generate<const N: usize>(
+        src: NDimView,
+        max_colors: usize,
+        algorithm: PaletteAlgorithm,
+        options: PaletteOptions,
+    ) -> Result<NDimImage, PaletteError> {
+        let pixels = src.rows().flat_map(|row| {
+            let (pixels, rest) = slice_as_chunks::<f32, N>(row);
+            debug_assert!(rest.is_empty());
+            pixels.iter().copied()
+        });
+
+        let colors = generate_palette_const(pixels, max_colors, algorithm, options)?;
+
+        let shape = Shape::new(colors.len(), 1, N);
+        let data = vec_into_flattened(colors);
+        Ok(NDimImage::new(shape, data))
+    }
+
+    match src.channels() {
+        1 => generate::<1>(src, max_colors, algorithm, options),
+        2 => generate::<2>(src, max_colors, algorithm, options),
+        3 => generate::<3>(src, max_colors, algorithm, options),
+        4 => generate::<4>(src, max_colors, algorithm, options),
+        _ => Err(PaletteError::UnsupportedChannels {
+            channels: src.channels(),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::Vec3A;
+    use test_util::data::{read_flower, read_flower_palette, read_flower_transparent};
+
+    use image_core::{Image, Rect};
+
+    use crate::dither::{quantize, BoundError, ChannelQuantization, ColorPalette, RGB};
+
+    use super::*;
+
+    const ALGORITHMS: [PaletteAlgorithm; 4] = [
+        PaletteAlgorithm::MedianCut,
+        PaletteAlgorithm::Octree,
+        PaletteAlgorithm::Wu,
+        PaletteAlgorithm::KMeans { seed: 0 },
+    ];
+
+    // the full images are too slow in debug builds
+    fn flower() -> Image<Vec3A> {
+        read_flower().crop(Rect::new(250, 300, 200, 200))
+    }
+
+    fn mean_error(img: &Image<Vec3A>) -> f32 {
+        let original = flower();
+        let error: f32 = img
+            .data()
+            .iter()
+            .zip(original.data())
+            .map(|(a, b)| (*a - *b).length_squared())
+            .sum();
+        error / img.len() as f32
+    }
+    fn quantization_error(palette: &NDimImage) -> f32 {
+        let colors = palette
+            .data()
+            .chunks_exact(3)
+            .map(|c| Vec3A::new(c[0], c[1], c[2]));
+        let mut img = flower();
+        quantize(img.view_mut(), &ColorPalette::new(RGB, colors, BoundError));
+        mean_error(&img)
+    }
+
+    #[test]
+    fn generate_palettes() {
+        let img: NDimImage = flower().into();
+
+        // 27 colors
+        let mut uniform = flower();
+        quantize(uniform.view_mut(), &ChannelQuantization::new(3));
+        let uniform_error = mean_error(&uniform);
+
+        for algorithm in ALGORITHMS {
+            for color_space in [PaletteSpace::Rgb, PaletteSpace::OkLab, PaletteSpace::CieLab] {
+                let options = PaletteOptions {
+                    color_space,
+                    alpha: false,
+                };
+                let palette = generate_palette_ndim(img.view(), 16, algorithm, options).unwrap();
+                assert_eq!(palette.height(), 1);
+                assert_eq!(palette.channels(), 3);
+                assert!(
+                    palette.width() <= 16 && palette.width() >= 8,
+                    "{algorithm:?}"
+                );
+
+                let error = quantization_error(&palette);
+                assert!(
+                    error < uniform_error,
+                    "{algorithm:?} {color_space:?}: {error} >= {uniform_error}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn generate_exact_palettes() {
+        let img: NDimImage = read_flower_palette().into();
+        let unique = crate::palette::extract_unique_ndim(img.view(), usize::MAX).unwrap();
+
+        for algorithm in ALGORITHMS {
+            let palette = generate_palette_ndim(
+                img.view(),
+                unique.width(),
+                algorithm,
+                PaletteOptions::default(),
+            )
+            .unwrap();
+            // colors with the same luminance may be sorted differently
+            let sorted = |img: &NDimImage| {
+                let mut colors: Vec<[u32; 3]> = img
+                    .data()
+                    .chunks_exact(3)
+                    .map(|c| [c[0].to_bits(), c[1].to_bits(), c[2].to_bits()])
+                    .collect();
+                colors.sort_unstable();
+                colors
+            };
+            assert_eq!(sorted(&palette), sorted(&unique), "{algorithm:?}");
+        }
+    }
+
+    #[test]
+    fn generate_exact_transparent_palettes() {
+        // all transparent colors have the same premultiplied coordinates
+        let colors = [
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.5, 0.5, 0.5, 1.0],
+        ];
+        for algorithm in ALGORITHMS {
+            let mut palette =
+                generate_palette_const(colors, 4, algorithm, PaletteOptions::default()).unwrap();
+            palette.sort_by(|a, b| a.partial_cmp(b).unwrap());
+            let mut expected = colors.to_vec();
+            expected.sort_by(|a, b| a.partial_cmp(b).unwrap());
+            assert_eq!(palette, expected, "{algorithm:?}");
+        }
+    }
+
+    #[test]
+    fn generate_alpha_palettes() {
+        let img: NDimImage = read_flower_transparent()
+            .crop(Rect::new(250, 300, 200, 200))
+            .into();
+        let options = PaletteOptions {
+            color_space: PaletteSpace::OkLab,
+            alpha: true,
+        };
+
+        for algorithm in ALGORITHMS {
+            let palette = generate_palette_ndim(img.view(), 8, algorithm, options).unwrap();
+            assert_eq!(palette.channels(), 4);
+            assert!(palette.width() <= 8);
+            assert!(palette.data().iter().all(|c| (0.0..=1.0).contains(c)));
+        }
+    }
+
+    #[test]
+    fn k_means_is_deterministic() {
+        let img: NDimImage = flower().into();
+        let generate = |seed| {
+            generate_palette_ndim(
+                img.view(),
+                8,
+                PaletteAlgorithm::KMeans { seed },
+                PaletteOptions::default(),
+            )
+            .unwrap()
+        };
+
+        assert_eq!(generate(3).data(), generate(3).data());
+    }
+
+    #[test]
+    fn invalid_arguments() {
+        let img: NDimImage = flower().into();
+        assert!(matches!(
+            generate_palette_ndim(
+                img.view(),
+                0,
+                PaletteAlgorithm::Wu,
+                PaletteOptions::default()
+            ),
+            Err(PaletteError::ZeroColors)
+        ));
+    }
+}
@@ -0,0 +1,136 @@
+use rayon::prelude::*;
+
+use crate::util::Random;
+
+use super::generate::{ColorSum, Entry};
+
+const MAX_ITERATIONS: usize = 50;
+
+fn distance_2<const N: usize>(a: &[f32; N], b: &[f32; N]) -> f32 {
+    a.iter().zip(b).map(|(a, b)| (a - b) * (a - b)).sum()
+}
+
+/// Picks an entry with a probability proportional to its weight times its
+/// given factor. Entries with a factor of 0 are never picked.
+fn pick_weighted<const N: usize>(
+    entries: &[Entry<N>],
+    factors: impl Fn(usize) -> f32,
+    random: &mut Random,
+) -> Option<usize> {
+    let total: f64 = (0..entries.len())
+        .map(|i| entries[i].weight as f64 * factors(i) as f64)
+        .sum();
+    if total <= 0.0 {
+        return None;
+    }
+
+    let target = random.next_f64() * total;
+    let mut sum = 0.0;
+    let mut last = None;
+    for (i, e) in entries.iter().enumerate() {
+        let p = e.weight as f64 * factors(i) as f64;
+        if p > 0.0 {
+            sum += p;
+            last = Some(i);
+            if sum > target {
+                break;
+            }
+        }
+    }
+    last
+}
+
+/// Chooses the initial centers with k-means++.
+fn initial_centers<const N: usize>(
+    entries: &[Entry<N>],
+    k: usize,
+    random: &mut Random,
+) -> Vec<[f32; N]> {
+    let mut centers = Vec::with_capacity(k);
+    let Some(first) = pick_weighted(entries, |_| 1.0, random) else {
+        return centers;
+    };
+    centers.push(entries[first].coord);
+
+    let mut nearest: Vec<f32> = entries
+        .iter()
+        .map(|e| distance_2(&e.coord, &centers[0]))
+        .collect();
+    while centers.len() < k {
+        // stops once all colors are centers
+        let Some(next) = pick_weighted(entries, |i| nearest[i], random) else {
+            break;
+        };
+        let center = entries[next].coord;
+        centers.push(center);
+
+        for (n, e) in nearest.iter_mut().zip(entries) {
+            *n = n.min(distance_2(&e.coord, &center));
+        }
+    }
+    centers
+}
+
+/// Assigns each entry to its nearest center and returns whether any
+/// assignment changed.
+fn assign<const N: usize>(
+    entries: &[Entry<N>],
+    centers: &[[f32; N]],
+    assignments: &mut [usize],
+) -> bool {
+    assignments
+        .par_iter_mut()
+        .zip(entries)
+        .map(|(assignment, e)| {
+            let mut nearest = 0;
+            let mut nearest_dist = f32::INFINITY;
+            for (i, c) in centers.iter().enumerate() {
+                let dist = distance_2(&e.coord, c);
+                if dist < nearest_dist {
+                    nearest = i;
+                    nearest_dist = dist;
+                }
+            }
+            let changed = *assignment != nearest;
+            *assignment = nearest;
+            changed
+        })
+        .reduce(|| false, |a, b| a || b)
+}
+
+pub(super) fn k_means<const N: usize>(
+    entries: &[Entry<N>],
+    max_colors: usize,
+    seed: u64,
+) -> Vec<[f32; N]> {
+    let mut random = Random::new(seed);
+    let mut centers = initial_centers(entries, max_colors.min(entries.len()), &mut random);
+
+    let mut assignments = vec![usize::MAX; entries.len()];
+    assign(entries, &centers, &mut assignments);
+    for _ in 0..MAX_ITERATIONS {
+        let mut sums = vec![ColorSum::<N>::default(); centers.len()];
+        for (e, &a) in entries.iter().zip(&assignments) {
+            sums[a].add(e.coord, e.weight);
+        }
+        for (center, sum) in centers.iter_mut().zip(&sums) {
+            // empty clusters keep their center
+            if sum.weight > 0.0 {
+                *center = sum.mean();
+            }
+        }
+
+        if !assign(entries, &centers, &mut assignments) {
+            break;
+        }
+    }
+
+    let mut sums = vec![ColorSum::<N>::default(); centers.len()];
+    for (e, &a) in entries.iter().zip(&assignments) {
+        sums[a].add(e.color, e.weight);
+    }
+    sums.iter()
+        .filter(|s| s.weight > 0.0)
+        .map(|s| s.mean())
+        .collect()
+}
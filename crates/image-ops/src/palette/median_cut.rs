@@ -0,0 +1,92 @@
+use super::generate::{ColorSum, Entry};
+
+#[derive(Debug, Clone, Copy)]
+struct Cluster {
+    start: usize,
+    end: usize,
+    /// The sum of the squared distances of all colors to the mean.
+    error: f64,
+}
+
+impl Cluster {
+    fn new<const N: usize>(entries: &[Entry<N>], start: usize, end: usize) -> Self {
+        let mut weight = 0.0;
+        let mut sum = [0.0; N];
+        let mut sum_2 = 0.0;
+        for e in &entries[start..end] {
+            let w = e.weight as f64;
+            weight += w;
+            for (s, c) in sum.iter_mut().zip(e.coord) {
+                *s += c as f64 * w;
+                sum_2 += (c as f64) * (c as f64) * w;
+            }
+        }
+        let error = sum_2 - sum.iter().map(|s| s * s).sum::<f64>() / weight;
+
+        Self { start, end, error }
+    }
+}
+
+pub(super) fn median_cut<const N: usize>(
+    entries: &mut [Entry<N>],
+    max_colors: usize,
+) -> Vec<[f32; N]> {
+    let mut clusters = vec![Cluster::new(entries, 0, entries.len())];
+
+    while clusters.len() < max_colors {
+        let Some(index) = clusters
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| c.end - c.start > 1 && c.error > 0.0)
+            .max_by(|(_, a), (_, b)| a.error.total_cmp(&b.error))
+            .map(|(i, _)| i)
+        else {
+            break;
+        };
+
+        let cluster = clusters[index];
+        let colors = &mut entries[cluster.start..cluster.end];
+
+        // split along the longest axis
+        let range = |axis: usize| {
+            let (min, max) = colors
+                .iter()
+                .map(|e| e.coord[axis])
+                .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), c| {
+                    (min.min(c), max.max(c))
+                });
+            max - min
+        };
+        let axis = (0..N)
+            .max_by(|a, b| range(*a).total_cmp(&range(*b)))
+            .unwrap_or(0);
+        colors.sort_by(|a, b| a.coord[axis].total_cmp(&b.coord[axis]));
+
+        // at the weighted median
+        let half = colors.iter().map(|e| e.weight as f64).sum::<f64>() / 2.0;
+        let mut weight = 0.0;
+        let mut split = colors.len() - 1;
+        for (i, e) in colors.iter().enumerate() {
+            weight += e.weight as f64;
+            if weight >= half {
+                split = i + 1;
+                break;
+            }
+        }
+        let split = cluster.start + split.clamp(1, colors.len() - 1);
+
+        clusters[index] = Cluster::new(entries, cluster.start, split);
+        clusters.push(Cluster::new(entries, split, cluster.end));
+    }
+
+    clusters
+        .iter()
+        .map(|c| {
+            let mut sum = ColorSum::default();
+            for e in &entries[c.start..c.end] {
+                sum.add(e.color, e.weight);
+            }
+            sum.mean()
+        })
+        .collect()
+}
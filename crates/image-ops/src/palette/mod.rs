@@ -1,3 +1,11 @@
+mod generate;
+mod kmeans;
+mod median_cut;
+mod octree;
+mod wu;
+
+pub use generate::*;
+
 use ahash::AHashSet;
 use image_core::{
     util::{slice_as_chunks, vec_into_flattened},
@@ -35,23 +43,28 @@ pub fn extract_unique_const<const N: usize>(
     }
 
     let mut vec: Vec<[f32; N]> = set.into_iter().map(|p| p.map(f32::from_bits)).collect();
+    sort_palette(&mut vec);
 
+    Ok(vec)
+}
+
+/// Sorts the colors of a palette by luminance, and by alpha first for RGBA
+/// colors.
+fn sort_palette<const N: usize>(vec: &mut [[f32; N]]) {
     fn luminance(r: f32, g: f32, b: f32) -> f32 {
         // Since the color values are likely sRGB, we will approximate 2.2 gamma by squaring the values.
         r * r * 0.2126 + g * g * 0.7152 + b * b * 0.0722
     }
 
     match N {
-        1 => sort_colors(&mut vec, |f| f[0]),
-        3 => sort_colors(&mut vec, |f| luminance(f[0], f[1], f[2])),
-        4 => sort_colors(&mut vec, |f| {
+        1 => sort_colors(vec, |f| f[0]),
+        3 => sort_colors(vec, |f| luminance(f[0], f[1], f[2])),
+        4 => sort_colors(vec, |f| {
             // we want values to sorted by alpha first, so we give it a large weight
             luminance(f[0], f[1], f[2]) + f[3] * 10.0
         }),
-        _ => sort_colors(&mut vec, |f| f.iter().sum()),
+        _ => sort_colors(vec, |f| f.iter().sum()),
     }
-
-    Ok(vec)
 }
 
 pub fn extract_unique_ndim_const<const N: usize>(
@@ -66,10 +79,13 @@ pub fn extract_unique_ndim_const<const N: usize>(
         });
     }
 
-    let (pixels, rest) = slice_as_chunks::<f32, N>(src.data());
-    assert!(rest.is_empty());
+    let pixels = src.rows().flat_map(|row| {
+        let (pixels, rest) = slice_as_chunks::<f32, N>(row);
+        assert!(rest.is_empty());
+        pixels.iter().copied()
+    });
 
-    extract_unique_const(pixels.iter().copied(), max_colors)
+    extract_unique_const(pixels, max_colors)
 }
 
 pub fn extract_unique_ndim(src: NDimView, max_colors: usize) -> Result<NDimImage, ExtractionError> {
@@ -0,0 +1,160 @@
+use super::generate::{bounds, ColorSum, Entry};
+
+/// The number of levels below the root. Each level halves the size of a node
+/// along every axis.
+const DEPTH: usize = 6;
+
+#[derive(Debug, Clone)]
+struct Node<const N: usize> {
+    level: usize,
+    /// The number of pixels of all colors in the subtree.
+    weight: f64,
+    /// The colors of a leaf.
+    sum: ColorSum<N>,
+    /// The index of the first of the `2^N` children in the child list.
+    /// `None` for leaves.
+    children: Option<usize>,
+}
+
+struct Octree<const N: usize> {
+    nodes: Vec<Node<N>>,
+    /// The node index of each child. 0 means that there is no child.
+    children: Vec<usize>,
+    leaves: usize,
+}
+
+impl<const N: usize> Octree<N> {
+    fn new() -> Self {
+        let mut tree = Self {
+            nodes: Vec::new(),
+            children: Vec::new(),
+            leaves: 0,
+        };
+        tree.add_node(0);
+        tree
+    }
+
+    fn add_node(&mut self, level: usize) -> usize {
+        let children = if level < DEPTH {
+            let start = self.children.len();
+            self.children.resize(start + (1 << N), 0);
+            Some(start)
+        } else {
+            self.leaves += 1;
+            None
+        };
+        self.nodes.push(Node {
+            level,
+            weight: 0.0,
+            sum: ColorSum::default(),
+            children,
+        });
+        self.nodes.len() - 1
+    }
+
+    /// Adds the color at the given grid position of the deepest level.
+    fn insert(&mut self, position: [usize; N], color: [f32; N], weight: f32) {
+        let mut node = 0;
+        for level in 0..DEPTH {
+            self.nodes[node].weight += weight as f64;
+
+            let shift = DEPTH - 1 - level;
+            let child: usize = position
+                .iter()
+                .enumerate()
+                .map(|(i, p)| ((p >> shift) & 1) << i)
+                .sum();
+            let slot = self.nodes[node].children.unwrap() + child;
+            if self.children[slot] == 0 {
+                self.children[slot] = self.add_node(level + 1);
+            }
+            node = self.children[slot];
+        }
+        self.nodes[node].weight += weight as f64;
+        self.nodes[node].sum.add(color, weight);
+    }
+
+    fn child_nodes(&self, node: usize) -> impl Iterator<Item = usize> + '_ {
+        let start = self.nodes[node].children.unwrap_or(0);
+        let len = if self.nodes[node].children.is_some() {
+            1 << N
+        } else {
+            0
+        };
+        self.children[start..start + len]
+            .iter()
+            .copied()
+            .filter(|&c| c != 0)
+    }
+
+    /// Turns the given node, whose children must all be leaves, into a leaf.
+    fn merge(&mut self, node: usize) {
+        let mut sum = ColorSum::default();
+        let mut count = 0;
+        for child in self.child_nodes(node) {
+            sum.add_sum(&self.nodes[child].sum);
+            count += 1;
+        }
+        self.nodes[node].sum = sum;
+        self.nodes[node].children = None;
+        self.leaves = self.leaves + 1 - count;
+    }
+
+    /// Merges the nodes with the fewest pixels, starting at the deepest
+    /// level, until there are at most `max_leaves` leaves.
+    fn reduce(&mut self, max_leaves: usize) {
+        for level in (0..DEPTH).rev() {
+            if self.leaves <= max_leaves {
+                return;
+            }
+
+            let mut candidates: Vec<usize> = (0..self.nodes.len())
+                .filter(|&i| self.nodes[i].level == level && self.nodes[i].children.is_some())
+                .collect();
+            candidates.sort_by(|a, b| self.nodes[*a].weight.total_cmp(&self.nodes[*b].weight));
+
+            for node in candidates {
+                if self.leaves <= max_leaves {
+                    return;
+                }
+                self.merge(node);
+            }
+        }
+    }
+
+    fn leaf_colors(&self) -> Vec<[f32; N]> {
+        let mut colors = Vec::with_capacity(self.leaves);
+        let mut stack = vec![0];
+        while let Some(node) = stack.pop() {
+            if self.nodes[node].children.is_some() {
+                stack.extend(self.child_nodes(node));
+            } else if self.nodes[node].sum.weight > 0.0 {
+                colors.push(self.nodes[node].sum.mean());
+            }
+        }
+        colors
+    }
+}
+
+pub(super) fn octree<const N: usize>(entries: &[Entry<N>], max_colors: usize) -> Vec<[f32; N]> {
+    let (min, max) = bounds(entries);
+    let cells = (1 << DEPTH) as f32;
+
+    let mut tree = Octree::<N>::new();
+    for e in entries {
+        let mut position = [0; N];
+        for (i, p) in position.iter_mut().enumerate() {
+            let range = max[i] - min[i];
+            let t = if range > 0.0 {
+                (e.coord[i] - min[i]) / range
+            } else {
+                0.0
+            };
+            *p = ((t * cells) as usize).min((1 << DEPTH) - 1);
+        }
+        tree.insert(position, e.color, e.weight);
+    }
+
+    tree.reduce(max_colors);
+    tree.leaf_colors()
+}
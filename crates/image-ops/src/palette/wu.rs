@@ -0,0 +1,215 @@
+use super::generate::{bounds, Entry};
+
+/// The moments of the colors in a region of the histogram.
+#[derive(Debug, Clone, Copy)]
+struct Moments<const N: usize> {
+    weight: f64,
+    /// The weighted sum of the coordinates.
+    coord: [f64; N],
+    /// The weighted sum of the squared lengths of the coordinates.
+    coord_2: f64,
+    /// The weighted sum of the colors.
+    color: [f64; N],
+}
+
+impl<const N: usize> Default for Moments<N> {
+    fn default() -> Self {
+        Self {
+            weight: 0.0,
+            coord: [0.0; N],
+            coord_2: 0.0,
+            color: [0.0; N],
+        }
+    }
+}
+
+impl<const N: usize> Moments<N> {
+    fn add(&mut self, other: &Self, sign: f64) {
+        self.weight += other.weight * sign;
+        for (a, b) in self.coord.iter_mut().zip(other.coord) {
+            *a += b * sign;
+        }
+        self.coord_2 += other.coord_2 * sign;
+        for (a, b) in self.color.iter_mut().zip(other.color) {
+            *a += b * sign;
+        }
+    }
+    fn sub(mut self, other: &Self) -> Self {
+        self.add(other, -1.0);
+        self
+    }
+
+    /// The squared length of the weighted mean times the weight.
+    fn mean_2(&self) -> f64 {
+        self.coord.iter().map(|c| c * c).sum::<f64>() / self.weight
+    }
+    /// The sum of the squared distances of all colors to the mean.
+    fn variance(&self) -> f64 {
+        if self.weight > 0.0 {
+            self.coord_2 - self.mean_2()
+        } else {
+            0.0
+        }
+    }
+}
+
+/// A box of histogram cells. The box contains cells `lower + 1..=upper` along
+/// each axis.
+#[derive(Debug, Clone, Copy)]
+struct Cube<const N: usize> {
+    lower: [usize; N],
+    upper: [usize; N],
+}
+
+/// A histogram with cumulative moments, so that the moments of any box can
+/// be computed from its `2^N` corners.
+struct Histogram<const N: usize> {
+    /// The number of cells along each axis, plus 1 for the leading zero
+    /// cells.
+    side: usize,
+    moments: Vec<Moments<N>>,
+}
+
+impl<const N: usize> Histogram<N> {
+    fn new(entries: &[Entry<N>], bins: usize) -> Self {
+        let side = bins + 1;
+        let mut moments = vec![Moments::default(); side.pow(N as u32)];
+
+        let (min, max) = bounds(entries);
+        for e in entries {
+            let mut index = 0;
+            for i in (0..N).rev() {
+                let range = max[i] - min[i];
+                let t = if range > 0.0 {
+                    (e.coord[i] - min[i]) / range
+                } else {
+                    0.0
+                };
+                let bin = ((t * bins as f32) as usize).min(bins - 1);
+                index = index * side + bin + 1;
+            }
+
+            let w = e.weight as f64;
+            let m = &mut moments[index];
+            m.weight += w;
+            for (a, c) in m.coord.iter_mut().zip(e.coord) {
+                *a += c as f64 * w;
+                m.coord_2 += (c as f64) * (c as f64) * w;
+            }
+            for (a, c) in m.color.iter_mut().zip(e.color) {
+                *a += c as f64 * w;
+            }
+        }
+
+        // cumulative sums along each axis
+        for axis in 0..N {
+            let stride = side.pow(axis as u32);
+            for index in 0..moments.len() {
+                if (index / stride) % side > 0 {
+                    let prev = moments[index - stride];
+                    moments[index].add(&prev, 1.0);
+                }
+            }
+        }
+
+        Self { side, moments }
+    }
+
+    fn volume(&self, cube: &Cube<N>) -> Moments<N> {
+        let mut result = Moments::default();
+        for corner in 0..(1_usize << N) {
+            let mut index = 0;
+            let mut sign = 1.0;
+            for i in (0..N).rev() {
+                let position = if corner & (1 << i) != 0 {
+                    sign = -sign;
+                    cube.lower[i]
+                } else {
+                    cube.upper[i]
+                };
+                index = index * self.side + position;
+            }
+            result.add(&self.moments[index], sign);
+        }
+        result
+    }
+
+    /// Returns the best position to cut the given cube along any axis.
+    fn best_cut(&self, cube: &Cube<N>) -> Option<(usize, usize)> {
+        let whole = self.volume(cube);
+
+        let mut best: Option<(usize, usize)> = None;
+        let mut best_score = 0.0;
+        for axis in 0..N {
+            for cut in cube.lower[axis] + 1..cube.upper[axis] {
+                let mut half = *cube;
+                half.upper[axis] = cut;
+                let half = self.volume(&half);
+                let rest = whole.sub(&half);
+                if half.weight <= 0.0 || rest.weight <= 0.0 {
+                    continue;
+                }
+
+                // maximizing this minimizes the sum of the variances
+                let score = half.mean_2() + rest.mean_2();
+                if best.is_none() || score > best_score {
+                    best = Some((axis, cut));
+                    best_score = score;
+                }
+            }
+        }
+        best
+    }
+}
+
+pub(super) fn wu<const N: usize>(entries: &[Entry<N>], max_colors: usize) -> Vec<[f32; N]> {
+    let bins = match N {
+        1 => 256,
+        2 => 64,
+        3 => 32,
+        _ => 16,
+    };
+    let histogram = Histogram::new(entries, bins);
+
+    let mut cubes = vec![Cube {
+        lower: [0; N],
+        upper: [bins; N],
+    }];
+    let mut variances = vec![histogram.volume(&cubes[0]).variance()];
+
+    while cubes.len() < max_colors {
+        let Some(index) = variances
+            .iter()
+            .enumerate()
+            .filter(|(_, v)| **v > 0.0)
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(i, _)| i)
+        else {
+            break;
+        };
+
+        let cube = cubes[index];
+        let Some((axis, cut)) = histogram.best_cut(&cube) else {
+            // the cube can't be cut, so we don't try again
+            variances[index] = 0.0;
+            continue;
+        };
+
+        let mut lower = cube;
+        lower.upper[axis] = cut;
+        let mut upper = cube;
+        upper.lower[axis] = cut;
+
+        cubes[index] = lower;
+        variances[index] = histogram.volume(&lower).variance();
+        cubes.push(upper);
+        variances.push(histogram.volume(&upper).variance());
+    }
+
+    cubes
+        .iter()
+        .map(|cube| histogram.volume(cube))
+        .filter(|m| m.weight > 0.0)
+        .map(|m| m.color.map(|c| (c / m.weight) as f32))
+        .collect()
+}
@@ -12,6 +12,8 @@ pub enum Filter {
     Lanczos3,
     Lagrange,
     Gauss,
+    MagicKernelSharp2013,
+    MagicKernelSharp2021,
 }
 
 #[inline]
@@ -65,49 +67,115 @@ fn lagrange(x: f32, support: f32) -> f32 {
     value
 }
 
+// Magic Kernel Sharp, see
+// https://johncostella.com/magic/
+// Both kernels are the magic kernel convolved with their respective sharpening
+// filter ([-1/4, 3/2, -1/4] for 2013, a 7-tap filter for 2021), so the
+// sharpening step is already part of the piecewise polynomials below.
+fn magic_kernel_sharp_2013(x: f32) -> f32 {
+    let x = x.abs();
+    if x <= 0.5 {
+        17.0 / 16.0 - 7.0 / 4.0 * x * x
+    } else if x <= 1.5 {
+        (1.0 - x) * (7.0 / 4.0 - x)
+    } else if x <= 2.5 {
+        let t = x - 2.5;
+        -1.0 / 8.0 * t * t
+    } else {
+        0.0
+    }
+}
+
+fn magic_kernel_sharp_2021(x: f32) -> f32 {
+    let x = x.abs();
+    if x <= 0.5 {
+        577.0 / 576.0 - 239.0 / 144.0 * x * x
+    } else if x <= 1.5 {
+        (140.0 * x * x - 379.0 * x + 239.0) / 144.0
+    } else if x <= 2.5 {
+        -(24.0 * x * x - 113.0 * x + 130.0) / 144.0
+    } else if x <= 3.5 {
+        (4.0 * x * x - 27.0 * x + 45.0) / 144.0
+    } else if x <= 4.5 {
+        -(4.0 * x * x - 36.0 * x + 81.0) / 1152.0
+    } else {
+        0.0
+    }
+}
+
+#[inline]
+fn triangle(x: f32) -> f32 {
+    f32::max(1.0 - x.abs(), 0.0)
+}
+
+// Taken from
+// https://github.com/image-rs/image/blob/81b3fe66fba04b8b60ba79b3641826df22fca67e/src/imageops/sample.rs#L181
+#[inline(always)]
+fn gaussian(x: f32, r: f32) -> f32 {
+    ((2.0 * std::f32::consts::PI).sqrt() * r).recip() * (-x.powi(2) / (2.0 * r.powi(2))).exp()
+}
+
+#[inline(always)]
+fn lanczos(taps: f32, x: f32) -> f32 {
+    if x.abs() < taps {
+        let pi = std::f32::consts::PI;
+        sinc(x * pi) * sinc(x / taps * pi)
+    } else {
+        0.0
+    }
+}
+
+impl Filter {
+    /// Returns the kernel function of this filter and its support.
+    ///
+    /// Filters that `resize` has built-in support for use the same kernels as
+    /// `resize`, so both produce identical coefficients.
+    pub(crate) fn kernel(self) -> (Box<dyn Fn(f32) -> f32 + Send + Sync>, f32) {
+        match self {
+            Filter::Nearest => (Box::new(|_| 1.0), 0.0),
+            Filter::Box => (Box::new(|x| if x.abs() <= 0.5 { 1.0 } else { 0.0 }), 1.0),
+            Filter::Linear => (Box::new(triangle), 1.0),
+            Filter::Hermite => (Box::new(|x| cubic_bc(0.0, 0.0, x)), 1.0),
+            Filter::CubicCatrom => (Box::new(|x| cubic_bc(0.0, 0.5, x)), 2.0),
+            Filter::CubicMitchell => (Box::new(|x| cubic_bc(1.0 / 3.0, 1.0 / 3.0, x)), 2.0),
+            Filter::CubicBSpline => (Box::new(|x| cubic_bc(1.0, 0.0, x)), 2.0),
+            Filter::Hamming => (
+                Box::new(|x| {
+                    let x = x.abs() * std::f32::consts::PI;
+                    sinc(x) * (0.54 + 0.46 * x.cos())
+                }),
+                1.0,
+            ),
+            Filter::Hann => (
+                Box::new(|x| {
+                    let x = x.abs() * std::f32::consts::PI;
+                    sinc(x) * (0.5 + 0.5 * x.cos())
+                }),
+                1.0,
+            ),
+            Filter::Lanczos3 => (Box::new(|x| lanczos(3.0, x)), 3.0),
+            Filter::Lagrange => (Box::new(|x| lagrange(x, 2.0)), 2.0),
+            Filter::Gauss => (Box::new(|x| gaussian(x, 0.5)), 3.0),
+            Filter::MagicKernelSharp2013 => (Box::new(magic_kernel_sharp_2013), 2.5),
+            Filter::MagicKernelSharp2021 => (Box::new(magic_kernel_sharp_2021), 4.5),
+        }
+    }
+}
+
 impl From<Filter> for resize::Type {
     fn from(filter: Filter) -> Self {
         match filter {
             Filter::Nearest => resize::Type::Point,
-            Filter::Box => {
-                let filter =
-                    resize::Filter::new(Box::new(|x| if x.abs() <= 0.5 { 1.0 } else { 0.0 }), 1.0);
-                resize::Type::Custom(filter)
-            }
             Filter::Linear => resize::Type::Triangle,
-            Filter::Hermite => {
-                let filter = resize::Filter::new(Box::new(|x| cubic_bc(0.0, 0.0, x)), 1.0);
-                resize::Type::Custom(filter)
-            }
             Filter::CubicCatrom => resize::Type::Catrom,
             Filter::CubicMitchell => resize::Type::Mitchell,
             Filter::CubicBSpline => resize::Type::BSpline,
-            Filter::Hamming => {
-                let filter = resize::Filter::new(
-                    Box::new(|x| {
-                        let x = x.abs() * std::f32::consts::PI;
-                        sinc(x) * (0.54 + 0.46 * x.cos())
-                    }),
-                    1.0,
-                );
-                resize::Type::Custom(filter)
-            }
-            Filter::Hann => {
-                let filter = resize::Filter::new(
-                    Box::new(|x| {
-                        let x = x.abs() * std::f32::consts::PI;
-                        sinc(x) * (0.5 + 0.5 * x.cos())
-                    }),
-                    1.0,
-                );
-                resize::Type::Custom(filter)
-            }
             Filter::Lanczos3 => resize::Type::Lanczos3,
-            Filter::Lagrange => {
-                let filter = resize::Filter::new(Box::new(|x| lagrange(x, 2.0)), 2.0);
-                resize::Type::Custom(filter)
-            }
             Filter::Gauss => resize::Type::Gaussian,
+            _ => {
+                let (kernel, support) = filter.kernel();
+                resize::Type::Custom(resize::Filter::new(kernel, support))
+            }
         }
     }
 }
@@ -1,5 +1,6 @@
 mod filter;
 mod pixel_format;
+mod resample;
 #[allow(clippy::module_inception)]
 mod scale;
 
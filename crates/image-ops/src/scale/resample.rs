@@ -0,0 +1,229 @@
+use std::{collections::HashMap, sync::Arc};
+
+use image_core::{ImageView, Size};
+use rayon::prelude::*;
+
+use super::{Filter, PixelFormat, SourceRect};
+
+/// The weights of the source pixels `start..start + coeffs.len()` for a single
+/// destination pixel.
+#[derive(Debug, Clone)]
+struct CoeffsLine {
+    start: usize,
+    coeffs: Arc<[f32]>,
+}
+
+type CoeffsCache = HashMap<(usize, [u8; 4], [u8; 4]), Arc<[f32]>>;
+
+/// A separable resampler that maps a (possibly fractional) rectangle of the
+/// source image onto the whole destination image.
+///
+/// The coefficients are computed exactly like `resize` computes them, so
+/// mapping the whole source image with the same filter on both axes produces
+/// the same result as `resize`.
+#[derive(Debug, Clone)]
+pub(crate) struct Resampler {
+    src_size: Size,
+    coeffs_x: Vec<CoeffsLine>,
+    coeffs_y: Vec<CoeffsLine>,
+}
+
+impl Resampler {
+    pub fn new(
+        src_size: Size,
+        rect: SourceRect,
+        dst_size: Size,
+        filter_x: Filter,
+        filter_y: Filter,
+    ) -> Result<Self, resize::Error> {
+        if src_size.is_empty() || dst_size.is_empty() || !rect.is_valid() {
+            return Err(resize::Error::InvalidParameters);
+        }
+
+        // filters very often create repeating patterns, so coefficients can be
+        // reused between pixels (and axes)
+        let mut cache = CoeffsCache::new();
+        let coeffs_x = calc_coeffs(
+            src_size.width,
+            (rect.left, rect.right),
+            dst_size.width,
+            filter_x,
+            &mut cache,
+        )?;
+        if filter_x != filter_y {
+            cache.clear();
+        }
+        let coeffs_y = calc_coeffs(
+            src_size.height,
+            (rect.top, rect.bottom),
+            dst_size.height,
+            filter_y,
+            &mut cache,
+        )?;
+
+        Ok(Self {
+            src_size,
+            coeffs_x,
+            coeffs_y,
+        })
+    }
+
+    pub fn dst_size(&self) -> Size {
+        Size::new(self.coeffs_x.len(), self.coeffs_y.len())
+    }
+
+    pub fn resample<F: PixelFormat>(
+        &self,
+        format: &F,
+        src: ImageView<F::InputPixel>,
+        dst: &mut [F::OutputPixel],
+    ) -> Result<(), resize::Error> {
+        if src.size() != self.src_size || dst.len() != self.dst_size().len() {
+            return Err(resize::Error::InvalidParameters);
+        }
+
+        let w2 = self.coeffs_x.len();
+        let h2 = self.coeffs_y.len();
+
+        // only the source rows used by the vertical coefficients are needed
+        let row_start = self.coeffs_y.iter().map(|c| c.start).min().unwrap_or(0);
+        let row_end = self
+            .coeffs_y
+            .iter()
+            .map(|c| c.start + c.coeffs.len())
+            .max()
+            .unwrap_or(0);
+        let h1 = row_end - row_start;
+
+        let mut tmp: Vec<F::Accumulator> = Vec::new();
+        tmp.try_reserve_exact(w2 * h1)?;
+        tmp.resize(w2 * h1, F::new_acc());
+
+        // Both passes are split into rows that are processed in parallel. Each
+        // pixel is still computed by exactly the same operations in the same
+        // order, so the result doesn't depend on the number of threads.
+        // In tiny images, spawning tasks takes longer than the work itself, so
+        // every task gets some minimum amount of work.
+        let min_rows = |rows: usize| ((1 << 14) / (w2 * rows.max(w2))).max(rows / 256);
+
+        // resample W1xH1 to W2xH1
+        (row_start..row_end)
+            .into_par_iter()
+            .map(|y| src.row(y))
+            .zip(tmp.par_chunks_exact_mut(w2))
+            .with_min_len(min_rows(h1))
+            .for_each(|(row, tmp)| {
+                for (col, tmp) in self.coeffs_x.iter().zip(tmp) {
+                    let in_px = &row[col.start..col.start + col.coeffs.len()];
+
+                    let mut acc = F::new_acc();
+                    for (coeff, in_px) in col.coeffs.iter().copied().zip(in_px.iter().copied()) {
+                        format.add_pixel_scaled(&mut acc, in_px, coeff);
+                    }
+                    *tmp = acc;
+                }
+            });
+
+        // resample W2xH1 to W2xH2
+        let tmp = tmp.as_slice();
+        dst.par_chunks_exact_mut(w2)
+            .zip(self.coeffs_y.par_iter())
+            .with_min_len(min_rows(h2))
+            .for_each(|(dst, row)| {
+                let tmp_row_start = &tmp[w2 * (row.start - row_start)..];
+                for (x, dst_px) in dst.iter_mut().enumerate() {
+                    let mut acc = F::new_acc();
+                    for (coeff, other) in row
+                        .coeffs
+                        .iter()
+                        .copied()
+                        .zip(tmp_row_start.iter().copied().skip(x).step_by(w2))
+                    {
+                        F::add_acc_scaled(&mut acc, other, coeff);
+                    }
+                    *dst_px = format.acc_to_pixel(acc);
+                }
+            });
+
+        Ok(())
+    }
+}
+
+fn calc_coeffs(
+    src_len: usize,
+    (src_start, src_end): (f64, f64),
+    dst_len: usize,
+    filter: Filter,
+    cache: &mut CoeffsCache,
+) -> Result<Vec<CoeffsLine>, resize::Error> {
+    let ratio = (src_end - src_start) / dst_len as f64;
+    let max_index = src_len as isize - 1;
+
+    let mut res = Vec::new();
+    res.try_reserve_exact(dst_len)?;
+
+    if filter == Filter::Nearest {
+        // the pixel whose area contains the center of the destination pixel
+        let coeffs: Arc<[f32]> = Arc::new([1.0]);
+        for x2 in 0..dst_len {
+            let x1 = (src_start + (x2 as f64 + 0.5) * ratio).floor() as isize;
+            res.push(CoeffsLine {
+                start: x1.clamp(0, max_index) as usize,
+                coeffs: coeffs.clone(),
+            });
+        }
+        return Ok(res);
+    }
+
+    let (kernel, support) = filter.kernel();
+
+    // Scale the filter when downsampling.
+    let filter_scale = ratio.max(1.);
+    let filter_radius = (f64::from(support) * filter_scale).ceil();
+
+    cache.try_reserve(dst_len)?;
+    for x2 in 0..dst_len {
+        let x1 = src_start + (x2 as f64 + 0.5) * ratio - 0.5;
+        let start = (x1 - filter_radius).ceil() as isize;
+        let start = start.clamp(0, max_index) as usize;
+        let end = (x1 + filter_radius).floor() as isize;
+        let end = (end.clamp(0, max_index) as usize).max(start);
+
+        let sum: f64 = (start..=end)
+            .map(|i| f64::from(kernel(((i as f64 - x1) / filter_scale) as f32)))
+            .sum();
+
+        if sum == 0.0 {
+            // This only happens if the source range is (partially) outside of
+            // the image and all pixels in reach have a weight of 0.
+            let nearest = (x1.round() as isize).clamp(0, max_index) as usize;
+            res.push(CoeffsLine {
+                start: nearest,
+                coeffs: Arc::new([1.0]),
+            });
+            continue;
+        }
+
+        let key = (
+            end - start,
+            (filter_scale as f32).to_ne_bytes(),
+            (start as f32 - x1 as f32).to_ne_bytes(),
+        );
+        let coeffs = match cache.get(&key) {
+            Some(coeffs) => coeffs.clone(),
+            None => {
+                let coeffs: Arc<[f32]> = (start..=end)
+                    .map(|i| {
+                        let n = ((i as f64 - x1) / filter_scale) as f32;
+                        (f64::from(kernel(n.min(support).max(-support))) / sum) as f32
+                    })
+                    .collect();
+                cache.insert(key, coeffs.clone());
+                coeffs
+            }
+        };
+        res.push(CoeffsLine { start, coeffs });
+    }
+
+    Ok(res)
+}
@@ -1,6 +1,54 @@
-use image_core::{Image, ImageView, Size};
+use glam::Vec4;
+use image_core::{Image, ImageView, NDimImage, NDimView, Shape, Size};
+
+use crate::util::{read_channel_plane, set_channel_plane};
+
+use super::{resample::Resampler, Filter, FloatPixelFormat, PixelFormat};
+
+/// A rectangular region of a source image in pixel coordinates.
+///
+/// The edges may be fractional and may lie outside of the image. E.g. the
+/// rectangle `left: 10.25, right: 90.75` covers the right three quarters of
+/// pixel 10 up to and including the left three quarters of pixel 90.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SourceRect {
+    pub left: f64,
+    pub top: f64,
+    pub right: f64,
+    pub bottom: f64,
+}
 
-use super::{Filter, FloatPixelFormat, PixelFormat};
+impl SourceRect {
+    pub const fn new(left: f64, top: f64, right: f64, bottom: f64) -> Self {
+        Self {
+            left,
+            top,
+            right,
+            bottom,
+        }
+    }
+    /// The rectangle covering the whole image of the given size.
+    pub fn from_size(size: Size) -> Self {
+        Self::new(0.0, 0.0, size.width as f64, size.height as f64)
+    }
+
+    pub fn width(&self) -> f64 {
+        self.right - self.left
+    }
+    pub fn height(&self) -> f64 {
+        self.bottom - self.top
+    }
+
+    /// Returns whether all edges are finite and the rectangle is not empty.
+    pub fn is_valid(&self) -> bool {
+        self.left.is_finite()
+            && self.top.is_finite()
+            && self.right.is_finite()
+            && self.bottom.is_finite()
+            && self.width() > 0.0
+            && self.height() > 0.0
+    }
+}
 
 pub fn scale<P>(img: ImageView<P>, size: Size, filter: Filter) -> Result<Image<P>, resize::Error>
 where
@@ -11,36 +59,176 @@ where
         return Ok(Image::new(size, Vec::new()));
     }
 
-    let filter_type = match filter {
-        Filter::Nearest => {
-            // the nearest implementation isn't correct, so we use our own
-            return Ok(nearest_neighbor(img, size));
-        }
-        _ => filter.into(),
-    };
+    if filter == Filter::Nearest {
+        // the nearest implementation of `resize` isn't correct, so we use our own
+        return Ok(nearest_neighbor(img, size));
+    }
+
+    let rect = SourceRect::from_size(img.size());
+    let resampler = Resampler::new(img.size(), rect, size, filter, filter)?;
 
     let mut dest = Image::from_const(size, P::default());
+    resampler.resample(&FloatPixelFormat::default(), img, dest.data_mut())?;
+
+    Ok(dest)
+}
+
+/// Scales the given region of the source image to the given size.
+///
+/// Unlike [`scale`], this allows the source region to be fractional and
+/// different filters to be used horizontally and vertically. Filter taps that
+/// lie outside of the source image are ignored, and the weights of the
+/// remaining taps are renormalized to sum to 1. If none of them have any
+/// weight, the nearest pixel of the source image is used.
+pub fn scale_rect<P>(
+    img: ImageView<P>,
+    rect: SourceRect,
+    size: Size,
+    filter_x: Filter,
+    filter_y: Filter,
+) -> Result<Image<P>, resize::Error>
+where
+    P: Clone + Default,
+    FloatPixelFormat<P>: PixelFormat<InputPixel = P, OutputPixel = P>,
+{
+    if rect == SourceRect::from_size(img.size()) && filter_x == filter_y {
+        return scale(img, size, filter_x);
+    }
+
+    if size.is_empty() {
+        return Ok(Image::new(size, Vec::new()));
+    }
+
+    let resampler = Resampler::new(img.size(), rect, size, filter_x, filter_y)?;
+
+    let mut dest = Image::from_const(size, P::default());
+    resampler.resample(&FloatPixelFormat::default(), img, dest.data_mut())?;
+
+    Ok(dest)
+}
 
-    resize::Resizer::new(
-        img.width(),
-        img.height(),
-        size.width,
-        size.height,
-        FloatPixelFormat::default(),
-        filter_type,
-    )?
-    .resize(img.data(), dest.data_mut())?;
+/// Scales the given RGBA image with premultiplied alpha.
+///
+/// Colors are multiplied with their alpha before filtering and divided by the
+/// filtered alpha afterwards. This prevents fully transparent pixels from
+/// bleeding their (usually meaningless) color into visible edges. Pixels with
+/// a resulting alpha of zero or less will have their color set to zero.
+pub fn scale_premultiplied_alpha(
+    img: ImageView<Vec4>,
+    size: Size,
+    filter: Filter,
+) -> Result<Image<Vec4>, resize::Error> {
+    let rect = SourceRect::from_size(img.size());
+    scale_rect_premultiplied_alpha(img, rect, size, filter, filter)
+}
+
+/// Scales the given region of the RGBA image with premultiplied alpha.
+///
+/// See [`scale_rect`] and [`scale_premultiplied_alpha`].
+pub fn scale_rect_premultiplied_alpha(
+    img: ImageView<Vec4>,
+    rect: SourceRect,
+    size: Size,
+    filter_x: Filter,
+    filter_y: Filter,
+) -> Result<Image<Vec4>, resize::Error> {
+    if filter_x == Filter::Nearest && filter_y == Filter::Nearest {
+        // NN doesn't interpolate pixels, so alpha doesn't matter
+        return scale_rect(img, rect, size, filter_x, filter_y);
+    }
+
+    let premultiplied = img.map(|p| (*p * p.w).truncate().extend(p.w));
+    let mut result = scale_rect(premultiplied.view(), rect, size, filter_x, filter_y)?;
+
+    // drop image now to free up memory asap
+    std::mem::drop(premultiplied);
+
+    result.data_mut().iter_mut().for_each(|p| {
+        let rgb = if p.w > 0.0 {
+            p.truncate() / p.w
+        } else {
+            Default::default()
+        };
+        *p = rgb.extend(p.w);
+    });
+
+    Ok(result)
+}
+
+/// Scales an image with any number of channels.
+///
+/// Each channel is scaled independently. The result is the same as scaling
+/// each channel on its own with [`scale`].
+pub fn scale_ndim(img: NDimView, size: Size, filter: Filter) -> Result<NDimImage, resize::Error> {
+    let rect = SourceRect::from_size(img.size());
+    scale_ndim_rect(img, rect, size, filter, filter)
+}
+
+/// Scales the given region of an image with any number of channels.
+///
+/// See [`scale_ndim`] and [`scale_rect`].
+pub fn scale_ndim_rect(
+    img: NDimView,
+    rect: SourceRect,
+    size: Size,
+    filter_x: Filter,
+    filter_y: Filter,
+) -> Result<NDimImage, resize::Error> {
+    let channels = img.channels();
+
+    let mut dest = NDimImage::zeros(Shape::from_size(size, channels));
+    let mut plane = Vec::new();
+    plane.try_reserve_exact(img.size().len())?;
+
+    for c in 0..channels {
+        // de-interleave the channel, scale it, and interleave it again
+        read_channel_plane(img, c, &mut plane);
+        let plane_view = ImageView::new(img.size(), &plane);
+
+        let scaled = scale_rect(plane_view, rect, size, filter_x, filter_y)?;
+        set_channel_plane(&mut dest, c, scaled.data());
+    }
 
     Ok(dest)
 }
 
+/// Scales an image that is stored as one single-channel image per channel.
+///
+/// All planes must have the same size. See [`scale_ndim`].
+pub fn scale_planar(
+    planes: &[ImageView<f32>],
+    size: Size,
+    filter: Filter,
+) -> Result<Vec<Image<f32>>, resize::Error> {
+    planes
+        .iter()
+        .map(|plane| scale(*plane, size, filter))
+        .collect()
+}
+
+/// Scales the given region of an image that is stored as one single-channel
+/// image per channel.
+///
+/// See [`scale_planar`] and [`scale_rect`].
+pub fn scale_planar_rect(
+    planes: &[ImageView<f32>],
+    rect: SourceRect,
+    size: Size,
+    filter_x: Filter,
+    filter_y: Filter,
+) -> Result<Vec<Image<f32>>, resize::Error> {
+    planes
+        .iter()
+        .map(|plane| scale_rect(*plane, rect, size, filter_x, filter_y))
+        .collect()
+}
+
 fn nearest_neighbor<P: Clone>(src: ImageView<P>, size: Size) -> Image<P> {
     if src.size() == size {
         return src.into_owned();
     }
 
     let src_size = src.size();
-    let src = src.data();
 
     {
         // optimization for power-of-2 scaling factors, e.g. 2x, 4x
@@ -50,12 +238,11 @@ fn nearest_neighbor<P: Clone>(src: ImageView<P>, size: Size) -> Image<P> {
 
             let mut data = Vec::with_capacity(size.len());
             for y in 0..size.height {
-                let src_y = y >> shift;
-                let src_i = src_y * src_size.width;
+                let src_row = src.row(y >> shift);
 
                 data.extend((0..size.width).map(move |x| {
                     let src_x = x >> shift;
-                    src[src_i + src_x].clone()
+                    src_row[src_x].clone()
                 }));
             }
 
@@ -63,6 +250,56 @@ fn nearest_neighbor<P: Clone>(src: ImageView<P>, size: Size) -> Image<P> {
         }
     }
 
+    let map_x = NearestMap::new(src_size.width, size.width);
+    let map_y = NearestMap::new(src_size.height, size.height);
+
+    let mut data = Vec::with_capacity(size.len());
+    for y in 0..size.height {
+        let src_row = src.row(map_y.get(y));
+        data.extend((0..size.width).map(|x| src_row[map_x.get(x)].clone()));
+    }
+
+    Image::new(size, data)
+}
+
+/// Scales an image with any number of channels and any sample type using
+/// nearest neighbor.
+///
+/// The result is the same as scaling each channel with [`scale`] and
+/// [`Filter::Nearest`].
+pub fn scale_ndim_nearest<T: Clone>(img: NDimView<T>, size: Size) -> NDimImage<T> {
+    let c = img.channels();
+    let shape = Shape::from_size(size, c);
+    if shape.is_empty() {
+        return NDimImage::new(shape, Vec::new());
+    }
+    if img.size() == size {
+        return img.into_owned();
+    }
+
+    let map_x = NearestMap::new(img.width(), size.width);
+    let map_y = NearestMap::new(img.height(), size.height);
+    let src_x: Vec<usize> = (0..size.width).map(|x| map_x.get(x) * c).collect();
+
+    let mut data = Vec::with_capacity(shape.len());
+    for y in 0..size.height {
+        let src_row = img.row(map_y.get(y));
+        for &i in &src_x {
+            data.extend_from_slice(&src_row[i..i + c]);
+        }
+    }
+
+    NDimImage::new(shape, data)
+}
+
+/// Maps a coordinate of the scaled image to the coordinate of the source pixel
+/// nearest to it.
+struct NearestMap {
+    k: u64,
+    k_half: u64,
+}
+
+impl NearestMap {
     // What is going on here? Okay, so this uses fixed point arithmetic (fixed)
     // to avoid floating point and divisions. Basic NN works like this:
     // We imagine that each pixel coordinate is at the center of the pixel and that center coordinate is then mapped to the src image. For the x coordinate this means:
@@ -79,33 +316,29 @@ fn nearest_neighbor<P: Clone>(src: ImageView<P>, size: Size) -> Image<P> {
     // super cheap.
     const SHIFT: i32 = 32;
 
-    assert!(src_size.width <= i32::MAX as usize);
-    assert!(src_size.height <= i32::MAX as usize);
-
-    let k_x: u64 = ((src_size.width as u64) << SHIFT) / size.width as u64;
-    let k_y: u64 = ((src_size.height as u64) << SHIFT) / size.height as u64;
-    let k_x_half: u64 = k_x >> 1;
-    let k_y_half: u64 = k_y >> 1;
+    fn new(src_len: usize, len: usize) -> Self {
+        assert!(src_len <= i32::MAX as usize);
 
-    let mut data = Vec::with_capacity(size.len());
-    for y in 0..(size.height as u64) {
-        let src_y = ((y * k_y + k_y_half) >> SHIFT) as usize;
-        let src_i = src_y * src_size.width;
-
-        data.extend((0..(size.width as u64)).map(move |x| {
-            let src_x = ((x * k_x + k_x_half) >> SHIFT) as usize;
-            src[src_i + src_x].clone()
-        }));
+        let k: u64 = ((src_len as u64) << Self::SHIFT) / len as u64;
+        Self { k, k_half: k >> 1 }
     }
 
-    Image::new(size, data)
+    #[inline]
+    fn get(&self, i: usize) -> usize {
+        ((i as u64 * self.k + self.k_half) >> Self::SHIFT) as usize
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use crate::scale::FloatPixelFormat;
     use glam::Vec3A;
-    use image_core::Size;
-    use test_util::{data::read_portrait, snap::ImageSnapshot};
+    use image_core::{NDimImage, Rect, Shape, Size};
+
+    use test_util::{
+        data::{read_abstract_transparent, read_flower_transparent, read_portrait},
+        snap::ImageSnapshot,
+    };
 
     fn small_portrait() -> image_core::Image<Vec3A> {
         let img = read_portrait();
@@ -293,4 +526,199 @@ mod tests {
         let nn = super::scale(original.view(), new_size, filter).unwrap();
         nn.snapshot("resize_lagrange_200");
     }
+
+    #[test]
+    fn scale_mks2013() {
+        let filter = super::Filter::MagicKernelSharp2013;
+
+        let original = small_portrait();
+        let new_size = original.size().scale(4.);
+        let nn = super::scale(original.view(), new_size, filter).unwrap();
+        nn.snapshot("resize_mks2013_4x");
+
+        let original = read_portrait();
+        let new_size = Size::new(200, 200);
+        let nn = super::scale(original.view(), new_size, filter).unwrap();
+        nn.snapshot("resize_mks2013_200");
+    }
+
+    #[test]
+    fn scale_mks2021() {
+        let filter = super::Filter::MagicKernelSharp2021;
+
+        let original = small_portrait();
+        let new_size = original.size().scale(4.);
+        let nn = super::scale(original.view(), new_size, filter).unwrap();
+        nn.snapshot("resize_mks2021_4x");
+
+        let original = read_portrait();
+        let new_size = Size::new(200, 200);
+        let nn = super::scale(original.view(), new_size, filter).unwrap();
+        nn.snapshot("resize_mks2021_200");
+    }
+
+    #[test]
+    fn scale_premultiplied_alpha() {
+        let original = read_abstract_transparent();
+        let new_size = original.size().scale(0.25);
+        let nn = super::scale_premultiplied_alpha(original.view(), new_size, super::Filter::Linear)
+            .unwrap();
+        nn.snapshot("resize_premultiplied_alpha_linear_025");
+
+        let original = read_flower_transparent();
+        let new_size = original.size().scale(2.);
+        let nn =
+            super::scale_premultiplied_alpha(original.view(), new_size, super::Filter::CubicCatrom)
+                .unwrap();
+        nn.snapshot("resize_premultiplied_alpha_cubic_catrom_2x");
+    }
+
+    #[test]
+    fn scale_rect() {
+        let original = read_portrait();
+
+        let rect = super::SourceRect::new(60.25, 40.5, 140.75, 120.0);
+        let new_size = Size::new(200, 200);
+        let filter = super::Filter::CubicMitchell;
+        let nn = super::scale_rect(original.view(), rect, new_size, filter, filter).unwrap();
+        nn.snapshot("resize_rect_cubic_mitchell");
+
+        let rect = super::SourceRect::from_size(original.size());
+        let new_size = Size::new(200, 200);
+        let nn = super::scale_rect(
+            original.view(),
+            rect,
+            new_size,
+            super::Filter::Nearest,
+            super::Filter::Lanczos3,
+        )
+        .unwrap();
+        nn.snapshot("resize_rect_nearest_lanczos3");
+    }
+
+    #[test]
+    fn scale_matches_resize() {
+        let original = small_portrait();
+
+        for filter in [
+            super::Filter::Box,
+            super::Filter::Linear,
+            super::Filter::CubicCatrom,
+            super::Filter::Lanczos3,
+            super::Filter::Gauss,
+            super::Filter::MagicKernelSharp2021,
+        ] {
+            for new_size in [original.size().scale(3.), original.size().scale(0.3)] {
+                let actual = super::scale(original.view(), new_size, filter).unwrap();
+
+                let mut expected = vec![Vec3A::ZERO; new_size.len()];
+                resize::Resizer::new(
+                    original.width(),
+                    original.height(),
+                    new_size.width,
+                    new_size.height,
+                    FloatPixelFormat::<Vec3A>::default(),
+                    filter.into(),
+                )
+                .unwrap()
+                .resize(original.data(), &mut expected)
+                .unwrap();
+
+                assert!(actual.data() == expected, "{:?} {:?}", filter, new_size);
+
+                let single_threaded = rayon::ThreadPoolBuilder::new()
+                    .num_threads(1)
+                    .build()
+                    .unwrap()
+                    .install(|| super::scale(original.view(), new_size, filter).unwrap());
+                assert!(
+                    single_threaded.data() == expected,
+                    "{:?} {:?}",
+                    filter,
+                    new_size
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn scale_ndim() {
+        let rgb = small_portrait();
+        let shape = Shape::from_size(rgb.size(), 6);
+        let data = rgb
+            .data()
+            .iter()
+            .flat_map(|p| [p.x, p.y, p.z, 1.0 - p.x, 1.0 - p.y, 1.0 - p.z])
+            .collect();
+        let original = NDimImage::new(shape, data);
+
+        let new_size = original.size().scale(4.);
+        let scaled = super::scale_ndim(original.view(), new_size, super::Filter::Lanczos3).unwrap();
+        assert_eq!(scaled.shape(), Shape::from_size(new_size, 6));
+
+        let split = |offset: usize| {
+            let data = scaled
+                .data()
+                .chunks_exact(6)
+                .flat_map(|p| p[offset..offset + 3].iter().copied())
+                .collect();
+            NDimImage::new(Shape::from_size(new_size, 3), data)
+        };
+        // must be the same as scaling the RGB image directly
+        let expected: NDimImage = super::scale(rgb.view(), new_size, super::Filter::Lanczos3)
+            .unwrap()
+            .into();
+        assert!(split(0).data() == expected.data());
+
+        split(3).snapshot("resize_ndim_lanczos3_inv");
+    }
+
+    #[test]
+    fn scale_sub_view() {
+        let original = small_portrait();
+        let rect = Rect::new(13, 7, 40, 50);
+        let view = original.sub_view(rect);
+        let cropped = original.crop(rect);
+        assert!(!view.is_contiguous());
+
+        for filter in [super::Filter::Nearest, super::Filter::Lanczos3] {
+            for new_size in [rect.size().scale(3.), rect.size().scale(0.3)] {
+                let actual = super::scale(view, new_size, filter).unwrap();
+                let expected = super::scale(cropped.view(), new_size, filter).unwrap();
+                assert!(
+                    actual.data() == expected.data(),
+                    "{:?} {:?}",
+                    filter,
+                    new_size
+                );
+            }
+        }
+
+        let ndim: NDimImage = original.into();
+        let new_size = Size::new(31, 17);
+        let actual =
+            super::scale_ndim(ndim.sub_view(rect), new_size, super::Filter::Linear).unwrap();
+        let expected =
+            super::scale_ndim(ndim.crop(rect).view(), new_size, super::Filter::Linear).unwrap();
+        assert!(actual.data() == expected.data());
+    }
+
+    #[test]
+    fn scale_ndim_nearest() {
+        let original = small_portrait();
+        let ndim: NDimImage = original.clone().into();
+        let ndim_u8 = ndim.convert::<u8>();
+
+        for new_size in [Size::new(200, 200), original.size().scale(4.)] {
+            let expected: NDimImage =
+                super::scale(original.view(), new_size, super::Filter::Nearest)
+                    .unwrap()
+                    .into();
+            let actual = super::scale_ndim_nearest(ndim.view(), new_size);
+            assert!(actual.data() == expected.data());
+
+            let actual = super::scale_ndim_nearest(ndim_u8.view(), new_size);
+            assert!(actual.data() == expected.convert::<u8>().data());
+        }
+    }
 }
@@ -0,0 +1,236 @@
+use glam::Vec4;
+use image_core::{Image, ImageView, NDimImage, NDimView, Size};
+use rayon::prelude::*;
+
+use crate::{
+    blur::{blur, Blur},
+    border::BorderMode,
+    util::{channel_plane, set_channel_plane},
+};
+
+/// Determines which channels the details are extracted from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SharpenMode {
+    /// Details are extracted from the luminance (Rec. 709) and added to all
+    /// color channels equally. This sharpens without shifting colors.
+    ///
+    /// This only applies to RGB and RGBA images. All other images are
+    /// processed per channel.
+    Luminance,
+    /// Each color channel is processed independently.
+    PerChannel,
+}
+
+/// Returns the number of color channels of an image with the given number of
+/// channels. Gray+alpha and RGBA images have an alpha channel that is not
+/// touched.
+fn color_channels(channels: usize) -> usize {
+    match channels {
+        2 | 4 => channels - 1,
+        _ => channels,
+    }
+}
+
+/// Extracts the details of the given color planes and combines them with the
+/// original values using `f(value, detail)`.
+fn apply_details(
+    planes: &mut [Vec<f32>],
+    size: Size,
+    radius: f32,
+    mode: SharpenMode,
+    f: impl Fn(f32, f32) -> f32 + Sync,
+) {
+    let details = |plane: &[f32]| -> Vec<f32> {
+        let kind = Blur::Gaussian {
+            sigma_x: radius,
+            sigma_y: radius,
+        };
+        let blurred = blur(ImageView::new(size, plane), kind, BorderMode::Reflect);
+        plane
+            .par_iter()
+            .zip(blurred.data())
+            .map(|(p, b)| p - b)
+            .collect()
+    };
+
+    if mode == SharpenMode::Luminance && planes.len() == 3 {
+        let luminance: Vec<f32> = (0..size.len())
+            .into_par_iter()
+            .map(|i| 0.2126 * planes[0][i] + 0.7152 * planes[1][i] + 0.0722 * planes[2][i])
+            .collect();
+        let detail = details(&luminance);
+        for plane in planes.iter_mut() {
+            plane
+                .par_iter_mut()
+                .zip(&detail)
+                .for_each(|(p, d)| *p = f(*p, *d));
+        }
+    } else {
+        for plane in planes.iter_mut() {
+            let detail = details(plane);
+            plane
+                .par_iter_mut()
+                .zip(detail)
+                .for_each(|(p, d)| *p = f(*p, d));
+        }
+    }
+}
+
+fn sharpen_ndim(
+    img: NDimView,
+    radius: f32,
+    mode: SharpenMode,
+    f: impl Fn(f32, f32) -> f32 + Sync,
+) -> NDimImage {
+    let mut planes: Vec<Vec<f32>> = (0..color_channels(img.channels()))
+        .map(|c| channel_plane(img, c))
+        .collect();
+    apply_details(&mut planes, img.size(), radius, mode, f);
+
+    let mut result = img.into_owned();
+    for (c, plane) in planes.iter().enumerate() {
+        set_channel_plane(&mut result, c, plane);
+    }
+    result
+}
+
+fn sharpen_rgba(
+    img: ImageView<Vec4>,
+    radius: f32,
+    mode: SharpenMode,
+    f: impl Fn(f32, f32) -> f32 + Sync,
+) -> Image<Vec4> {
+    let src = img.contiguous_data();
+    let mut planes: Vec<Vec<f32>> = (0..3).map(|c| src.iter().map(|p| p[c]).collect()).collect();
+    apply_details(&mut planes, img.size(), radius, mode, f);
+
+    Image::new(
+        img.size(),
+        src.iter()
+            .enumerate()
+            .map(|(i, p)| Vec4::new(planes[0][i], planes[1][i], planes[2][i], p.w))
+            .collect(),
+    )
+}
+
+/// Returns the function that combines a value and its detail for an unsharp
+/// mask.
+fn unsharp(amount: f32, threshold: f32) -> impl Fn(f32, f32) -> f32 + Sync {
+    move |value, detail| {
+        if detail.abs() < threshold {
+            value
+        } else {
+            value + amount * detail
+        }
+    }
+}
+
+/// Sharpens the image with an unsharp mask.
+///
+/// The details of the image are the difference between the image and a
+/// Gaussian blur with standard deviation `radius`. Details with an absolute
+/// value of at least `threshold` are added `amount` times to the image.
+///
+/// If the image has 2 or 4 channels, the last channel is treated as alpha and
+/// left unchanged. The result is not clipped.
+pub fn unsharp_mask(
+    img: NDimView,
+    radius: f32,
+    amount: f32,
+    threshold: f32,
+    mode: SharpenMode,
+) -> NDimImage {
+    sharpen_ndim(img, radius, mode, unsharp(amount, threshold))
+}
+
+/// Sharpens the RGB channels of the image with an unsharp mask. Alpha is left
+/// unchanged.
+///
+/// See [`unsharp_mask`].
+pub fn unsharp_mask_rgba(
+    img: ImageView<Vec4>,
+    radius: f32,
+    amount: f32,
+    threshold: f32,
+    mode: SharpenMode,
+) -> Image<Vec4> {
+    sharpen_rgba(img, radius, mode, unsharp(amount, threshold))
+}
+
+/// Applies a high-pass filter to the image.
+///
+/// The result is the difference between the image and a Gaussian blur with
+/// standard deviation `radius`, offset by 0.5. So flat areas become 50% gray.
+/// In [`SharpenMode::Luminance`], all color channels get the luminance
+/// details, so the result is gray.
+///
+/// If the image has 2 or 4 channels, the last channel is treated as alpha and
+/// left unchanged.
+pub fn high_pass(img: NDimView, radius: f32, mode: SharpenMode) -> NDimImage {
+    sharpen_ndim(img, radius, mode, |_, detail| detail + 0.5)
+}
+
+/// Applies a high-pass filter to the RGB channels of the image. Alpha is left
+/// unchanged.
+///
+/// See [`high_pass`].
+pub fn high_pass_rgba(img: ImageView<Vec4>, radius: f32, mode: SharpenMode) -> Image<Vec4> {
+    sharpen_rgba(img, radius, mode, |_, detail| detail + 0.5)
+}
+
+#[cfg(test)]
+mod tests {
+    use image_core::NDimImage;
+    use test_util::{
+        data::{read_flower_transparent, read_portrait},
+        snap::ImageSnapshot,
+    };
+
+    use super::{high_pass, high_pass_rgba, unsharp_mask, unsharp_mask_rgba, SharpenMode};
+
+    #[test]
+    fn unsharp() {
+        let img: NDimImage = read_portrait().into();
+        unsharp_mask(img.view(), 2.0, 1.5, 0.0, SharpenMode::Luminance)
+            .snapshot("unsharp_mask_luminance");
+        unsharp_mask(img.view(), 2.0, 1.5, 0.05, SharpenMode::PerChannel)
+            .snapshot("unsharp_mask_per_channel_threshold");
+
+        // a threshold above all details doesn't change anything
+        let unchanged = unsharp_mask(img.view(), 2.0, 1.5, 2.0, SharpenMode::PerChannel);
+        assert_eq!(unchanged.data(), img.data());
+    }
+
+    #[test]
+    fn high() {
+        let img: NDimImage = read_portrait().into();
+        high_pass(img.view(), 3.0, SharpenMode::Luminance).snapshot("high_pass_luminance");
+    }
+
+    #[test]
+    fn rgba_preserves_alpha() {
+        let original = read_flower_transparent();
+        let sharpened = unsharp_mask_rgba(original.view(), 1.0, 1.0, 0.0, SharpenMode::Luminance);
+        let high = high_pass_rgba(original.view(), 1.0, SharpenMode::PerChannel);
+
+        for ((o, s), h) in original
+            .data()
+            .iter()
+            .zip(sharpened.data())
+            .zip(high.data())
+        {
+            assert_eq!(o.w, s.w);
+            assert_eq!(o.w, h.w);
+        }
+
+        // RGBA images as NDim images behave the same
+        let ndim = unsharp_mask(
+            NDimImage::from(original).view(),
+            1.0,
+            1.0,
+            0.0,
+            SharpenMode::Luminance,
+        );
+        assert_eq!(ndim.data(), NDimImage::from(sharpened).data());
+    }
+}
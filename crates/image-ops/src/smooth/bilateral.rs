@@ -0,0 +1,122 @@
+use image_core::{NDimImage, NDimView, Shape};
+use rayon::prelude::*;
+
+use crate::border::BorderMode;
+
+use super::window;
+
+/// Applies a bilateral filter to the image.
+///
+/// Each pixel becomes a weighted average of its neighbors. The weight of a
+/// neighbor is a Gaussian of its spatial distance (`sigma_spatial`, in pixels)
+/// times a Gaussian of its color distance (`sigma_range`). The color distance
+/// is the Euclidean distance over all channels, so edges are preserved
+/// consistently across channels.
+///
+/// The window is cut off at 2 `sigma_spatial`, but its radius is at most the
+/// larger side of the image. Pixels outside the image are determined by the
+/// border mode, and [`BorderMode::Transparent`] ignores them.
+pub fn bilateral_filter(
+    img: NDimView,
+    sigma_spatial: f32,
+    sigma_range: f32,
+    border: BorderMode,
+) -> NDimImage {
+    let shape = img.shape();
+    let size = img.size();
+    let channels = img.channels();
+    let src = img.contiguous_data();
+
+    let valid_sigmas = sigma_spatial > 0.0 && sigma_spatial.is_finite() && sigma_range > 0.0;
+    if !valid_sigmas || size.is_empty() || channels == 0 {
+        return NDimImage::new(shape, src.to_vec());
+    }
+
+    let radius = ((sigma_spatial * 2.0).ceil() as usize).min(size.width.max(size.height));
+    // the spatial Gaussian is separable, so 1 weight per offset is enough
+    let spatial: Vec<f32> = (0..2 * radius + 1)
+        .map(|i| {
+            let d = i as f32 - radius as f32;
+            (-(d * d) / (2.0 * sigma_spatial * sigma_spatial)).exp()
+        })
+        .collect();
+    let range_factor = -1.0 / (2.0 * sigma_range * sigma_range);
+
+    let mut dst = vec![0.0; shape.len()];
+    dst.par_chunks_exact_mut(size.width * channels)
+        .enumerate()
+        .for_each_init(
+            || vec![0.0; channels],
+            |acc, (y, row)| {
+                for (x, dst) in row.chunks_exact_mut(channels).enumerate() {
+                    let i = (y * size.width + x) * channels;
+                    let center = &src[i..i + channels];
+
+                    acc.fill(0.0);
+                    let mut weight_sum = 0.0;
+
+                    let (columns, rows) = window((x, y), radius, size, border);
+                    for (sy, spatial_y) in rows.zip(&spatial) {
+                        let Some(sy) = sy else {
+                            continue;
+                        };
+
+                        for (sx, spatial_x) in columns.clone().zip(&spatial) {
+                            let Some(sx) = sx else {
+                                continue;
+                            };
+                            let j = (sy * size.width + sx) * channels;
+                            let pixel = &src[j..j + channels];
+
+                            let dist: f32 = pixel
+                                .iter()
+                                .zip(center)
+                                .map(|(a, b)| (a - b) * (a - b))
+                                .sum();
+                            let weight = spatial_y * spatial_x * (dist * range_factor).exp();
+
+                            weight_sum += weight;
+                            for (a, p) in acc.iter_mut().zip(pixel) {
+                                *a += p * weight;
+                            }
+                        }
+                    }
+
+                    // the center pixel always has a weight of 1, so the sum
+                    // can't be 0
+                    for (d, a) in dst.iter_mut().zip(acc.iter()) {
+                        *d = a / weight_sum;
+                    }
+                }
+            },
+        );
+
+    NDimImage::new(Shape::from_size(size, channels), dst)
+}
+
+#[cfg(test)]
+mod tests {
+    use image_core::{NDimImage, Shape};
+    use test_util::{data::read_portrait, snap::ImageSnapshot};
+
+    use crate::border::BorderMode;
+
+    use super::bilateral_filter;
+
+    #[test]
+    fn bilateral() {
+        let img: NDimImage = read_portrait().into();
+        bilateral_filter(img.view(), 4.0, 0.1, BorderMode::Reflect)
+            .snapshot("bilateral_4_01_reflect");
+    }
+
+    #[test]
+    fn huge_sigma() {
+        // the window is limited to the image, so this doesn't run out of memory
+        let img = NDimImage::new(Shape::new(3, 2, 1), vec![0.0, 0.5, 1.0, 0.0, 0.5, 1.0]);
+        let result = bilateral_filter(img.view(), 1e30, 1e30, BorderMode::Transparent);
+        for v in result.data() {
+            assert!((v - 0.5).abs() < 1e-5, "{v}");
+        }
+    }
+}
@@ -0,0 +1,183 @@
+use image_core::{ImageView, NDimImage, NDimView, Size};
+use rayon::prelude::*;
+
+use crate::{
+    blur::{blur, Blur},
+    border::BorderMode,
+    util::{channel_plane, set_channel_plane},
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum GuideError {
+    SizeMismatch { expected: Size, actual: Size },
+    ChannelMismatch { expected: usize, actual: usize },
+    InvalidEps,
+}
+
+/// Applies a guided filter (He et al.) to each channel of the image.
+///
+/// Without a guide, each channel guides itself, which makes this a fast
+/// edge-preserving smoothing filter. A guide must have the same size as the
+/// image and either 1 channel (used for all channels) or the same number of
+/// channels as the image (used channel by channel).
+///
+/// `radius` is the radius of the box windows and `eps` regularizes the
+/// result: larger values smooth more strongly across edges. `eps` must be
+/// finite and positive.
+///
+/// With [`BorderMode::Transparent`], windows only consider the pixels inside
+/// the image.
+pub fn guided_filter(
+    img: NDimView,
+    guide: Option<NDimView>,
+    radius: usize,
+    eps: f32,
+    border: BorderMode,
+) -> Result<NDimImage, GuideError> {
+    let size = img.size();
+    let channels = img.channels();
+
+    if !(eps > 0.0 && eps.is_finite()) {
+        return Err(GuideError::InvalidEps);
+    }
+    if let Some(guide) = guide {
+        if guide.size() != size {
+            return Err(GuideError::SizeMismatch {
+                expected: size,
+                actual: guide.size(),
+            });
+        }
+        if guide.channels() != 1 && guide.channels() != channels {
+            return Err(GuideError::ChannelMismatch {
+                expected: channels,
+                actual: guide.channels(),
+            });
+        }
+    }
+
+    let mut result = img.into_owned();
+    if radius == 0 || size.is_empty() {
+        return Ok(result);
+    }
+
+    let shared_guide = match guide {
+        Some(guide) if guide.channels() == 1 => Some(channel_plane(guide, 0)),
+        _ => None,
+    };
+
+    for c in 0..channels {
+        let p = channel_plane(img, c);
+        let filtered = match (&shared_guide, guide) {
+            (Some(i), _) => guided_plane(i, &p, size, radius, eps, border),
+            (None, Some(guide)) => {
+                let i = channel_plane(guide, c);
+                guided_plane(&i, &p, size, radius, eps, border)
+            }
+            (None, None) => guided_plane(&p, &p, size, radius, eps, border),
+        };
+        set_channel_plane(&mut result, c, &filtered);
+    }
+
+    Ok(result)
+}
+
+fn guided_plane(
+    i: &[f32],
+    p: &[f32],
+    size: Size,
+    radius: usize,
+    eps: f32,
+    border: BorderMode,
+) -> Vec<f32> {
+    let kind = Blur::Box {
+        radius_x: radius,
+        radius_y: radius,
+    };
+    let box_blur =
+        |data: &[f32]| -> Vec<f32> { blur(ImageView::new(size, data), kind, border).take() };
+
+    // transparent pixels count as 0, so we have to divide by the fraction of
+    // each window that is inside the image
+    let coverage = (border == BorderMode::Transparent).then(|| box_blur(&vec![1.0; size.len()]));
+    let mean = |data: &[f32]| -> Vec<f32> {
+        let mut mean = box_blur(data);
+        if let Some(coverage) = &coverage {
+            mean.par_iter_mut().zip(coverage).for_each(|(m, c)| *m /= c);
+        }
+        mean
+    };
+    let product =
+        |a: &[f32], b: &[f32]| -> Vec<f32> { a.par_iter().zip(b).map(|(a, b)| a * b).collect() };
+
+    let mean_i = mean(i);
+    let mean_p = mean(p);
+    let corr_ii = mean(&product(i, i));
+    let corr_ip = mean(&product(i, p));
+
+    // the linear coefficients `q = a * I + b` of each window
+    let (a, b): (Vec<f32>, Vec<f32>) = (0..size.len())
+        .into_par_iter()
+        .map(|k| {
+            let var_i = corr_ii[k] - mean_i[k] * mean_i[k];
+            let cov_ip = corr_ip[k] - mean_i[k] * mean_p[k];
+            let a = cov_ip / (var_i + eps);
+            let b = mean_p[k] - a * mean_i[k];
+            (a, b)
+        })
+        .unzip();
+
+    let mean_a = mean(&a);
+    let mean_b = mean(&b);
+
+    (0..size.len())
+        .into_par_iter()
+        .map(|k| mean_a[k] * i[k] + mean_b[k])
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use image_core::{NDimImage, Shape};
+    use test_util::{data::read_portrait, snap::ImageSnapshot};
+
+    use crate::border::BorderMode;
+
+    use super::{guided_filter, GuideError};
+
+    #[test]
+    fn guided() {
+        let img: NDimImage = read_portrait().into();
+        guided_filter(img.view(), None, 8, 0.01, BorderMode::Reflect)
+            .unwrap()
+            .snapshot("guided_8_001_self");
+
+        // guide all channels with the luminance
+        let gray: Vec<f32> = img
+            .data()
+            .chunks_exact(3)
+            .map(|p| p[0] * 0.299 + p[1] * 0.587 + p[2] * 0.114)
+            .collect();
+        let gray = NDimImage::new(Shape::from_size(img.size(), 1), gray);
+        guided_filter(img.view(), Some(gray.view()), 8, 0.001, BorderMode::Clamp)
+            .unwrap()
+            .snapshot("guided_8_0001_gray");
+
+        let small = NDimImage::zeros(Shape::new(3, 3, 1));
+        assert!(guided_filter(img.view(), Some(small.view()), 8, 0.01, BorderMode::Clamp).is_err());
+
+        for eps in [0.0, -1.0, f32::NAN, f32::INFINITY] {
+            let result = guided_filter(img.view(), None, 8, eps, BorderMode::Clamp);
+            assert_eq!(result.err(), Some(GuideError::InvalidEps));
+        }
+    }
+
+    #[test]
+    fn transparent_border() {
+        // a constant image must stay the same, even at the border
+        let img = NDimImage::new(Shape::new(7, 5, 1), vec![0.5; 35]);
+        let result = guided_filter(img.view(), None, 3, 0.01, BorderMode::Transparent).unwrap();
+        for v in result.data() {
+            assert!((v - 0.5).abs() < 1e-5, "{v}");
+        }
+    }
+}
@@ -0,0 +1,225 @@
+use image_core::{NDimImage, NDimView, Size};
+use rayon::prelude::*;
+
+use crate::{
+    border::BorderMode,
+    util::{channel_plane, set_channel_plane},
+};
+
+use super::window;
+
+/// Windows with a radius above this use the histogram-based algorithm.
+const HISTOGRAM_MIN_RADIUS: usize = 3;
+
+const FINE_BITS: u32 = 6;
+const COARSE_BITS: u32 = 6;
+const LEVELS: usize = 1 << (FINE_BITS + COARSE_BITS);
+
+/// Applies a median filter with a square window of the given radius to each
+/// channel of the image.
+///
+/// Pixels outside the image are determined by the border mode.
+/// [`BorderMode::Transparent`] ignores them, so windows at the edge of the
+/// image contain fewer pixels. If a window contains an even number of pixels,
+/// the lower median is used.
+///
+/// Small radii compute the exact median. For larger radii, a sliding
+/// histogram is used, which runs in `O(radius)` per pixel instead of
+/// `O(radius^2)`. The histogram quantizes the values of each channel to 4096
+/// levels between the channel's minimum and maximum, so the result may differ
+/// from the exact median by half a level.
+pub fn median_filter(img: NDimView, radius: usize, border: BorderMode) -> NDimImage {
+    let mut result = img.into_owned();
+    if radius == 0 || img.size().is_empty() {
+        return result;
+    }
+
+    for c in 0..img.channels() {
+        let plane = channel_plane(img, c);
+        let filtered = if radius > HISTOGRAM_MIN_RADIUS {
+            median_histogram(&plane, img.size(), radius, border)
+        } else {
+            median_exact(&plane, img.size(), radius, border)
+        };
+        set_channel_plane(&mut result, c, &filtered);
+    }
+
+    result
+}
+
+fn median_exact(src: &[f32], size: Size, radius: usize, border: BorderMode) -> Vec<f32> {
+    let mut dst = vec![0.0; size.len()];
+    dst.par_chunks_exact_mut(size.width)
+        .enumerate()
+        .for_each_init(Vec::new, |values, (y, row)| {
+            for (x, dst) in row.iter_mut().enumerate() {
+                values.clear();
+                let (columns, rows) = window((x, y), radius, size, border);
+                for sy in rows.flatten() {
+                    let src_row = &src[sy * size.width..(sy + 1) * size.width];
+                    values.extend(columns.clone().flatten().map(|sx| src_row[sx]));
+                }
+
+                let mid = (values.len() - 1) / 2;
+                *dst = *values.select_nth_unstable_by(mid, f32::total_cmp).1;
+            }
+        });
+    dst
+}
+
+/// A histogram of quantized values with a coarse level to find the median
+/// quickly.
+struct Histogram {
+    coarse: [u32; 1 << COARSE_BITS],
+    fine: Vec<u32>,
+    count: u32,
+}
+
+impl Histogram {
+    fn new() -> Self {
+        Self {
+            coarse: [0; 1 << COARSE_BITS],
+            fine: vec![0; LEVELS],
+            count: 0,
+        }
+    }
+    fn add(&mut self, level: u16) {
+        self.coarse[(level >> FINE_BITS) as usize] += 1;
+        self.fine[level as usize] += 1;
+        self.count += 1;
+    }
+    fn remove(&mut self, level: u16) {
+        self.coarse[(level >> FINE_BITS) as usize] -= 1;
+        self.fine[level as usize] -= 1;
+        self.count -= 1;
+    }
+    fn add_column(&mut self, rows: &[&[u16]], x: Option<usize>) {
+        if let Some(x) = x {
+            rows.iter().for_each(|row| self.add(row[x]));
+        }
+    }
+    fn remove_column(&mut self, rows: &[&[u16]], x: Option<usize>) {
+        if let Some(x) = x {
+            rows.iter().for_each(|row| self.remove(row[x]));
+        }
+    }
+    fn median(&self) -> u16 {
+        let mut remaining = (self.count - 1) / 2;
+        for (i, c) in self.coarse.iter().enumerate() {
+            if remaining < *c {
+                let fine_start = i << FINE_BITS;
+                let fine = &self.fine[fine_start..fine_start + (1 << FINE_BITS)];
+                for (j, f) in fine.iter().enumerate() {
+                    if remaining < *f {
+                        return (fine_start + j) as u16;
+                    }
+                    remaining -= f;
+                }
+            }
+            remaining -= c;
+        }
+        unreachable!("the histogram is not empty")
+    }
+}
+
+fn median_histogram(src: &[f32], size: Size, radius: usize, border: BorderMode) -> Vec<f32> {
+    let (min, max) = src
+        .iter()
+        .filter(|v| v.is_finite())
+        .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), v| {
+            (min.min(*v), max.max(*v))
+        });
+    if min >= max {
+        // constant image (or no finite values at all)
+        return src.to_vec();
+    }
+
+    let max_level = (LEVELS - 1) as f32;
+    let scale = max_level / (max - min);
+    let levels: Vec<u16> = src
+        .par_iter()
+        .map(|v| ((v - min) * scale).round().clamp(0.0, max_level) as u16)
+        .collect();
+
+    let mut dst = vec![0.0; size.len()];
+    dst.par_chunks_exact_mut(size.width)
+        .enumerate()
+        .for_each(|(y, row)| {
+            let mut histogram = Histogram::new();
+            let (_, rows) = window((0, y), radius, size, border);
+            let rows: Vec<&[u16]> = rows
+                .flatten()
+                .map(|sy| &levels[sy * size.width..(sy + 1) * size.width])
+                .collect();
+
+            let column = |x: isize| border.map(x, size.width);
+            let r = radius as isize;
+            for x in -r..=r {
+                histogram.add_column(&rows, column(x));
+            }
+
+            for (x, dst) in row.iter_mut().enumerate() {
+                let x = x as isize;
+                if x > 0 {
+                    histogram.remove_column(&rows, column(x - r - 1));
+                    histogram.add_column(&rows, column(x + r));
+                }
+                *dst = min + histogram.median() as f32 / scale;
+            }
+        });
+    dst
+}
+
+#[cfg(test)]
+mod tests {
+    use image_core::NDimImage;
+    use test_util::{data::read_portrait, snap::ImageSnapshot};
+
+    use crate::border::BorderMode;
+
+    use super::{median_exact, median_filter, median_histogram};
+
+    /// Adds deterministic salt and pepper noise.
+    fn salt_and_pepper(img: &mut NDimImage) {
+        let mut state: u32 = 12345;
+        for p in img.data_mut() {
+            state = state.wrapping_mul(1664525).wrapping_add(1013904223);
+            match state >> 24 {
+                0..=12 => *p = 0.0,
+                13..=25 => *p = 1.0,
+                _ => {}
+            }
+        }
+    }
+
+    #[test]
+    fn median() {
+        let mut img: NDimImage = read_portrait().into();
+        salt_and_pepper(&mut img);
+
+        median_filter(img.view(), 1, BorderMode::Reflect).snapshot("median_1_reflect");
+        median_filter(img.view(), 6, BorderMode::Transparent).snapshot("median_6_transparent");
+    }
+
+    #[test]
+    fn histogram_matches_exact() {
+        let img: NDimImage = read_portrait().into();
+        let plane: Vec<f32> = img.data().iter().step_by(3).copied().collect();
+
+        for border in [
+            BorderMode::Transparent,
+            BorderMode::Clamp,
+            BorderMode::Reflect,
+            BorderMode::Wrap,
+        ] {
+            let exact = median_exact(&plane, img.size(), 2, border);
+            let histogram = median_histogram(&plane, img.size(), 2, border);
+            for (a, b) in exact.iter().zip(histogram) {
+                assert!(
+                    (a - b).abs() <= 0.5 / 4095.0 + 1e-6,
+                    "{border:?}: {a} != {b}"
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,29 @@
+mod bilateral;
+mod guided;
+mod median;
+
+pub use bilateral::*;
+pub use guided::*;
+pub use median::*;
+
+use image_core::Size;
+
+use crate::border::BorderMode;
+
+/// Returns the square window of the given radius around `(x, y)` as (mapped)
+/// column and row indexes. Pixels outside the image that are transparent
+/// according to the border mode are `None`.
+fn window(
+    (x, y): (usize, usize),
+    radius: usize,
+    size: Size,
+    border: BorderMode,
+) -> (
+    impl Iterator<Item = Option<usize>> + Clone,
+    impl Iterator<Item = Option<usize>>,
+) {
+    let r = radius as isize;
+    let columns = (x as isize - r..=x as isize + r).map(move |i| border.map(i, size.width));
+    let rows = (y as isize - r..=y as isize + r).map(move |i| border.map(i, size.height));
+    (columns, rows)
+}
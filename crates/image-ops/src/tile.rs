@@ -0,0 +1,409 @@
+use image_core::{NDimImage, NDimView, Shape, Size};
+use rayon::prelude::*;
+
+use crate::border::BorderMode;
+
+/// Determines how tiles at the right and bottom edge of the image are handled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TilePadding {
+    /// Edge tiles are moved inward, so all tiles lie completely inside the
+    /// image. If the image is smaller than the tile size, tiles will be
+    /// smaller as well.
+    Shift,
+    /// All tiles have exactly the tile size. Pixels of tiles that lie outside
+    /// the image are filled according to the border mode.
+    Pad(BorderMode),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TileError {
+    EmptySize,
+    OverlapTooLarge {
+        overlap: usize,
+        tile_size: Size,
+    },
+    ImageSizeMismatch {
+        expected: Size,
+        actual: Size,
+    },
+    TileCountMismatch {
+        expected: usize,
+        actual: usize,
+    },
+    TileSizeMismatch {
+        index: usize,
+        expected: Size,
+        actual: Size,
+    },
+    ChannelMismatch {
+        index: usize,
+        expected: usize,
+        actual: usize,
+    },
+}
+
+/// The position and size of a tile in the source image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Tile {
+    pub x: usize,
+    pub y: usize,
+    pub size: Size,
+}
+
+/// The tiles along one axis.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Span {
+    start: usize,
+    len: usize,
+    /// How many pixels this tile shares with the previous tile.
+    before: usize,
+    /// How many pixels this tile shares with the next tile.
+    after: usize,
+}
+
+impl Span {
+    fn split(len: usize, tile: usize, overlap: usize, padding: TilePadding) -> Vec<Span> {
+        let tile = match padding {
+            TilePadding::Shift => tile.min(len),
+            TilePadding::Pad(_) => tile,
+        };
+
+        // a shrunk tile may be smaller than the overlap, but then it's the
+        // only tile anyway
+        let stride = tile.saturating_sub(overlap);
+        let count = if len <= tile {
+            1
+        } else {
+            (len - overlap + stride - 1) / stride
+        };
+
+        let mut spans: Vec<Span> = (0..count)
+            .map(|i| {
+                let mut start = i * stride;
+                if padding == TilePadding::Shift {
+                    start = start.min(len - tile);
+                }
+                Span {
+                    start,
+                    len: tile,
+                    before: 0,
+                    after: 0,
+                }
+            })
+            .collect();
+
+        for i in 1..spans.len() {
+            let shared = (spans[i - 1].start + spans[i - 1].len).saturating_sub(spans[i].start);
+            spans[i - 1].after = shared;
+            spans[i].before = shared;
+        }
+
+        spans
+    }
+
+    /// The blending weights of the pixels of this tile at the given scale.
+    ///
+    /// The weights linearly ramp up/down in the regions shared with the
+    /// neighboring tiles, so that the weights of 2 overlapping tiles add up
+    /// to 1.
+    fn weights(&self, scale: usize) -> Vec<f32> {
+        let len = self.len * scale;
+        let before = self.before * scale;
+        let after = self.after * scale;
+
+        (0..len)
+            .map(|i| {
+                let mut w: f32 = 1.0;
+                if i < before {
+                    w = w.min((i as f32 + 0.5) / before as f32);
+                }
+                if len - i <= after {
+                    w = w.min(((len - i) as f32 - 0.5) / after as f32);
+                }
+                w
+            })
+            .collect()
+    }
+}
+
+/// A grid of (possibly overlapping) tiles covering an image.
+///
+/// Tiles are ordered row by row. Processed tiles can be merged back into a
+/// single image with [`TileGrid::merge`], which blends the overlapping
+/// regions of neighboring tiles to avoid visible seams.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TileGrid {
+    size: Size,
+    padding: TilePadding,
+    columns: Vec<Span>,
+    rows: Vec<Span>,
+}
+
+impl TileGrid {
+    /// Creates a new grid of tiles of the given size for an image of the
+    /// given size.
+    ///
+    /// Neighboring tiles will share (at least) `overlap` pixels.
+    pub fn new(
+        size: Size,
+        tile_size: Size,
+        overlap: usize,
+        padding: TilePadding,
+    ) -> Result<Self, TileError> {
+        if size.is_empty() || tile_size.is_empty() {
+            return Err(TileError::EmptySize);
+        }
+        if overlap >= tile_size.width.min(tile_size.height) {
+            return Err(TileError::OverlapTooLarge { overlap, tile_size });
+        }
+
+        Ok(Self {
+            size,
+            padding,
+            columns: Span::split(size.width, tile_size.width, overlap, padding),
+            rows: Span::split(size.height, tile_size.height, overlap, padding),
+        })
+    }
+
+    /// The size of the image this grid covers.
+    pub fn size(&self) -> Size {
+        self.size
+    }
+    pub fn tile_count(&self) -> usize {
+        self.columns.len() * self.rows.len()
+    }
+    pub fn tile(&self, index: usize) -> Tile {
+        let column = &self.columns[index % self.columns.len()];
+        let row = &self.rows[index / self.columns.len()];
+        Tile {
+            x: column.start,
+            y: row.start,
+            size: Size::new(column.len, row.len),
+        }
+    }
+    pub fn tiles(&self) -> impl Iterator<Item = Tile> + '_ {
+        (0..self.tile_count()).map(|i| self.tile(i))
+    }
+
+    /// Copies the tile with the given index out of the image.
+    pub fn extract(&self, img: NDimView, index: usize) -> Result<NDimImage, TileError> {
+        if img.size() != self.size {
+            return Err(TileError::ImageSizeMismatch {
+                expected: self.size,
+                actual: img.size(),
+            });
+        }
+
+        let tile = self.tile(index);
+        let channels = img.channels();
+        if channels == 0 {
+            return Err(TileError::EmptySize);
+        }
+
+        let mut data = vec![0.0; tile.size.len() * channels];
+        for (y, row) in data
+            .chunks_exact_mut(tile.size.width * channels)
+            .enumerate()
+        {
+            let Some(src_y) = self.map(tile.y + y, self.size.height) else {
+                continue;
+            };
+            let src_row = img.row(src_y);
+
+            for (x, pixel) in row.chunks_exact_mut(channels).enumerate() {
+                if let Some(src_x) = self.map(tile.x + x, self.size.width) {
+                    pixel.copy_from_slice(&src_row[src_x * channels..(src_x + 1) * channels]);
+                }
+            }
+        }
+
+        Ok(NDimImage::new(Shape::from_size(tile.size, channels), data))
+    }
+
+    /// Splits the image into tiles.
+    pub fn split(&self, img: NDimView) -> Result<Vec<NDimImage>, TileError> {
+        (0..self.tile_count())
+            .into_par_iter()
+            .map(|i| self.extract(img, i))
+            .collect()
+    }
+
+    /// Merges the given (processed) tiles into a single image.
+    ///
+    /// `scale` is the integer factor by which the tiles were upscaled during
+    /// processing. The merged image will be `scale` times as large as the
+    /// original image and has the same number of channels as the tiles.
+    pub fn merge(&self, tiles: &[NDimView], scale: usize) -> Result<NDimImage, TileError> {
+        if tiles.len() != self.tile_count() {
+            return Err(TileError::TileCountMismatch {
+                expected: self.tile_count(),
+                actual: tiles.len(),
+            });
+        }
+        let channels = tiles[0].channels();
+        if scale == 0 || channels == 0 {
+            return Err(TileError::EmptySize);
+        }
+        for (index, (tile, view)) in self.tiles().zip(tiles).enumerate() {
+            let expected = Size::new(tile.size.width * scale, tile.size.height * scale);
+            if view.size() != expected {
+                return Err(TileError::TileSizeMismatch {
+                    index,
+                    expected,
+                    actual: view.size(),
+                });
+            }
+            if view.channels() != channels {
+                return Err(TileError::ChannelMismatch {
+                    index,
+                    expected: channels,
+                    actual: view.channels(),
+                });
+            }
+        }
+
+        let size = Size::new(self.size.width * scale, self.size.height * scale);
+        let weights_x: Vec<Vec<f32>> = self.columns.iter().map(|c| c.weights(scale)).collect();
+        let weights_y: Vec<Vec<f32>> = self.rows.iter().map(|r| r.weights(scale)).collect();
+
+        let mut data = vec![0.0; size.len() * channels];
+        data.par_chunks_exact_mut(size.width * channels)
+            .enumerate()
+            .for_each(|(y, out_row)| {
+                let mut weight_sum = vec![0.0; size.width];
+
+                for (r, row) in self.rows.iter().enumerate() {
+                    let start = row.start * scale;
+                    if y < start || y >= start + row.len * scale {
+                        continue;
+                    }
+                    let tile_y = y - start;
+                    let wy = weights_y[r][tile_y];
+
+                    for (c, column) in self.columns.iter().enumerate() {
+                        let tile = &tiles[r * self.columns.len() + c];
+                        let tile_width = column.len * scale;
+                        let tile_row = tile.row(tile_y);
+
+                        let start = column.start * scale;
+                        let end = (start + tile_width).min(size.width);
+                        for x in start..end {
+                            let w = weights_x[c][x - start] * wy;
+                            let src = &tile_row[(x - start) * channels..][..channels];
+                            let dst = &mut out_row[x * channels..][..channels];
+                            for (d, s) in dst.iter_mut().zip(src) {
+                                *d += s * w;
+                            }
+                            weight_sum[x] += w;
+                        }
+                    }
+                }
+
+                for (pixel, w) in out_row.chunks_exact_mut(channels).zip(weight_sum) {
+                    pixel.iter_mut().for_each(|p| *p /= w);
+                }
+            });
+
+        Ok(NDimImage::new(Shape::from_size(size, channels), data))
+    }
+
+    fn map(&self, i: usize, len: usize) -> Option<usize> {
+        match self.padding {
+            TilePadding::Shift => Some(i),
+            TilePadding::Pad(border) => border.map(i as isize, len),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image_core::{NDimImage, NDimView, Size};
+    use test_util::{data::read_portrait, snap::ImageSnapshot};
+
+    use crate::{border::BorderMode, scale::Filter};
+
+    use super::{TileGrid, TilePadding};
+
+    #[test]
+    fn layout() {
+        let grid = TileGrid::new(Size::new(10, 4), Size::new(4, 4), 1, TilePadding::Shift).unwrap();
+        let xs: Vec<_> = grid.tiles().map(|t| t.x).collect();
+        assert_eq!(xs, [0, 3, 6]);
+
+        let grid = TileGrid::new(Size::new(11, 4), Size::new(4, 4), 1, TilePadding::Shift).unwrap();
+        let xs: Vec<_> = grid.tiles().map(|t| t.x).collect();
+        assert_eq!(xs, [0, 3, 6, 7]);
+
+        let grid = TileGrid::new(
+            Size::new(11, 2),
+            Size::new(4, 4),
+            1,
+            TilePadding::Pad(BorderMode::Reflect),
+        )
+        .unwrap();
+        let tiles: Vec<_> = grid.tiles().map(|t| (t.x, t.size)).collect();
+        assert_eq!(tiles, [0, 3, 6, 9].map(|x| (x, Size::new(4, 4))).to_vec());
+
+        let grid = TileGrid::new(Size::new(3, 2), Size::new(4, 4), 1, TilePadding::Shift).unwrap();
+        assert_eq!(grid.tile_count(), 1);
+        assert_eq!(grid.tile(0).size, Size::new(3, 2));
+    }
+
+    #[test]
+    fn split_merge_roundtrip() {
+        let original: NDimImage = read_portrait().into();
+
+        for padding in [
+            TilePadding::Shift,
+            TilePadding::Pad(BorderMode::Transparent),
+            TilePadding::Pad(BorderMode::Reflect),
+        ] {
+            let grid = TileGrid::new(original.size(), Size::new(64, 48), 16, padding).unwrap();
+            let tiles = grid.split(original.view()).unwrap();
+            let views: Vec<NDimView> = tiles.iter().map(|t| t.view()).collect();
+            let merged = grid.merge(&views, 1).unwrap();
+
+            assert_eq!(merged.shape(), original.shape());
+            for (a, b) in merged.data().iter().zip(original.data()) {
+                assert!((a - b).abs() < 1e-5, "{padding:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn merge_scaled() {
+        let original = read_portrait();
+        let grid = TileGrid::new(
+            original.size(),
+            Size::new(80, 80),
+            20,
+            TilePadding::Pad(BorderMode::Clamp),
+        )
+        .unwrap();
+
+        let tiles = grid.split(NDimImage::from(original).view()).unwrap();
+        let scaled: Vec<NDimImage> = tiles
+            .iter()
+            .enumerate()
+            .map(|(i, tile)| {
+                let size = Size::new(tile.width() * 2, tile.height() * 2);
+                let mut scaled =
+                    crate::scale::scale_ndim(tile.view(), size, Filter::CubicCatrom).unwrap();
+                // tint every tile differently to make the blending visible
+                let tint = [(i % 3) as f32 * 0.1, (i % 2) as f32 * 0.1, 0.0];
+                for pixel in scaled.data_mut().chunks_exact_mut(3) {
+                    pixel.iter_mut().zip(tint).for_each(|(p, t)| *p += t);
+                }
+                scaled
+            })
+            .collect();
+        let views: Vec<NDimView> = scaled.iter().map(|t| t.view()).collect();
+
+        grid.merge(&views, 2)
+            .unwrap()
+            .snapshot("tile_merge_scaled_tinted");
+
+        assert!(grid.merge(&views[1..], 2).is_err());
+        assert!(grid.merge(&views, 1).is_err());
+    }
+}
@@ -0,0 +1,185 @@
+use image_core::{NDimImage, NDimView, Rect};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TrimError {
+    /// The image doesn't have an alpha channel. Only images with 2 or 4
+    /// channels have one.
+    NoAlpha { channels: usize },
+    /// The background color doesn't have the same number of channels as the
+    /// image.
+    ChannelMismatch { expected: usize, actual: usize },
+}
+
+/// Determines which pixels are background.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Background<'a> {
+    /// Pixels with an alpha below the threshold are background. This is the
+    /// same threshold as in [`crate::fill_alpha::fill_alpha`].
+    Alpha { threshold: f32 },
+    /// Pixels whose channels all differ by at most `tolerance` from the given
+    /// color are background.
+    Color { color: &'a [f32], tolerance: f32 },
+}
+
+impl Background<'_> {
+    fn validate(&self, channels: usize) -> Result<(), TrimError> {
+        match self {
+            Background::Alpha { .. } if channels != 2 && channels != 4 => {
+                Err(TrimError::NoAlpha { channels })
+            }
+            Background::Color { color, .. } if color.len() != channels => {
+                Err(TrimError::ChannelMismatch {
+                    expected: channels,
+                    actual: color.len(),
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn is_background(&self, pixel: &[f32]) -> bool {
+        match *self {
+            Background::Alpha { threshold } => pixel[pixel.len() - 1] < threshold,
+            Background::Color { color, tolerance } => pixel
+                .iter()
+                .zip(color)
+                .all(|(p, c)| (p - c).abs() <= tolerance),
+        }
+    }
+}
+
+/// Returns the tight bounding box of all non-background pixels, extended by
+/// `padding` pixels on each side. The padded box is clipped to the image.
+///
+/// If the image only contains background, an empty rectangle is returned.
+pub fn trim_bounds(
+    img: NDimView,
+    background: Background,
+    padding: usize,
+) -> Result<Rect, TrimError> {
+    let channels = img.channels();
+    background.validate(channels)?;
+
+    let size = img.size();
+    // pixels without channels can't differ from the background
+    if size.is_empty() || channels == 0 {
+        return Ok(Rect::new(0, 0, 0, 0));
+    }
+
+    let mut left = size.width;
+    let mut right = 0;
+    let mut top = size.height;
+    let mut bottom = 0;
+    for (y, row) in img.rows().enumerate() {
+        let mut pixels = row.chunks_exact(channels);
+        let Some(first) = pixels.position(|p| !background.is_background(p)) else {
+            continue;
+        };
+        // the last content pixel of the row, searched from the end
+        let last = row
+            .chunks_exact(channels)
+            .rposition(|p| !background.is_background(p))
+            .unwrap_or(first);
+
+        left = left.min(first);
+        right = right.max(last + 1);
+        top = top.min(y);
+        bottom = y + 1;
+    }
+
+    if left >= right {
+        return Ok(Rect::new(0, 0, 0, 0));
+    }
+
+    let x = left.saturating_sub(padding);
+    let y = top.saturating_sub(padding);
+    let right = (right + padding).min(size.width);
+    let bottom = (bottom + padding).min(size.height);
+    Ok(Rect::new(x, y, right - x, bottom - y))
+}
+
+/// Crops the image to the bounds returned by [`trim_bounds`].
+pub fn trim(
+    img: NDimView,
+    background: Background,
+    padding: usize,
+) -> Result<(Rect, NDimImage), TrimError> {
+    let rect = trim_bounds(img, background, padding)?;
+    let cropped = img.crop(rect);
+    Ok((rect, cropped))
+}
+
+#[cfg(test)]
+mod tests {
+    use image_core::{NDimImage, Rect, Shape, Size};
+    use test_util::{
+        data::{read_abstract_transparent, read_at},
+        snap::ImageSnapshot,
+    };
+
+    use super::{trim, trim_bounds, Background, TrimError};
+
+    #[test]
+    fn alpha() {
+        let img: NDimImage = read_abstract_transparent().into();
+        let (rect, cropped) = trim(img.view(), Background::Alpha { threshold: 0.5 }, 0).unwrap();
+        assert_eq!(cropped.size(), rect.size());
+        cropped.snapshot("trim_alpha");
+
+        let padded = trim_bounds(img.view(), Background::Alpha { threshold: 0.5 }, 5).unwrap();
+        assert_eq!(padded.x, rect.x.saturating_sub(5));
+        assert_eq!(padded.right(), (rect.right() + 5).min(img.width()));
+
+        assert_eq!(
+            trim_bounds(img.view(), Background::Alpha { threshold: 2.0 }, 3),
+            Ok(Rect::new(0, 0, 0, 0))
+        );
+    }
+
+    #[test]
+    fn color() {
+        let img: NDimImage = read_at().into();
+        let white = Background::Color {
+            color: &[1.0],
+            tolerance: 0.1,
+        };
+        let (_, cropped) = trim(img.view(), white, 10).unwrap();
+        cropped.snapshot("trim_color_white_10");
+
+        let rows = [
+            "....", //
+            "..#.", //
+            ".#..", //
+            "....", //
+        ];
+        let data = rows
+            .iter()
+            .flat_map(|r| r.chars().map(|c| if c == '#' { 1.0 } else { 0.0 }))
+            .collect();
+        let img = NDimImage::new(Shape::from_size(Size::new(4, 4), 1), data);
+        let black = Background::Color {
+            color: &[0.0],
+            tolerance: 0.0,
+        };
+        assert_eq!(trim_bounds(img.view(), black, 0), Ok(Rect::new(1, 1, 2, 2)));
+        assert_eq!(trim_bounds(img.view(), black, 1), Ok(Rect::new(0, 0, 4, 4)));
+
+        assert_eq!(
+            trim_bounds(img.view(), Background::Alpha { threshold: 0.5 }, 0),
+            Err(TrimError::NoAlpha { channels: 1 })
+        );
+    }
+
+    #[test]
+    fn no_channels() {
+        let img = NDimImage::new(Shape::new(4, 3, 0), vec![]);
+        let background = Background::Color {
+            color: &[],
+            tolerance: 0.0,
+        };
+        assert_eq!(
+            trim_bounds(img.view(), background, 2),
+            Ok(Rect::new(0, 0, 0, 0))
+        );
+    }
+}
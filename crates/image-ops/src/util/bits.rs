@@ -14,7 +14,7 @@ const USIZE_BITS: usize = USIZE_BYTES * 8;
 impl FixedBits {
     pub fn new(bits: usize) -> Self {
         Self {
-            data: vec![0; div_ceil(bits, USIZE_BYTES)].into_boxed_slice(),
+            data: vec![0; div_ceil(bits, USIZE_BITS)].into_boxed_slice(),
             bits,
         }
     }
@@ -83,6 +83,46 @@ impl FixedBits {
         }
     }
 
+    pub fn not(&mut self) {
+        for a in self.data.iter_mut() {
+            *a = !*a;
+        }
+        self.fix_tail();
+    }
+
+    /// Returns a copy of the bits shifted such that `result[i] = self[i + offset]`.
+    /// Bits outside the range are false.
+    pub fn shifted(&self, offset: isize) -> Self {
+        let words = self.data.len() as isize;
+        let word_offset = offset.div_euclid(USIZE_BITS as isize);
+        let bit_offset = offset.rem_euclid(USIZE_BITS as isize) as usize;
+
+        let word = |i: isize| -> usize {
+            if (0..words).contains(&i) {
+                self.data[i as usize]
+            } else {
+                0
+            }
+        };
+
+        let mut result = Self {
+            data: (0..words)
+                .map(|i| {
+                    let low = word(i + word_offset);
+                    if bit_offset == 0 {
+                        low
+                    } else {
+                        let high = word(i + word_offset + 1);
+                        (low >> bit_offset) | (high << (USIZE_BITS - bit_offset))
+                    }
+                })
+                .collect(),
+            bits: self.bits,
+        };
+        result.fix_tail();
+        result
+    }
+
     pub fn expand_one(&mut self) {
         for part in self.data.iter_mut() {
             *part |= (*part >> 1) | (*part << 1)
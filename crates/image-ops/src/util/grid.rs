@@ -64,6 +64,13 @@ impl<const CELL_SIZE: usize> Grid<CELL_SIZE> {
         self.lines[y].set(x, value)
     }
 
+    pub fn line(&self, y: usize) -> &FixedBits {
+        &self.lines[y]
+    }
+    pub fn line_mut(&mut self, y: usize) -> &mut FixedBits {
+        &mut self.lines[y]
+    }
+
     pub fn fill_with_pixels(&mut self, f: impl Fn(usize, usize) -> bool) {
         let size = self.pixels();
         self.fill_cells(|_, cx, cy| {
@@ -148,6 +155,12 @@ impl<const CELL_SIZE: usize> Grid<CELL_SIZE> {
         }
     }
 
+    pub fn not(&mut self) {
+        for line in self.lines.iter_mut() {
+            line.not()
+        }
+    }
+
     pub fn expand_one(&mut self) {
         fn or_many(a: &mut FixedBits, b: &mut FixedBits) {
             a.or(b)
@@ -1,6 +1,9 @@
 use std::ops::{Deref, DerefMut};
 
-use image_core::{Image, Size};
+use image_core::{Image, ImageView, NDimImage, NDimView, Size};
+use rayon::prelude::*;
+
+use crate::border::BorderMode;
 
 pub fn from_const<P: Clone>(size: Size, c: P, out: Option<Image<P>>) -> Image<P> {
     if let Some(mut out) = out {
@@ -59,14 +62,80 @@ pub fn from_const_cow<P: Clone>(size: Size, c: P, out: Option<&mut Image<P>>) ->
 }
 
 pub fn from_image_cow<'a, P: Copy>(
-    img: &Image<P>,
+    img: ImageView<P>,
     out: Option<&'a mut Image<P>>,
 ) -> ImageCow<'a, P> {
     if let Some(out) = out {
         assert_eq!(out.size(), img.size());
-        out.data_mut().copy_from_slice(img.data());
+        out.view_mut().copy_from(img);
         ImageCow::Borrowed(out)
     } else {
-        ImageCow::Owned(img.clone())
+        ImageCow::Owned(img.into_owned())
+    }
+}
+
+/// Applies the given function to every row of the image. The function gets
+/// the row padded with `radius` pixels on the left and `radius + 1` pixels on
+/// the right, filled according to the border mode.
+pub fn filter_rows<P>(
+    img: ImageView<P>,
+    radius: usize,
+    border: BorderMode,
+    f: impl Fn(&[P], &mut [P]) + Sync,
+) -> Image<P>
+where
+    P: Copy + Default + Send + Sync,
+{
+    let size = img.size();
+    let mut data = vec![P::default(); size.len()];
+    if size.is_empty() {
+        return Image::new(size, data);
+    }
+
+    data.par_chunks_exact_mut(size.width)
+        .enumerate()
+        .for_each_init(Vec::new, |padded, (y, dst)| {
+            let src = img.row(y);
+            padded.clear();
+            padded.extend((0..size.width + 2 * radius + 1).map(|i| {
+                match border.map(i as isize - radius as isize, size.width) {
+                    Some(i) => src[i],
+                    None => P::default(),
+                }
+            }));
+            f(padded, dst);
+        });
+
+    Image::new(size, data)
+}
+
+/// De-interleaves the given channel of the image.
+pub fn channel_plane<T: Copy>(img: NDimView<T>, c: usize) -> Vec<T> {
+    let mut plane = Vec::with_capacity(img.size().len());
+    read_channel_plane(img, c, &mut plane);
+    plane
+}
+
+/// De-interleaves the given channel of the image into the given buffer,
+/// replacing its previous contents.
+pub fn read_channel_plane<T: Copy>(img: NDimView<T>, c: usize, plane: &mut Vec<T>) {
+    let channels = img.channels();
+    plane.clear();
+    for row in img.rows() {
+        plane.extend(row.iter().skip(c).step_by(channels));
+    }
+}
+
+/// Interleaves the given plane into the given channel of the image.
+pub fn set_channel_plane<T: Copy>(img: &mut NDimImage<T>, c: usize, plane: &[T]) {
+    let channels = img.channels();
+    for (d, s) in img
+        .data_mut()
+        .iter_mut()
+        .skip(c)
+        .step_by(channels)
+        .zip(plane)
+    {
+        *d = *s;
     }
 }
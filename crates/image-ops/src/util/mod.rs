@@ -2,6 +2,7 @@ mod bilinear;
 mod bits;
 mod grid;
 mod image;
+mod random;
 
 use std::ops::Range;
 
@@ -9,6 +10,7 @@ pub use bilinear::*;
 pub use bits::FixedBits;
 pub use grid::Grid;
 pub use image::*;
+pub use random::Random;
 
 #[inline(always)]
 pub const fn div_ceil(a: usize, b: usize) -> usize {
@@ -0,0 +1,376 @@
+use glam::{Affine2, Mat2, Mat3, Vec2, Vec3};
+use image_core::{Image, ImageView, Size};
+use rayon::prelude::*;
+
+use crate::scale::{Filter, FloatPixelFormat, PixelFormat};
+
+pub use crate::border::BorderMode;
+
+/// The size of the image produced by [`rotate`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RotationSize {
+    /// The rotated image has the same size as the source image. Corners
+    /// rotated outside of the image are cut off.
+    Crop,
+    /// The rotated image is large enough to contain the whole rotated source
+    /// image.
+    Expand,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum WarpError {
+    /// The transformation matrix is not invertible (or not finite).
+    NotInvertible,
+}
+
+/// Applies the given perspective transformation to the image.
+///
+/// `transform` maps homogeneous source coordinates to destination
+/// coordinates. Coordinates are in pixels, so `(0, 0)` is the top left corner
+/// of the top left pixel and `(width, height)` is the bottom right corner of
+/// the bottom right pixel.
+///
+/// Each destination pixel samples the source image at the position its center
+/// is mapped to. Unlike [`crate::scale::scale`], the filter is not widened
+/// when the transformation shrinks the image, so strong minification will
+/// alias.
+pub fn warp_perspective<P>(
+    img: ImageView<P>,
+    transform: Mat3,
+    size: Size,
+    filter: Filter,
+    border: BorderMode,
+) -> Result<Image<P>, WarpError>
+where
+    P: Copy + Default + Send + Sync,
+    FloatPixelFormat<P>: PixelFormat<InputPixel = P, OutputPixel = P>,
+{
+    let det = transform.determinant();
+    if det == 0.0 || !det.is_finite() {
+        return Err(WarpError::NotInvertible);
+    }
+
+    Ok(warp_inverse(img, transform.inverse(), size, filter, border))
+}
+
+/// Applies the given affine transformation to the image.
+///
+/// See [`warp_perspective`] for the coordinate system.
+pub fn warp_affine<P>(
+    img: ImageView<P>,
+    transform: Affine2,
+    size: Size,
+    filter: Filter,
+    border: BorderMode,
+) -> Result<Image<P>, WarpError>
+where
+    P: Copy + Default + Send + Sync,
+    FloatPixelFormat<P>: PixelFormat<InputPixel = P, OutputPixel = P>,
+{
+    warp_perspective(img, Mat3::from(transform), size, filter, border)
+}
+
+/// Rotates the image by the given angle (in radians, counter-clockwise)
+/// around its center.
+pub fn rotate<P>(
+    img: ImageView<P>,
+    angle: f32,
+    size: RotationSize,
+    filter: Filter,
+    border: BorderMode,
+) -> Image<P>
+where
+    P: Copy + Default + Send + Sync,
+    FloatPixelFormat<P>: PixelFormat<InputPixel = P, OutputPixel = P>,
+{
+    let src_size = img.size();
+    let (sin, cos) = angle.sin_cos();
+
+    let dst_size = match size {
+        RotationSize::Crop => src_size,
+        RotationSize::Expand => {
+            let w = src_size.width as f32;
+            let h = src_size.height as f32;
+            // the small epsilon prevents e.g. 90° rotations from adding an
+            // extra row/column due to rounding errors
+            let new_w = (w * cos.abs() + h * sin.abs() - 1e-3).ceil().max(0.0);
+            let new_h = (w * sin.abs() + h * cos.abs() - 1e-3).ceil().max(0.0);
+            Size::new(new_w as usize, new_h as usize)
+        }
+    };
+
+    let src_center = Vec2::new(src_size.width as f32, src_size.height as f32) / 2.0;
+    let dst_center = Vec2::new(dst_size.width as f32, dst_size.height as f32) / 2.0;
+
+    // Since the y axis points down, a counter-clockwise rotation on screen is
+    // a clockwise rotation in image coordinates. We directly construct the
+    // inverse, i.e. a rotation in the other direction.
+    let inverse = Affine2::from_translation(src_center)
+        * Affine2::from_mat2(Mat2::from_cols(Vec2::new(cos, sin), Vec2::new(-sin, cos)))
+        * Affine2::from_translation(-dst_center);
+
+    warp_inverse(img, Mat3::from(inverse), dst_size, filter, border)
+}
+
+/// The maximum number of taps per axis. The widest filter (MKS 2021) has a
+/// support of 4.5, so it needs at most 10 taps.
+const MAX_TAPS: usize = 16;
+
+struct Taps {
+    start: isize,
+    len: usize,
+    weights: [f32; MAX_TAPS],
+}
+
+impl Taps {
+    fn new(pos: f32, kernel: &dyn Fn(f32) -> f32, support: f32) -> Self {
+        // the position in pixel indexes, where pixel `i` has its center at `i`
+        let pos = pos - 0.5;
+
+        let start = (pos - support).ceil();
+        let end = (pos + support).floor();
+        let len = ((end - start) as usize + 1).min(MAX_TAPS);
+
+        let mut weights = [0.0; MAX_TAPS];
+        let mut sum = 0.0;
+        for (i, w) in weights[..len].iter_mut().enumerate() {
+            *w = kernel(start + i as f32 - pos);
+            sum += *w;
+        }
+
+        if sum == 0.0 {
+            // fall back to nearest neighbor
+            weights[0] = 1.0;
+            return Self {
+                start: pos.round() as isize,
+                len: 1,
+                weights,
+            };
+        }
+
+        weights[..len].iter_mut().for_each(|w| *w /= sum);
+        Self {
+            start: start as isize,
+            len,
+            weights,
+        }
+    }
+
+    fn iter(&self) -> impl Iterator<Item = (isize, f32)> + '_ {
+        self.weights[..self.len]
+            .iter()
+            .enumerate()
+            .map(move |(i, w)| (self.start + i as isize, *w))
+    }
+}
+
+/// Warps the image using a transformation that maps destination coordinates
+/// to source coordinates.
+fn warp_inverse<P>(
+    img: ImageView<P>,
+    inverse: Mat3,
+    size: Size,
+    filter: Filter,
+    border: BorderMode,
+) -> Image<P>
+where
+    P: Copy + Default + Send + Sync,
+    FloatPixelFormat<P>: PixelFormat<InputPixel = P, OutputPixel = P>,
+{
+    let mut data = vec![P::default(); size.len()];
+    if img.is_empty() || size.is_empty() {
+        return Image::new(size, data);
+    }
+
+    let src_size = img.size();
+    let format = FloatPixelFormat::<P>::default();
+    let (kernel, support) = filter.kernel();
+
+    let sample = |x: f32, y: f32| -> P {
+        if filter == Filter::Nearest {
+            let x = border.map(x.floor() as isize, src_size.width);
+            let y = border.map(y.floor() as isize, src_size.height);
+            return match (x, y) {
+                (Some(x), Some(y)) => img.row(y)[x],
+                _ => P::default(),
+            };
+        }
+
+        let taps_x = Taps::new(x, &*kernel, support);
+        let taps_y = Taps::new(y, &*kernel, support);
+
+        let mut acc = FloatPixelFormat::<P>::new_acc();
+        for (y, wy) in taps_y.iter() {
+            let Some(y) = border.map(y, src_size.height) else {
+                continue;
+            };
+            let row = img.row(y);
+
+            let mut row_acc = FloatPixelFormat::<P>::new_acc();
+            for (x, wx) in taps_x.iter() {
+                if let Some(x) = border.map(x, src_size.width) {
+                    format.add_pixel_scaled(&mut row_acc, row[x], wx);
+                }
+            }
+            FloatPixelFormat::<P>::add_acc_scaled(&mut acc, row_acc, wy);
+        }
+        format.acc_to_pixel(acc)
+    };
+
+    data.par_chunks_exact_mut(size.width)
+        .enumerate()
+        .for_each(|(y, row)| {
+            for (x, out) in row.iter_mut().enumerate() {
+                let p = inverse * Vec3::new(x as f32 + 0.5, y as f32 + 0.5, 1.0);
+                if p.z == 0.0 {
+                    continue;
+                }
+                let (sx, sy) = (p.x / p.z, p.y / p.z);
+                if !sx.is_finite() || !sy.is_finite() {
+                    continue;
+                }
+                // far away samples could overflow the index computations
+                const LIMIT: f32 = (1 << 24) as f32;
+                if sx.abs() > LIMIT || sy.abs() > LIMIT {
+                    if border != BorderMode::Transparent {
+                        *out = sample(sx.clamp(-LIMIT, LIMIT), sy.clamp(-LIMIT, LIMIT));
+                    }
+                    continue;
+                }
+                *out = sample(sx, sy);
+            }
+        });
+
+    Image::new(size, data)
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::{Affine2, Mat3, Vec2, Vec4};
+    use image_core::Size;
+    use test_util::{
+        data::{read_flower_transparent, read_portrait},
+        snap::ImageSnapshot,
+    };
+
+    use crate::scale::Filter;
+
+    use super::{rotate, warp_affine, warp_perspective, BorderMode, RotationSize};
+
+    #[test]
+    fn rotate_crop_and_expand() {
+        let original = read_flower_transparent();
+        let angle = 30f32.to_radians();
+
+        rotate(
+            original.view(),
+            angle,
+            RotationSize::Crop,
+            Filter::CubicCatrom,
+            BorderMode::Transparent,
+        )
+        .snapshot("rotate_30_crop_transparent");
+
+        let expanded = rotate(
+            original.view(),
+            angle,
+            RotationSize::Expand,
+            Filter::Linear,
+            BorderMode::Clamp,
+        );
+        assert!(expanded.width() > original.width());
+        assert!(expanded.height() > original.height());
+        expanded.snapshot("rotate_30_expand_clamp");
+    }
+
+    #[test]
+    fn rotate_90_is_exact() {
+        let original = read_portrait();
+        let rotated = rotate(
+            original.view(),
+            90f32.to_radians(),
+            RotationSize::Expand,
+            Filter::Nearest,
+            BorderMode::Transparent,
+        );
+
+        assert_eq!(
+            rotated.size(),
+            Size::new(original.height(), original.width())
+        );
+        for (x, y) in rotated.size().iter_pos() {
+            let expected = original.row(x)[original.width() - 1 - y];
+            assert_eq!(rotated.row(y)[x], expected);
+        }
+    }
+
+    #[test]
+    fn affine() {
+        let original = read_portrait();
+        let size = original.size();
+        let transform = Affine2::from_scale_angle_translation(
+            Vec2::new(0.8, 1.2),
+            0.2,
+            Vec2::new(size.width as f32 * 0.3, -(size.height as f32) * 0.1),
+        );
+
+        for (border, name) in [
+            (BorderMode::Reflect, "warp_affine_reflect"),
+            (BorderMode::Wrap, "warp_affine_wrap"),
+        ] {
+            warp_affine(original.view(), transform, size, Filter::Lanczos3, border)
+                .unwrap()
+                .snapshot(name);
+        }
+    }
+
+    #[test]
+    fn perspective() {
+        let original = read_flower_transparent();
+        let size = original.size();
+        let transform =
+            Mat3::from_cols_array_2d(&[[1.0, 0.1, 0.0004], [-0.2, 0.9, 0.0002], [20.0, 10.0, 1.0]]);
+
+        warp_perspective(
+            original.view(),
+            transform,
+            size,
+            Filter::CubicMitchell,
+            BorderMode::Transparent,
+        )
+        .unwrap()
+        .snapshot("warp_perspective_transparent");
+
+        assert!(warp_perspective::<Vec4>(
+            original.view(),
+            Mat3::ZERO,
+            size,
+            Filter::Linear,
+            BorderMode::Transparent,
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn identity() {
+        let original = read_portrait();
+        for filter in [
+            Filter::Nearest,
+            Filter::Linear,
+            Filter::CubicCatrom,
+            Filter::Lanczos3,
+        ] {
+            let warped = warp_affine(
+                original.view(),
+                Affine2::IDENTITY,
+                original.size(),
+                filter,
+                BorderMode::Clamp,
+            )
+            .unwrap();
+            for (a, b) in warped.data().iter().zip(original.data()) {
+                assert!((*a - *b).abs().max_element() < 1e-5, "{filter:?}");
+            }
+        }
+    }
+}